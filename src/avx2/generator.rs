@@ -6,8 +6,9 @@ use std::arch::x86_64::*;
 use crate::value::generator::ESCAPED;
 use std::io;
 
-#[inline(always)]
-pub unsafe fn write_str_simd<W>(
+#[target_feature(enable = "avx2")]
+#[inline]
+pub(crate) unsafe fn write_str_simd<W>(
     writer: &mut W,
     string: &mut &[u8],
     len: &mut usize,
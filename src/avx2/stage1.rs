@@ -16,6 +16,7 @@ struct SimdInput {
     hi: __m256i,
 }
 
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
 fn fill_input(ptr: &[u8]) -> SimdInput {
     unsafe {
         #[allow(clippy::cast_ptr_alignment)]
@@ -354,157 +355,215 @@ fn finalize_structurals(
 //WARN_UNUSED
 /*never_inline*/
 //#[inline(never)]
-impl<'de> Deserializer<'de> {
-    //#[inline(never)]
-    #[allow(clippy::cast_possible_truncation)]
-    pub unsafe fn find_structural_bits(input: &[u8]) -> std::result::Result<Vec<u32>, ErrorType> {
-        let len = input.len();
-        // 6 is a heuristic number to estimate it turns out a rate of 1/6 structural caracters lears
-        // almost never to relocations.
-        let mut structural_indexes = Vec::with_capacity(len / 6);
-        structural_indexes.push(0); // push extra root element
-
-        let mut has_error: __m256i = _mm256_setzero_si256();
-        let mut previous = AvxProcessedUtfBytes::default();
-        // we have padded the input out to 64 byte multiple with the remainder being
-        // zeros
-
-        // persistent state across loop
-        // does the last iteration end with an odd-length sequence of backslashes?
-        // either 0 or 1, but a 64-bit value
-        let mut prev_iter_ends_odd_backslash: u64 = 0;
-        // does the previous iteration end inside a double-quote pair?
-        let mut prev_iter_inside_quote: u64 = 0;
-        // either all zeros or all ones
-        // does the previous iteration end on something that is a predecessor of a
-        // pseudo-structural character - i.e. whitespace or a structural character
-        // effectively the very first char is considered to follow "whitespace" for
-        // the
-        // purposes of pseudo-structural character detection so we initialize to 1
-        let mut prev_iter_ends_pseudo_pred: u64 = 1;
-
-        // structurals are persistent state across loop as we flatten them on the
-        // subsequent iteration into our array pointed to be base_ptr.
-        // This is harmless on the first iteration as structurals==0
-        // and is done for performance reasons; we can hide some of the latency of the
-        // expensive carryless multiply in the previous step with this work
-        let mut structurals: u64 = 0;
+#[target_feature(enable = "avx2", enable = "pclmulqdq")]
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) unsafe fn find_structural_bits(
+    input: &[u8],
+    structural_indexes: &mut Vec<u32>,
+) -> std::result::Result<(), ErrorType> {
+    let len = input.len();
+    // 6 is a heuristic number to estimate it turns out a rate of 1/6 structural caracters lears
+    // almost never to relocations.
+    structural_indexes.clear();
+    structural_indexes.reserve(len / 6);
+    structural_indexes.push(0); // push extra root element
+
+    let mut has_error: __m256i = _mm256_setzero_si256();
+    let mut previous = AvxProcessedUtfBytes::default();
+    // we have padded the input out to 64 byte multiple with the remainder being
+    // zeros
+
+    // persistent state across loop
+    // does the last iteration end with an odd-length sequence of backslashes?
+    // either 0 or 1, but a 64-bit value
+    let mut prev_iter_ends_odd_backslash: u64 = 0;
+    // does the previous iteration end inside a double-quote pair?
+    let mut prev_iter_inside_quote: u64 = 0;
+    // either all zeros or all ones
+    // does the previous iteration end on something that is a predecessor of a
+    // pseudo-structural character - i.e. whitespace or a structural character
+    // effectively the very first char is considered to follow "whitespace" for
+    // the
+    // purposes of pseudo-structural character detection so we initialize to 1
+    let mut prev_iter_ends_pseudo_pred: u64 = 1;
+
+    // structurals are persistent state across loop as we flatten them on the
+    // subsequent iteration into our array pointed to be base_ptr.
+    // This is harmless on the first iteration as structurals==0
+    // and is done for performance reasons; we can hide some of the latency of the
+    // expensive carryless multiply in the previous step with this work
+    let mut structurals: u64 = 0;
+
+    let lenminus64: usize = if len < 64 { 0 } else { len as usize - 64 };
+    let mut idx: usize = 0;
+    let mut error_mask: u64 = 0; // for unescaped characters within strings (ASCII code points < 0x20)
+
+    while idx < lenminus64 {
+        /*
+        #ifndef _MSC_VER
+          __builtin_prefetch(buf + idx + 128);
+        #endif
+         */
+        let input: SimdInput = fill_input(input.get_unchecked(idx as usize..));
+        check_utf8(&input, &mut has_error, &mut previous);
+        // detect odd sequences of backslashes
+        let odd_ends: u64 = find_odd_backslash_sequences(&input, &mut prev_iter_ends_odd_backslash);
+
+        // detect insides of quote pairs ("quote_mask") and also our quote_bits
+        // themselves
+        let mut quote_bits: u64 = 0;
+        let quote_mask: u64 = find_quote_mask_and_bits(
+            &input,
+            odd_ends,
+            &mut prev_iter_inside_quote,
+            &mut quote_bits,
+            &mut error_mask,
+        );
 
-        let lenminus64: usize = if len < 64 { 0 } else { len as usize - 64 };
-        let mut idx: usize = 0;
-        let mut error_mask: u64 = 0; // for unescaped characters within strings (ASCII code points < 0x20)
-
-        while idx < lenminus64 {
-            /*
-            #ifndef _MSC_VER
-              __builtin_prefetch(buf + idx + 128);
-            #endif
-             */
-            let input: SimdInput = fill_input(input.get_unchecked(idx as usize..));
-            check_utf8(&input, &mut has_error, &mut previous);
-            // detect odd sequences of backslashes
-            let odd_ends: u64 =
-                find_odd_backslash_sequences(&input, &mut prev_iter_ends_odd_backslash);
-
-            // detect insides of quote pairs ("quote_mask") and also our quote_bits
-            // themselves
-            let mut quote_bits: u64 = 0;
-            let quote_mask: u64 = find_quote_mask_and_bits(
-                &input,
-                odd_ends,
-                &mut prev_iter_inside_quote,
-                &mut quote_bits,
-                &mut error_mask,
-            );
-
-            // take the previous iterations structural bits, not our current iteration,
-            // and flatten
-            #[allow(clippy::cast_possible_truncation)]
-            flatten_bits(&mut structural_indexes, idx as u32, structurals);
-
-            let mut whitespace: u64 = 0;
-            find_whitespace_and_structurals(&input, &mut whitespace, &mut structurals);
-
-            // fixup structurals to reflect quotes and add pseudo-structural characters
-            structurals = finalize_structurals(
-                structurals,
-                whitespace,
-                quote_mask,
-                quote_bits,
-                &mut prev_iter_ends_pseudo_pred,
-            );
-            idx += 64;
-        }
+        // take the previous iterations structural bits, not our current iteration,
+        // and flatten
+        #[allow(clippy::cast_possible_truncation)]
+        flatten_bits(structural_indexes, idx as u32, structurals);
+
+        let mut whitespace: u64 = 0;
+        find_whitespace_and_structurals(&input, &mut whitespace, &mut structurals);
+
+        // fixup structurals to reflect quotes and add pseudo-structural characters
+        structurals = finalize_structurals(
+            structurals,
+            whitespace,
+            quote_mask,
+            quote_bits,
+            &mut prev_iter_ends_pseudo_pred,
+        );
+        idx += 64;
+    }
 
-        // we use a giant copy-paste which is ugly.
-        // but otherwise the string needs to be properly padded or else we
-        // risk invalidating the UTF-8 checks.
-        if idx < len {
-            let mut tmpbuf: [u8; 64] = [0x20; 64];
-            tmpbuf
-                .as_mut_ptr()
-                .copy_from(input.as_ptr().add(idx), len as usize - idx);
-            let input: SimdInput = fill_input(&tmpbuf);
-
-            check_utf8(&input, &mut has_error, &mut previous);
-
-            // detect odd sequences of backslashes
-            let odd_ends: u64 =
-                find_odd_backslash_sequences(&input, &mut prev_iter_ends_odd_backslash);
-
-            // detect insides of quote pairs ("quote_mask") and also our quote_bits
-            // themselves
-            let mut quote_bits: u64 = 0;
-            let quote_mask: u64 = find_quote_mask_and_bits(
-                &input,
-                odd_ends,
-                &mut prev_iter_inside_quote,
-                &mut quote_bits,
-                &mut error_mask,
-            );
-
-            // take the previous iterations structural bits, not our current iteration,
-            // and flatten
-            flatten_bits(&mut structural_indexes, idx as u32, structurals);
-
-            let mut whitespace: u64 = 0;
-            find_whitespace_and_structurals(&input, &mut whitespace, &mut structurals);
-
-            // fixup structurals to reflect quotes and add pseudo-structural characters
-            structurals = finalize_structurals(
-                structurals,
-                whitespace,
-                quote_mask,
-                quote_bits,
-                &mut prev_iter_ends_pseudo_pred,
-            );
-            idx += 64;
-        }
-        // This test isn't in upstream, for some reason the error mask is et for then.
-        if prev_iter_inside_quote != 0 {
-            return Err(ErrorType::Syntax);
-        }
-        // finally, flatten out the remaining structurals from the last iteration
-        flatten_bits(&mut structural_indexes, idx as u32, structurals);
+    // we use a giant copy-paste which is ugly.
+    // but otherwise the string needs to be properly padded or else we
+    // risk invalidating the UTF-8 checks.
+    if idx < len {
+        let mut tmpbuf: [u8; 64] = [0x20; 64];
+        tmpbuf
+            .as_mut_ptr()
+            .copy_from(input.as_ptr().add(idx), len as usize - idx);
+        let input: SimdInput = fill_input(&tmpbuf);
+
+        check_utf8(&input, &mut has_error, &mut previous);
+
+        // detect odd sequences of backslashes
+        let odd_ends: u64 = find_odd_backslash_sequences(&input, &mut prev_iter_ends_odd_backslash);
+
+        // detect insides of quote pairs ("quote_mask") and also our quote_bits
+        // themselves
+        let mut quote_bits: u64 = 0;
+        let quote_mask: u64 = find_quote_mask_and_bits(
+            &input,
+            odd_ends,
+            &mut prev_iter_inside_quote,
+            &mut quote_bits,
+            &mut error_mask,
+        );
 
-        // a valid JSON file cannot have zero structural indexes - we should have
-        // found something (note that we compare to 1 as we always add the root!)
-        if structural_indexes.len() == 1 {
-            return Err(ErrorType::EOF);
-        }
+        // take the previous iterations structural bits, not our current iteration,
+        // and flatten
+        flatten_bits(structural_indexes, idx as u32, structurals);
 
-        if structural_indexes.last() > Some(&(len as u32)) {
-            return Err(ErrorType::InternalError);
-        }
+        let mut whitespace: u64 = 0;
+        find_whitespace_and_structurals(&input, &mut whitespace, &mut structurals);
 
-        if error_mask != 0 {
-            return Err(ErrorType::Syntax);
-        }
+        // fixup structurals to reflect quotes and add pseudo-structural characters
+        structurals = finalize_structurals(
+            structurals,
+            whitespace,
+            quote_mask,
+            quote_bits,
+            &mut prev_iter_ends_pseudo_pred,
+        );
+        idx += 64;
+    }
+    // This test isn't in upstream, for some reason the error mask is et for then.
+    if prev_iter_inside_quote != 0 {
+        return Err(ErrorType::Syntax);
+    }
+    // finally, flatten out the remaining structurals from the last iteration
+    flatten_bits(structural_indexes, idx as u32, structurals);
 
-        if _mm256_testz_si256(has_error, has_error) == 0 {
-            Err(ErrorType::InvalidUTF8)
+    // a valid JSON file cannot have zero structural indexes - we should have
+    // found something (note that we compare to 1 as we always add the root!)
+    if structural_indexes.len() == 1 {
+        return Err(ErrorType::EOF);
+    }
+
+    if structural_indexes.last() > Some(&(len as u32)) {
+        return Err(ErrorType::InternalError);
+    }
+
+    if error_mask != 0 {
+        return Err(ErrorType::Syntax);
+    }
+
+    if _mm256_testz_si256(has_error, has_error) == 0 {
+        Err(ErrorType::InvalidUTF8)
+    } else {
+        Ok(())
+    }
+}
+
+/// Same as `find_structural_bits` but instead of flattening the structurals
+/// it pushes a mask per 64 byte block to `whitespace`, with the bits of the
+/// whitespace outside of strings set.
+#[target_feature(enable = "avx2", enable = "pclmulqdq")]
+pub(crate) unsafe fn find_whitespace_bits(
+    input: &[u8],
+    whitespace: &mut Vec<u64>,
+) -> std::result::Result<(), ErrorType> {
+    let len = input.len();
+    whitespace.clear();
+    whitespace.reserve(len / 64 + 1);
+
+    let mut has_error: __m256i = _mm256_setzero_si256();
+    let mut previous = AvxProcessedUtfBytes::default();
+    let mut prev_iter_ends_odd_backslash: u64 = 0;
+    let mut prev_iter_inside_quote: u64 = 0;
+    let mut error_mask: u64 = 0; // for unescaped characters within strings (ASCII code points < 0x20)
+
+    // the last block is padded with spaces, they are dropped like any other
+    // whitespace
+    let mut tmpbuf: [u8; 64] = [0x20; 64];
+
+    let mut idx: usize = 0;
+    while idx < len {
+        let input: SimdInput = if len - idx >= 64 {
+            fill_input(input.get_unchecked(idx..))
         } else {
-            Ok(structural_indexes)
-        }
+            tmpbuf
+                .as_mut_ptr()
+                .copy_from(input.as_ptr().add(idx), len - idx);
+            fill_input(&tmpbuf)
+        };
+        check_utf8(&input, &mut has_error, &mut previous);
+        let odd_ends: u64 = find_odd_backslash_sequences(&input, &mut prev_iter_ends_odd_backslash);
+        let mut quote_bits: u64 = 0;
+        let quote_mask: u64 = find_quote_mask_and_bits(
+            &input,
+            odd_ends,
+            &mut prev_iter_inside_quote,
+            &mut quote_bits,
+            &mut error_mask,
+        );
+        let mut ws: u64 = 0;
+        let mut structurals: u64 = 0;
+        find_whitespace_and_structurals(&input, &mut ws, &mut structurals);
+        whitespace.push(ws & !quote_mask);
+        idx += 64;
+    }
+    if prev_iter_inside_quote != 0 || error_mask != 0 {
+        return Err(ErrorType::Syntax);
+    }
+    if _mm256_testz_si256(has_error, has_error) == 0 {
+        Err(ErrorType::InvalidUTF8)
+    } else {
+        Ok(())
     }
 }
@@ -24,18 +24,18 @@ use std::arch::x86_64::*;
 // all byte values must be no larger than 0xF4
 
 /*****************************/
-#[cfg_attr(not(feature = "no-inline"), inline)]
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
 fn push_last_byte_of_a_to_b(a: __m256i, b: __m256i) -> __m256i {
     unsafe { _mm256_alignr_epi8(b, _mm256_permute2x128_si256(a, b, 0x21), 15) }
 }
 
-#[cfg_attr(not(feature = "no-inline"), inline)]
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
 fn push_last_2bytes_of_a_to_b(a: __m256i, b: __m256i) -> __m256i {
     unsafe { _mm256_alignr_epi8(b, _mm256_permute2x128_si256(a, b, 0x21), 14) }
 }
 
 // all byte values must be no larger than 0xF4
-#[cfg_attr(not(feature = "no-inline"), inline)]
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
 fn avxcheck_smaller_than_0xf4(current_bytes: __m256i, has_error: &mut __m256i) {
     // unsigned, saturates to 0 below max
     *has_error = unsafe {
@@ -46,7 +46,7 @@ fn avxcheck_smaller_than_0xf4(current_bytes: __m256i, has_error: &mut __m256i) {
     };
 }
 
-#[cfg_attr(not(feature = "no-inline"), inline)]
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
 fn avxcontinuation_lengths(high_nibbles: __m256i) -> __m256i {
     unsafe {
         _mm256_shuffle_epi8(
@@ -67,7 +67,7 @@ fn avxcontinuation_lengths(high_nibbles: __m256i) -> __m256i {
     }
 }
 
-#[cfg_attr(not(feature = "no-inline"), inline)]
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
 fn avxcarry_continuations(initial_lengths: __m256i, previous_carries: __m256i) -> __m256i {
     unsafe {
         let right1: __m256i = _mm256_subs_epu8(
@@ -83,7 +83,7 @@ fn avxcarry_continuations(initial_lengths: __m256i, previous_carries: __m256i) -
     }
 }
 
-#[cfg_attr(not(feature = "no-inline"), inline)]
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
 fn avxcheck_continuations(initial_lengths: __m256i, carries: __m256i, has_error: &mut __m256i) {
     // overlap || underlap
     // carry > length && length > 0 || !(carry > length) && !(length > 0)
@@ -101,7 +101,7 @@ fn avxcheck_continuations(initial_lengths: __m256i, carries: __m256i, has_error:
 // when 0xED is found, next byte must be no larger than 0x9F
 // when 0xF4 is found, next byte must be no larger than 0x8F
 // next byte must be continuation, ie sign bit is set, so signed < is ok
-#[cfg_attr(not(feature = "no-inline"), inline)]
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
 fn avxcheck_first_continuation_max(
     current_bytes: __m256i,
     off1_current_bytes: __m256i,
@@ -136,7 +136,7 @@ fn avxcheck_first_continuation_max(
 // E       => < E1 && < A0
 // F       => < F1 && < 90
 // else      false && false
-#[cfg_attr(not(feature = "no-inline"), inline)]
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
 fn avxcheck_overlong(
     current_bytes: __m256i,
     off1_current_bytes: __m256i,
@@ -235,7 +235,7 @@ pub(crate) struct AvxProcessedUtfBytes {
 }
 
 impl Default for AvxProcessedUtfBytes {
-    #[cfg_attr(not(feature = "no-inline"), inline)]
+    #[cfg_attr(not(feature = "no-inline"), inline(always))]
     fn default() -> Self {
         unsafe {
             Self {
@@ -247,7 +247,7 @@ impl Default for AvxProcessedUtfBytes {
     }
 }
 
-#[cfg_attr(not(feature = "no-inline"), inline)]
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
 fn avx_count_nibbles(bytes: __m256i, answer: &mut AvxProcessedUtfBytes) {
     answer.rawbytes = bytes;
     answer.high_nibbles =
@@ -256,7 +256,7 @@ fn avx_count_nibbles(bytes: __m256i, answer: &mut AvxProcessedUtfBytes) {
 
 // check whether the current bytes are valid UTF-8
 // at the end of the function, previous gets updated
-#[cfg_attr(not(feature = "no-inline"), inline)]
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
 pub(crate) fn avxcheck_utf8_bytes(
     current_bytes: __m256i,
     previous: &AvxProcessedUtfBytes,
@@ -1,7 +1,39 @@
+use std::convert::TryFrom;
 use std::fmt;
+use std::io;
+use std::str;
+
+/// Broad category of an error, see `Error::classify`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    /// Reading the input failed
+    Io,
+    /// The input isn't valid JSON
+    Syntax,
+    /// The input is valid JSON but doesn't match what was expected, for
+    /// example the wrong type when deserializing into a struct
+    Data,
+    /// The input ended before the JSON did
+    Eof,
+    /// The parser failed for reasons that aren't the fault of the input
+    Internal,
+}
+
+/// A limit from `Limits` that was exceeded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    /// `max_depth`
+    Depth,
+    /// `max_document_size`
+    DocumentSize,
+    /// `max_string_length`
+    StringLength,
+    /// `max_elements`
+    Elements,
+}
 
 /// Error types encountered while parsing
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ErrorType {
     /// The key of a map isn't a string
     BadKeyType,
@@ -53,6 +85,8 @@ pub enum ErrorType {
     InvlaidUnicodeCodepoint,
     /// Object Key isn't a string
     KeyMustBeAString,
+    /// The object has no field with the requested key
+    NoSuchField,
     /// Non structural character
     NoStructure,
     /// Parser Erropr
@@ -79,46 +113,289 @@ pub enum ErrorType {
     ExpectedObjectKey,
     /// Overflow of a limited buffer
     Overflow,
+    /// Reading the input failed
+    Io(io::ErrorKind, String),
+    /// The document exceeds one of the configured limits
+    LimitExceeded(Limit),
+}
+
+/// Number of bytes shown on either side of the error in the snippet
+const SNIPPET_CONTEXT: usize = 32;
+
+/// Where in the input an error was encountered
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Position {
+    line: usize,
+    column: usize,
+    /// Part of the line around the error
+    snippet: String,
+    /// Column of the error in `snippet`
+    caret: usize,
+}
+
+impl Position {
+    fn new(input: &[u8], index: usize) -> Self {
+        let index = index.min(input.len());
+        let line_start = input[..index]
+            .iter()
+            .rposition(|c| *c == b'\n')
+            .map_or(0, |p| p + 1);
+        let line_end = input[index..]
+            .iter()
+            .position(|c| *c == b'\n')
+            .map_or(input.len(), |p| index + p);
+        let line = input[..line_start].iter().filter(|c| **c == b'\n').count() + 1;
+
+        let mut start = index.saturating_sub(SNIPPET_CONTEXT).max(line_start);
+        while start < index && is_continuation(input[start]) {
+            start += 1;
+        }
+        let end = (index + SNIPPET_CONTEXT).min(line_end);
+        let snippet = String::from_utf8_lossy(&input[start..end])
+            .trim_end_matches('\r')
+            .replace('\t', " ");
+        Self {
+            line,
+            column: count_chars(&input[line_start..index]) + 1,
+            snippet,
+            caret: count_chars(&input[start..index]),
+        }
+    }
+}
+
+fn is_continuation(c: u8) -> bool {
+    c & 0xC0 == 0x80
+}
+
+fn count_chars(s: &[u8]) -> usize {
+    s.iter().filter(|c| !is_continuation(**c)).count()
+}
+
+/// Decodes the UTF-8 character starting at `index`
+fn char_at(input: &[u8], index: usize) -> Option<char> {
+    if is_continuation(*input.get(index)?) {
+        return None;
+    }
+    let c = &input[index..(index + 4).min(input.len())];
+    let c = match str::from_utf8(c) {
+        Ok(c) => c,
+        Err(e) => str::from_utf8(&c[..e.valid_up_to()]).ok()?,
+    };
+    c.chars().next()
 }
 
 /// Parser error
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Error {
-    /// Strucutral the error was encountered at
-    structural: usize,
+    /// Strucutral the error was encountered at, structural indexes are
+    /// `u32` so this keeps `Error` small
+    structural: u32,
     /// Byte index it was encountered at
     index: usize,
-    /// Current character
-    character: char,
+    /// Current character, the outer `None` is for errors that aren't tied
+    /// to a place in the input and don't get a position
+    character: Option<Option<char>>,
     /// Tyep of error
     error: ErrorType,
+    /// Line, column and snippet, only computed once the error is handed
+    /// to the caller
+    position: Option<Box<Position>>,
+}
+
+/// Structural indexes are `u32`, so this never saturates for one that
+/// came from the parser
+fn structural_index(structural: usize) -> u32 {
+    u32::try_from(structural).unwrap_or(u32::max_value())
 }
 
 impl Error {
-    pub(crate) fn new(structural: usize, index: usize, character: char, error: ErrorType) -> Self {
+    #[cold]
+    pub(crate) fn new(structural: usize, index: usize, input: &[u8], error: ErrorType) -> Self {
         Self {
-            structural,
+            structural: structural_index(structural),
             index,
-            character,
+            character: Some(char_at(input, index)),
             error,
+            position: None,
         }
     }
+    /// Computes the line, column and snippet of the error. This scans the
+    /// input up to the error so it is only done for errors that are
+    /// returned to the caller and not for those serde discards.
+    #[cold]
+    pub(crate) fn locate(mut self, input: &[u8]) -> Self {
+        if self.character.is_some() && self.position.is_none() {
+            self.position = Some(Box::new(Position::new(input, self.index)));
+        }
+        self
+    }
+    /// Same as `locate` but errors that aren't tied to a place in the
+    /// input yet, like the ones serde creates, are placed at `index`.
+    #[cold]
+    pub(crate) fn locate_at(mut self, structural: usize, index: usize, input: &[u8]) -> Self {
+        if self.character.is_none() {
+            self.structural = structural_index(structural);
+            self.index = index;
+            self.character = Some(char_at(input, index));
+        }
+        self.locate(input)
+    }
+    fn position(&self) -> Option<&Position> {
+        self.position.as_deref()
+    }
     pub(crate) fn generic(t: ErrorType) -> Self {
         Self {
             structural: 0,
             index: 0,
-            character: '💩', //this is the poop emoji
+            character: None,
             error: t,
+            position: None,
+        }
+    }
+
+    /// Byte index in the input the error was encountered at
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// What went wrong
+    pub fn error_type(&self) -> &ErrorType {
+        &self.error
+    }
+
+    /// Categorizes the error, this allows telling apart invalid input from
+    /// failures that aren't the fault of the input.
+    pub fn classify(&self) -> Category {
+        match self.error {
+            ErrorType::Io(..) => Category::Io,
+            ErrorType::EarlyEnd
+            | ErrorType::EOF
+            | ErrorType::UnexpectedEnd
+            | ErrorType::UnterminatedString => Category::Eof,
+            ErrorType::BadKeyType
+            | ErrorType::ExpectedArray
+            | ErrorType::ExpectedBoolean
+            | ErrorType::ExpectedEnum
+            | ErrorType::ExpectedFloat
+            | ErrorType::ExpectedInteger
+            | ErrorType::ExpectedMap
+            | ErrorType::ExpectedNull
+            | ErrorType::ExpectedNumber
+            | ErrorType::ExpectedSigned
+            | ErrorType::ExpectedString
+            | ErrorType::ExpectedUnsigned
+            | ErrorType::KeyMustBeAString
+            | ErrorType::NoSuchField
+            | ErrorType::Overflow
+            | ErrorType::LimitExceeded(_)
+            | ErrorType::Serde(_) => Category::Data,
+            ErrorType::ExpectedArrayComma
+            | ErrorType::ExpectedObjectColon
+            | ErrorType::ExpectedMapComma
+            | ErrorType::ExpectedMapEnd
+            | ErrorType::InvalidEscape
+            | ErrorType::InvalidExponent
+            | ErrorType::InvalidNumber
+            | ErrorType::InvalidUTF8
+            | ErrorType::InvalidUnicodeEscape
+            | ErrorType::InvlaidUnicodeCodepoint
+            | ErrorType::NoStructure
+            | ErrorType::Parser
+            | ErrorType::Syntax
+            | ErrorType::TrailingCharacters
+            | ErrorType::UnexpectedCharacter
+            | ErrorType::ExpectedArrayContent
+            | ErrorType::ExpectedObjectContent
+            | ErrorType::ExpectedObjectKey => Category::Syntax,
+            ErrorType::InternalError => Category::Internal,
         }
     }
+
+    /// The input couldn't be read
+    pub fn is_io(&self) -> bool {
+        self.classify() == Category::Io
+    }
+
+    /// The input isn't valid JSON
+    pub fn is_syntax(&self) -> bool {
+        self.classify() == Category::Syntax
+    }
+
+    /// The input doesn't match what was expected
+    pub fn is_data(&self) -> bool {
+        self.classify() == Category::Data
+    }
+
+    /// The input ended early
+    pub fn is_eof(&self) -> bool {
+        self.classify() == Category::Eof
+    }
+
+    /// The parser failed for reasons that aren't the fault of the input
+    pub fn is_internal(&self) -> bool {
+        self.classify() == Category::Internal
+    }
+
+    /// Line the error was encountered at, starting at 1
+    pub fn line(&self) -> Option<usize> {
+        self.position().map(|p| p.line)
+    }
+
+    /// Column the error was encountered at in characters, starting at 1
+    pub fn column(&self) -> Option<usize> {
+        self.position().map(|p| p.column)
+    }
+
+    /// Character the error was encountered at, if there is one
+    pub fn character(&self) -> Option<char> {
+        self.character.flatten()
+    }
+
+    /// Part of the input line surrounding the error
+    pub fn snippet(&self) -> Option<&str> {
+        self.position().map(|p| p.snippet.as_str())
+    }
 }
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{:?} at character {} ('{}')",
-            self.error, self.index, self.character
-        )
+        if let Some(p) = self.position() {
+            write!(
+                f,
+                "{:?} at line {}, column {} (character {}",
+                self.error, p.line, p.column, self.index
+            )?;
+            if let Some(Some(c)) = self.character {
+                write!(f, ", {:?}", c)?;
+            }
+            write!(f, ")\n{}\n{:>w$}", p.snippet, "^", w = p.caret + 1)
+        } else if let Some(character) = self.character {
+            write!(f, "{:?} at character {}", self.error, self.index)?;
+            if let Some(c) = character {
+                write!(f, " ({:?})", c)?;
+            }
+            Ok(())
+        } else {
+            write!(f, "{:?}", self.error)
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::generic(ErrorType::Io(e.kind(), e.to_string()))
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        match e.error {
+            ErrorType::Io(kind, msg) => Self::new(kind, msg),
+            _ if e.is_eof() => Self::new(io::ErrorKind::UnexpectedEof, e),
+            _ if e.is_internal() => Self::new(io::ErrorKind::Other, e),
+            _ => Self::new(io::ErrorKind::InvalidData, e),
+        }
     }
 }
 
@@ -128,9 +405,127 @@ mod test {
     #[test]
     fn fmt() {
         let e = Error::generic(ErrorType::InternalError);
+        assert_eq!(format!("{}", e), "InternalError")
+    }
+
+    #[test]
+    fn classify() {
+        let e = Error::new(0, 1, b"[}", ErrorType::ExpectedArrayContent);
+        assert_eq!(e.classify(), Category::Syntax);
+        assert_eq!(e.index(), 1);
+        assert_eq!(e.error_type(), &ErrorType::ExpectedArrayContent);
+        assert!(Error::generic(ErrorType::ExpectedString).is_data());
+        assert!(Error::generic(ErrorType::EOF).is_eof());
+        assert!(Error::generic(ErrorType::InternalError).is_internal());
+        assert!(!Error::generic(ErrorType::InternalError).is_syntax());
+        assert_eq!(e.clone(), e);
+    }
+
+    #[test]
+    fn malformed_input_is_syntax() {
+        for (d, t) in &[
+            ("[1,]", ErrorType::ExpectedArrayContent),
+            ("[1,,2]", ErrorType::ExpectedArrayContent),
+            ("[1 2]", ErrorType::ExpectedArrayComma),
+            ("[1]x", ErrorType::TrailingCharacters),
+            ("[]]", ErrorType::TrailingCharacters),
+            (r#"{"a": ]}"#, ErrorType::ExpectedObjectContent),
+            ("]", ErrorType::UnexpectedCharacter),
+        ] {
+            let mut v = d.as_bytes().to_vec();
+            let e = crate::to_owned_value(&mut v).expect_err(d);
+            assert!(e.is_syntax(), "{}", d);
+            assert_eq!(e.error_type(), t, "{}", d);
+            let e = crate::validate(d.as_bytes()).expect_err(d);
+            assert!(e.is_syntax(), "{}", d);
+        }
+    }
+
+    #[test]
+    fn size() {
+        // Errors are part of every `Result` so the position is boxed, the
+        // rest is the error type and where it was encountered
+        assert!(std::mem::size_of::<Error>() <= 56);
+        assert!(std::mem::size_of::<crate::Result<crate::BorrowedValue>>() <= 56);
+    }
+
+    #[test]
+    fn io() {
+        let e = Error::from(io::Error::new(io::ErrorKind::BrokenPipe, "snot"));
+        assert!(e.is_io());
+        let e = io::Error::from(e);
+        assert_eq!(e.kind(), io::ErrorKind::BrokenPipe);
+        assert_eq!(e.to_string(), "snot");
+
+        let e = io::Error::from(Error::generic(ErrorType::UnexpectedEnd));
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+        let e = io::Error::from(Error::generic(ErrorType::InternalError));
+        assert_eq!(e.kind(), io::ErrorKind::Other);
+        let e = io::Error::from(Error::new(0, 1, b"[}", ErrorType::ExpectedArrayContent));
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        let e = e.into_inner().expect("no inner error");
+        assert_eq!(
+            e.downcast_ref::<Error>().map(Error::classify),
+            Some(Category::Syntax)
+        );
+    }
+
+    #[test]
+    fn position() {
+        let input = b"{\n  \"a\": 1,\n  \"\xc3\xa9\": ]\n}";
+        let e = Error::new(5, 20, input, ErrorType::UnexpectedCharacter);
+        assert_eq!(e.line(), None);
+        assert_eq!(
+            format!("{}", e),
+            "UnexpectedCharacter at character 20 (']')"
+        );
+        let e = e.locate(input);
+        assert_eq!(e.line(), Some(3));
+        assert_eq!(e.column(), Some(8));
+        assert_eq!(e.character(), Some(']'));
+        assert_eq!(e.snippet(), Some("  \"\u{e9}\": ]"));
         assert_eq!(
             format!("{}", e),
-            "InternalError at character 0 ('\u{1f4a9}')"
-        )
+            "UnexpectedCharacter at line 3, column 8 (character 20, ']')\n  \"\u{e9}\": ]\n       ^"
+        );
+    }
+
+    #[test]
+    fn position_end() {
+        let e = Error::new(0, 3, b"[1,", ErrorType::EOF).locate(b"[1,");
+        assert_eq!(e.line(), Some(1));
+        assert_eq!(e.column(), Some(4));
+        assert_eq!(e.character(), None);
+        assert_eq!(
+            format!("{}", e),
+            "EOF at line 1, column 4 (character 3)\n[1,\n   ^"
+        );
+    }
+
+    #[test]
+    fn snippet_is_cut() {
+        let mut input = vec![b' '; 100];
+        input[60] = b'x';
+        let e = Error::new(0, 60, &input, ErrorType::Syntax).locate(&input);
+        assert_eq!(e.column(), Some(61));
+        assert_eq!(e.snippet().map(str::len), Some(64));
+        assert!(format!("{}", e).ends_with(&format!("\n{}^", " ".repeat(32))));
+    }
+
+    #[test]
+    fn character() {
+        let input = "[\"a\", \u{e9}, \u{1f600}]".as_bytes();
+        let e = Error::new(0, 6, input, ErrorType::UnexpectedCharacter);
+        assert_eq!(e.character(), Some('\u{e9}'));
+        let e = Error::new(0, 10, input, ErrorType::UnexpectedCharacter);
+        assert_eq!(e.character(), Some('\u{1f600}'));
+        // Not at the start of a character
+        let e = Error::new(0, 7, input, ErrorType::UnexpectedCharacter);
+        assert_eq!(e.character(), None);
+        let e = Error::new(0, 1, &[b'"', 0xff, b'"'], ErrorType::InvalidUTF8);
+        assert_eq!(e.character(), None);
+        // Generic errors don't get a position
+        let e = Error::generic(ErrorType::Syntax).locate(input);
+        assert_eq!(e.line(), None);
     }
 }
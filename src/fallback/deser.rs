@@ -0,0 +1,199 @@
+use crate::error::ErrorType;
+use crate::fallback::stage1::{eq_bytes, movemask};
+use crate::stringparse::*;
+use crate::{Deserializer, ParsedStr, Result};
+
+// Loads the next 8 bytes of `src` as a little endian word, padding with
+// zeros when we are close to the end of the input.
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+fn load_word(src: &[u8], src_i: usize) -> u64 {
+    let mut bytes = [0_u8; 8];
+    if src.len() >= src_i + 8 {
+        bytes.copy_from_slice(unsafe { src.get_unchecked(src_i..src_i + 8) });
+    } else {
+        unsafe {
+            bytes
+                .get_unchecked_mut(..src.len() - src_i)
+                .clone_from_slice(src.get_unchecked(src_i..));
+        }
+    }
+    u64::from_le_bytes(bytes)
+}
+
+// Allow it to keep in sync with upstream
+#[allow(clippy::if_not_else, clippy::cast_possible_truncation)]
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+pub(crate) unsafe fn parse_str<'a, 'de>(
+    de: &'a mut Deserializer<'de>,
+) -> Result<ParsedStr<'de, 'a>> {
+    // Add 1 to skip the initial "
+    let idx = de.iidx + 1;
+
+    // we include the terminal '"' so we know where to end
+    // This is safe since we check sub's lenght in the range access above and only
+    // create sub sliced form sub to `sub.len()`.
+
+    let src: &[u8] = de.input.as_slice().get_unchecked(idx..);
+    let mut src_i: usize = 0;
+    let mut len = src_i;
+    loop {
+        let v = load_word(src, src_i);
+
+        let bs_bits: u32 = movemask(eq_bytes(v, b'\\')) as u32;
+        let quote_bits: u32 = movemask(eq_bytes(v, b'"')) as u32;
+        if (bs_bits.wrapping_sub(1) & quote_bits) != 0 {
+            // we encountered quotes first. Move dst to point to quotes and exit
+            // find out where the quote is...
+            let quote_dist: u32 = quote_bits.trailing_zeros();
+
+            len += quote_dist as usize;
+            let v = de.input.as_slice().get_unchecked(idx..idx + len) as *const [u8] as *const str;
+            return Ok(ParsedStr::Borrowed(&*v));
+        }
+        if (quote_bits.wrapping_sub(1) & bs_bits) != 0 {
+            // Move to the 'bad' character
+            let bs_dist: u32 = bs_bits.trailing_zeros();
+            len += bs_dist as usize;
+            src_i += bs_dist as usize;
+            break;
+        } else {
+            // they are the same. Since they can't co-occur, it means we encountered
+            // neither.
+            src_i += 8;
+            len += 8;
+        }
+    }
+
+    let mut dst_i: usize = 0;
+    let dst: &mut [u8] = &mut de.strings;
+
+    loop {
+        let v = load_word(src, src_i);
+
+        // store to dest unconditionally - we can overwrite the bits we don't like
+        // later
+        dst.get_unchecked_mut(dst_i..dst_i + 8)
+            .copy_from_slice(&v.to_le_bytes());
+
+        let bs_bits: u32 = movemask(eq_bytes(v, b'\\')) as u32;
+        let quote_bits: u32 = movemask(eq_bytes(v, b'"')) as u32;
+        if (bs_bits.wrapping_sub(1) & quote_bits) != 0 {
+            // we encountered quotes first. Move dst to point to quotes and exit
+            // find out where the quote is...
+            let quote_dist: u32 = quote_bits.trailing_zeros();
+
+            dst_i += quote_dist as usize;
+            return Ok(de.unescaped_str(idx, len, dst_i));
+        }
+        if (quote_bits.wrapping_sub(1) & bs_bits) != 0 {
+            // find out where the backspace is
+            let bs_dist: u32 = bs_bits.trailing_zeros();
+            let escape_char: u8 = *src.get_unchecked(src_i + bs_dist as usize + 1);
+            // we encountered backslash first. Handle backslash
+            if escape_char == b'u' {
+                // move src/dst up to the start; they will be further adjusted
+                // within the unicode codepoint handling code.
+                src_i += bs_dist as usize;
+                dst_i += bs_dist as usize;
+                let (o, s) = if let Ok(r) = handle_unicode_codepoint(
+                    src.get_unchecked(src_i..),
+                    dst.get_unchecked_mut(dst_i..),
+                ) {
+                    r
+                } else {
+                    return Err(de.error(ErrorType::InvlaidUnicodeCodepoint));
+                };
+                if o == 0 {
+                    return Err(de.error(ErrorType::InvlaidUnicodeCodepoint));
+                };
+                // We moved o steps forword at the destiation and 6 on the source
+                src_i += s;
+                dst_i += o;
+            } else {
+                // simple 1:1 conversion. Will eat bs_dist+2 characters in input and
+                // write bs_dist+1 characters to output
+                // note this may reach beyond the part of the buffer we've actually
+                // seen. I think this is ok
+                let escape_result: u8 = *ESCAPE_MAP.get_unchecked(escape_char as usize);
+                if escape_result == 0 {
+                    return Err(de.error(ErrorType::InvalidEscape));
+                }
+                *dst.get_unchecked_mut(dst_i + bs_dist as usize) = escape_result;
+                src_i += bs_dist as usize + 2;
+                dst_i += bs_dist as usize + 1;
+            }
+        } else {
+            // they are the same. Since they can't co-occur, it means we encountered
+            // neither.
+            src_i += 8;
+            dst_i += 8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::non_ascii_literal)]
+    use super::parse_str;
+    use crate::{Deserializer, ParsedStr, Result};
+
+    type ParseStrFn =
+        for<'a, 'de> unsafe fn(&'a mut Deserializer<'de>) -> Result<ParsedStr<'de, 'a>>;
+
+    // The fallback and the SIMD kernels this CPU can run
+    fn kernels() -> Vec<(&'static str, ParseStrFn)> {
+        #[allow(unused_mut)]
+        let mut kernels: Vec<(&'static str, ParseStrFn)> = vec![("fallback", parse_str)];
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                kernels.push(("avx2", crate::avx2::deser::parse_str));
+            }
+            if is_x86_feature_detected!("sse4.2") {
+                kernels.push(("sse4.2", crate::sse42::deser::parse_str));
+            }
+        }
+        kernels
+    }
+
+    // Parses `json`, a single string, with every kernel both borrowing and
+    // copying the unescaped string.
+    fn check(json: &str) {
+        let expected: String = serde_json::from_str(json).expect("valid string");
+        for (name, f) in kernels() {
+            let mut d = json.as_bytes().to_vec();
+            let mut de = Deserializer::from_slice(&mut d).expect("valid json");
+            de.skip();
+            let s = unsafe { f(&mut de) }.expect(name);
+            assert_eq!(s.as_str(), expected, "{} on {}", name, json);
+
+            let mut de = Deserializer::from_slice_ref(json.as_bytes()).expect("valid json");
+            de.skip();
+            let s = unsafe { f(&mut de) }.expect(name);
+            assert_eq!(s.as_str(), expected, "{} on {}", name, json);
+        }
+    }
+
+    #[test]
+    fn unescape_matches() {
+        // put every escape before, on and after the blocks of all kernels
+        for escape in &[
+            r#"\n"#,
+            r#"\""#,
+            r#"\\"#,
+            r#"\/"#,
+            r#"\u00e9"#,
+            r#"\u20ac"#,
+            r#"\ud83d\ude00"#,
+        ] {
+            for len in 0..70 {
+                let prefix = "a".repeat(len);
+                check(&format!(r#""{}{}""#, prefix, escape));
+                check(&format!(r#""{}{}é{}\t{}""#, prefix, escape, escape, prefix));
+            }
+        }
+        for len in 0..70 {
+            check(&format!(r#""{}""#, "é".repeat(len)));
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use crate::fallback::stage1::{eq_bytes, lteq_bytes, movemask};
+use crate::value::generator::ESCAPED;
+use std::io;
+
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+pub(crate) unsafe fn write_str_simd<W>(
+    writer: &mut W,
+    string: &mut &[u8],
+    len: &mut usize,
+    idx: &mut usize,
+) -> io::Result<()>
+where
+    W: std::io::Write,
+{
+    while *len - *idx >= 8 {
+        // Load 8 bytes of data;
+        let mut bytes = [0_u8; 8];
+        bytes.copy_from_slice(string.get_unchecked(*idx..*idx + 8));
+        let data = u64::from_le_bytes(bytes);
+        // Test the data against being backslash, quote or in the quote
+        // range (0x1F).
+        let quote_bits =
+            movemask(eq_bytes(data, b'\\') | eq_bytes(data, b'"') | lteq_bytes(data, 0x1F));
+        if quote_bits == 0 {
+            *idx += 8;
+        } else {
+            let quote_dist = quote_bits.trailing_zeros() as usize;
+            stry!(writer.write_all(&string[0..*idx + quote_dist]));
+            let ch = string[*idx + quote_dist];
+            match ESCAPED[ch as usize] {
+                b'u' => stry!(write!(writer, "\\u{:04x}", ch)),
+
+                escape => stry!(writer.write_all(&[b'\\', escape])),
+            };
+            *string = &string[*idx + quote_dist + 1..];
+            *idx = 0;
+            *len = string.len();
+        }
+    }
+    stry!(writer.write_all(&string[0..*idx]));
+    *string = &string[*idx..];
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::non_ascii_literal)]
+    use super::write_str_simd;
+    use crate::value::generator::ESCAPED;
+    use std::io::{self, Write};
+
+    type WriteStrFn = unsafe fn(&mut Vec<u8>, &mut &[u8], &mut usize, &mut usize) -> io::Result<()>;
+
+    // The fallback and the SIMD kernels this CPU can run
+    fn kernels() -> Vec<(&'static str, WriteStrFn)> {
+        #[allow(unused_mut)]
+        let mut kernels: Vec<(&'static str, WriteStrFn)> = vec![("fallback", write_str_simd)];
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                kernels.push(("avx2", crate::avx2::generator::write_str_simd));
+            }
+            if is_x86_feature_detected!("sse4.2") {
+                kernels.push(("sse4.2", crate::sse42::generator::write_str_simd));
+            }
+        }
+        kernels
+    }
+
+    // Escapes `s` with `f` and writes the remainder it leaves the same way
+    // the generator does.
+    fn escape(f: WriteStrFn, s: &str) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut string = s.as_bytes();
+        let mut len = string.len();
+        let mut idx = 0;
+        unsafe { f(&mut out, &mut string, &mut len, &mut idx) }.expect("written");
+        for ch in string {
+            match ESCAPED[*ch as usize] {
+                0 => out.push(*ch),
+                b'u' => write!(out, "\\u{:04x}", ch).expect("written"),
+                escape => out.extend_from_slice(&[b'\\', escape]),
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn escape_matches() {
+        for special in &["\"", "\\", "\n", "\u{1}", "\u{1f}", "\u{7f}", "é"] {
+            for len in 0..70 {
+                let prefix = "a".repeat(len);
+                for s in &[
+                    format!("{}{}", prefix, special),
+                    format!("{}{}b{}{}", special, prefix, special, prefix),
+                ] {
+                    let expected = serde_json::to_string(s).expect("serialized");
+                    let expected = &expected.as_bytes()[1..expected.len() - 1];
+                    for (name, f) in kernels() {
+                        assert_eq!(escape(f, s), expected, "{} on {:?}", name, s);
+                    }
+                }
+            }
+        }
+    }
+}
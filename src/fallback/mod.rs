@@ -0,0 +1,4 @@
+pub mod deser;
+pub mod generator;
+pub mod stage1;
+pub mod utf8check;
@@ -0,0 +1,626 @@
+#![allow(dead_code)]
+use crate::fallback::utf8check::*;
+use crate::*;
+
+use std::mem;
+
+pub const SIMDJSON_PADDING: usize = mem::size_of::<u64>() * 4;
+
+const ONES: u64 = 0x0101_0101_0101_0101;
+const LOW_BITS: u64 = 0x7f7f_7f7f_7f7f_7f7f;
+
+// We emulate the 64 byte registers of the simd implementations with eight
+// little endian words so that byte `n` of a block always ends up as bit `n`
+// of the masks we compute, independent of the targets endianess.
+#[derive(Debug)]
+struct SimdInput {
+    v: [u64; 8],
+}
+
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+fn fill_input(ptr: &[u8]) -> SimdInput {
+    let mut v = [0_u64; 8];
+    for (i, word) in v.iter_mut().enumerate() {
+        let mut bytes = [0_u8; 8];
+        bytes.copy_from_slice(unsafe { ptr.get_unchecked(i * 8..i * 8 + 8) });
+        *word = u64::from_le_bytes(bytes);
+    }
+    SimdInput { v }
+}
+
+/// sets the high bit of every byte in `word` that is zero. Unlike the
+/// classic `haszero` trick this is exact, borrows can't leak into the next
+/// byte since we never subtract.
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+pub(crate) fn zero_bytes(word: u64) -> u64 {
+    !(((word & LOW_BITS) + LOW_BITS) | word | LOW_BITS)
+}
+
+/// sets the high bit of every byte in `word` that is equal to `c`
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+pub(crate) fn eq_bytes(word: u64, c: u8) -> u64 {
+    zero_bytes(word ^ (ONES * u64::from(c)))
+}
+
+/// sets the high bit of every byte in `word` that is less or equal to `max`
+/// (using unsigned arithmetic), `max` has to be smaller then 0x80.
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+pub(crate) fn lteq_bytes(word: u64, max: u8) -> u64 {
+    !(((word & LOW_BITS) + ONES * u64::from(0x7f - max)) | word | LOW_BITS)
+}
+
+/// collects the high bits of the 8 bytes in `mask` into a u8, this is the
+/// equivalent of `_mm_movemask_epi8` for a single word.
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+pub(crate) fn movemask(mask: u64) -> u64 {
+    (mask >> 7).wrapping_mul(0x0102_0408_1020_4080) >> 56
+}
+
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+fn collect_mask<F>(input: &SimdInput, f: F) -> u64
+where
+    F: Fn(u64) -> u64,
+{
+    input
+        .v
+        .iter()
+        .enumerate()
+        .fold(0, |res, (i, word)| res | (movemask(f(*word)) << (i * 8)))
+}
+
+/// a straightforward comparison of a mask against input.
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+fn cmp_mask_against_input(input: &SimdInput, m: u8) -> u64 {
+    collect_mask(input, |word| eq_bytes(word, m))
+}
+
+// find all values less than or equal than maxval (using unsigned arithmetic)
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+fn unsigned_lteq_against_input(input: &SimdInput, maxval: u8) -> u64 {
+    collect_mask(input, |word| lteq_bytes(word, maxval))
+}
+
+// return a bitvector indicating where we have characters that end an odd-length
+// sequence of backslashes (and thus change the behavior of the next character
+// to follow). A even-length sequence of backslashes, and, for that matter, the
+// largest even-length prefix of our odd-length sequence of backslashes, simply
+// modify the behavior of the backslashes themselves.
+// We also update the prev_iter_ends_odd_backslash reference parameter to
+// indicate whether we end an iteration on an odd-length sequence of
+// backslashes, which modifies our subsequent search for odd-length
+// sequences of backslashes in an obvious way.
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+fn find_odd_backslash_sequences(input: &SimdInput, prev_iter_ends_odd_backslash: &mut u64) -> u64 {
+    const EVEN_BITS: u64 = 0x5555_5555_5555_5555;
+    const ODD_BITS: u64 = !EVEN_BITS;
+
+    let bs_bits: u64 = cmp_mask_against_input(input, b'\\');
+    let start_edges: u64 = bs_bits & !(bs_bits << 1);
+    // flip lowest if we have an odd-length run at the end of the prior
+    // iteration
+    let even_start_mask: u64 = EVEN_BITS ^ *prev_iter_ends_odd_backslash;
+    let even_starts: u64 = start_edges & even_start_mask;
+    let odd_starts: u64 = start_edges & !even_start_mask;
+    let even_carries: u64 = bs_bits.wrapping_add(even_starts);
+
+    // must record the carry-out of our odd-carries out of bit 63; this
+    // indicates whether the sense of any edge going to the next iteration
+    // should be flipped
+    let (mut odd_carries, iter_ends_odd_backslash) = bs_bits.overflowing_add(odd_starts);
+
+    odd_carries |= *prev_iter_ends_odd_backslash; // push in bit zero as a potential end
+                                                  // if we had an odd-numbered run at the
+                                                  // end of the previous iteration
+    *prev_iter_ends_odd_backslash = if iter_ends_odd_backslash { 0x1 } else { 0x0 };
+    let even_carry_ends: u64 = even_carries & !bs_bits;
+    let odd_carry_ends: u64 = odd_carries & !bs_bits;
+    let even_start_odd_end: u64 = even_carry_ends & ODD_BITS;
+    let odd_start_even_end: u64 = odd_carry_ends & EVEN_BITS;
+    let odd_ends: u64 = even_start_odd_end | odd_start_even_end;
+    odd_ends
+}
+
+// The simd implementations use a carry-less multiplication with all ones to
+// compute the prefix xor of the quote bits, without `pclmulqdq` we get there
+// with six shifts.
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+fn prefix_xor(bitmask: u64) -> u64 {
+    let mut bitmask = bitmask;
+    bitmask ^= bitmask << 1;
+    bitmask ^= bitmask << 2;
+    bitmask ^= bitmask << 4;
+    bitmask ^= bitmask << 8;
+    bitmask ^= bitmask << 16;
+    bitmask ^= bitmask << 32;
+    bitmask
+}
+
+// return both the quote mask (which is a half-open mask that covers the first
+// quote in an unescaped quote pair and everything in the quote pair) and the
+// quote bits, which are the simple unescaped quoted bits.
+//
+// We also update the prev_iter_inside_quote value to tell the next iteration
+// whether we finished the final iteration inside a quote pair; if so, this
+// inverts our behavior of whether we're inside quotes for the next iteration.
+//
+// Note that we don't do any error checking to see if we have backslash
+// sequences outside quotes; these
+// backslash sequences (of any length) will be detected elsewhere.
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+fn find_quote_mask_and_bits(
+    input: &SimdInput,
+    odd_ends: u64,
+    prev_iter_inside_quote: &mut u64,
+    quote_bits: &mut u64,
+    error_mask: &mut u64,
+) -> u64 {
+    *quote_bits = cmp_mask_against_input(input, b'"');
+    *quote_bits &= !odd_ends;
+    // remove from the valid quoted region the unescapted characters.
+    let mut quote_mask: u64 = prefix_xor(*quote_bits);
+    quote_mask ^= *prev_iter_inside_quote;
+    // All Unicode characters may be placed within the
+    // quotation marks, except for the characters that MUST be escaped:
+    // quotation mark, reverse solidus, and the control characters (U+0000
+    //through U+001F).
+    // https://tools.ietf.org/html/rfc8259
+    let unescaped: u64 = unsigned_lteq_against_input(input, 0x1F);
+    *error_mask |= quote_mask & unescaped;
+    // broadcast the last bit, all ones if we ended inside a quote pair
+    *prev_iter_inside_quote = 0_u64.wrapping_sub(quote_mask >> 63);
+    quote_mask
+}
+
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+fn find_whitespace_and_structurals(input: &SimdInput, whitespace: &mut u64, structurals: &mut u64) {
+    // the structural JSON characters are
+    // * `{` 0x7b
+    // * `}` 0x7d
+    // * `:` 0x3a
+    // * `[` 0x5b
+    // * `]` 0x5d
+    // * `,` 0x2c
+    // the brackets only differ from the braces in the 0x20 bit so by setting
+    // it we can test for both with a single comparison.
+    *structurals = collect_mask(input, |word| {
+        let lower = word | (ONES * 0x20);
+        eq_bytes(lower, b'{') | eq_bytes(lower, b'}') | eq_bytes(word, b':') | eq_bytes(word, b',')
+    });
+
+    // we are also interested in the four whitespace characters:
+    // * space 0x20
+    // * linefeed 0x0a
+    // * horizontal tab 0x09
+    // * carriage return 0x0d
+    *whitespace = collect_mask(input, |word| {
+        eq_bytes(word, b' ') | eq_bytes(word, b'\n') | eq_bytes(word, b'\t') | eq_bytes(word, b'\r')
+    });
+}
+
+// flatten out values in 'bits' assuming that they are are to have values of idx
+// plus their position in the bitvector, and store these indexes at
+// base_ptr[base] incrementing base as we go
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+fn flatten_bits(base: &mut Vec<u32>, idx: u32, mut bits: u64) {
+    let cnt: usize = bits.count_ones() as usize;
+    let idx_minus_64 = idx.wrapping_sub(64);
+    base.reserve(cnt);
+    while bits != 0 {
+        base.push(idx_minus_64.wrapping_add(bits.trailing_zeros()));
+        bits &= bits.wrapping_sub(1);
+    }
+}
+
+// return a updated structural bit vector with quoted contents cleared out and
+// pseudo-structural characters added to the mask
+// updates prev_iter_ends_pseudo_pred which tells us whether the previous
+// iteration ended on a whitespace or a structural character (which means that
+// the next iteration
+// will have a pseudo-structural character at its start)
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+fn finalize_structurals(
+    mut structurals: u64,
+    whitespace: u64,
+    quote_mask: u64,
+    quote_bits: u64,
+    prev_iter_ends_pseudo_pred: &mut u64,
+) -> u64 {
+    // mask off anything inside quotes
+    structurals &= !quote_mask;
+    // add the real quote bits back into our bitmask as well, so we can
+    // quickly traverse the strings we've spent all this trouble gathering
+    structurals |= quote_bits;
+    // Now, establish "pseudo-structural characters". These are non-whitespace
+    // characters that are (a) outside quotes and (b) have a predecessor that's
+    // either whitespace or a structural character. This means that subsequent
+    // passes will get a chance to encounter the first character of every string
+    // of non-whitespace and, if we're parsing an atom like true/false/null or a
+    // number we can stop at the first whitespace or structural character
+    // following it.
+
+    // a qualified predecessor is something that can happen 1 position before an
+    // psuedo-structural character
+    let pseudo_pred: u64 = structurals | whitespace;
+
+    let shifted_pseudo_pred: u64 = (pseudo_pred << 1) | *prev_iter_ends_pseudo_pred;
+    *prev_iter_ends_pseudo_pred = pseudo_pred >> 63;
+    let pseudo_structurals: u64 = shifted_pseudo_pred & (!whitespace) & (!quote_mask);
+    structurals |= pseudo_structurals;
+
+    // now, we've used our close quotes all we need to. So let's switch them off
+    // they will be off in the quote mask and on in quote bits.
+    structurals &= !(quote_bits & !quote_mask);
+    structurals
+}
+
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) unsafe fn find_structural_bits(
+    input: &[u8],
+    structural_indexes: &mut Vec<u32>,
+) -> std::result::Result<(), ErrorType> {
+    let len = input.len();
+    // 6 is a heuristic number to estimate it turns out a rate of 1/6 structural caracters lears
+    // almost never to relocations.
+    structural_indexes.clear();
+    structural_indexes.reserve(len / 6);
+    structural_indexes.push(0); // push extra root element
+
+    // persistent state across loop
+    // does the last iteration end with an odd-length sequence of backslashes?
+    // either 0 or 1, but a 64-bit value
+    let mut prev_iter_ends_odd_backslash: u64 = 0;
+    // does the previous iteration end inside a double-quote pair?
+    let mut prev_iter_inside_quote: u64 = 0;
+    // either all zeros or all ones
+    // does the previous iteration end on something that is a predecessor of a
+    // pseudo-structural character - i.e. whitespace or a structural character
+    // effectively the very first char is considered to follow "whitespace" for
+    // the
+    // purposes of pseudo-structural character detection so we initialize to 1
+    let mut prev_iter_ends_pseudo_pred: u64 = 1;
+
+    // structurals are persistent state across loop as we flatten them on the
+    // subsequent iteration into our array pointed to be base_ptr.
+    // This is harmless on the first iteration as structurals==0
+    let mut structurals: u64 = 0;
+
+    let lenminus64: usize = len.saturating_sub(64);
+    let mut idx: usize = 0;
+    let mut error_mask: u64 = 0; // for unescaped characters within strings (ASCII code points < 0x20)
+
+    while idx < lenminus64 {
+        let input: SimdInput = fill_input(input.get_unchecked(idx..));
+        // detect odd sequences of backslashes
+        let odd_ends: u64 = find_odd_backslash_sequences(&input, &mut prev_iter_ends_odd_backslash);
+
+        // detect insides of quote pairs ("quote_mask") and also our quote_bits
+        // themselves
+        let mut quote_bits: u64 = 0;
+        let quote_mask: u64 = find_quote_mask_and_bits(
+            &input,
+            odd_ends,
+            &mut prev_iter_inside_quote,
+            &mut quote_bits,
+            &mut error_mask,
+        );
+
+        // take the previous iterations structural bits, not our current iteration,
+        // and flatten
+        flatten_bits(structural_indexes, idx as u32, structurals);
+
+        let mut whitespace: u64 = 0;
+        find_whitespace_and_structurals(&input, &mut whitespace, &mut structurals);
+
+        // fixup structurals to reflect quotes and add pseudo-structural characters
+        structurals = finalize_structurals(
+            structurals,
+            whitespace,
+            quote_mask,
+            quote_bits,
+            &mut prev_iter_ends_pseudo_pred,
+        );
+        idx += 64;
+    }
+
+    // we use a giant copy-paste which is ugly.
+    // but otherwise the string needs to be properly padded or else we
+    // risk invalidating the UTF-8 checks.
+    if idx < len {
+        let mut tmpbuf: [u8; 64] = [0x20; 64];
+        tmpbuf
+            .as_mut_ptr()
+            .copy_from(input.as_ptr().add(idx), len - idx);
+        let input: SimdInput = fill_input(&tmpbuf);
+
+        // detect odd sequences of backslashes
+        let odd_ends: u64 = find_odd_backslash_sequences(&input, &mut prev_iter_ends_odd_backslash);
+
+        // detect insides of quote pairs ("quote_mask") and also our quote_bits
+        // themselves
+        let mut quote_bits: u64 = 0;
+        let quote_mask: u64 = find_quote_mask_and_bits(
+            &input,
+            odd_ends,
+            &mut prev_iter_inside_quote,
+            &mut quote_bits,
+            &mut error_mask,
+        );
+
+        // take the previous iterations structural bits, not our current iteration,
+        // and flatten
+        flatten_bits(structural_indexes, idx as u32, structurals);
+
+        let mut whitespace: u64 = 0;
+        find_whitespace_and_structurals(&input, &mut whitespace, &mut structurals);
+
+        // fixup structurals to reflect quotes and add pseudo-structural characters
+        structurals = finalize_structurals(
+            structurals,
+            whitespace,
+            quote_mask,
+            quote_bits,
+            &mut prev_iter_ends_pseudo_pred,
+        );
+        idx += 64;
+    }
+    // This test isn't in upstream, for some reason the error mask is et for then.
+    if prev_iter_inside_quote != 0 {
+        return Err(ErrorType::Syntax);
+    }
+    // finally, flatten out the remaining structurals from the last iteration
+    flatten_bits(structural_indexes, idx as u32, structurals);
+
+    // a valid JSON file cannot have zero structural indexes - we should have
+    // found something (note that we compare to 1 as we always add the root!)
+    if structural_indexes.len() == 1 {
+        return Err(ErrorType::EOF);
+    }
+
+    if structural_indexes.last() > Some(&(len as u32)) {
+        return Err(ErrorType::InternalError);
+    }
+
+    if error_mask != 0 {
+        return Err(ErrorType::Syntax);
+    }
+
+    if is_valid_utf8(input) {
+        Ok(())
+    } else {
+        Err(ErrorType::InvalidUTF8)
+    }
+}
+
+/// Same as `find_structural_bits` but instead of flattening the structurals
+/// it pushes a mask per 64 byte block to `whitespace`, with the bits of the
+/// whitespace outside of strings set.
+pub(crate) unsafe fn find_whitespace_bits(
+    input: &[u8],
+    whitespace: &mut Vec<u64>,
+) -> std::result::Result<(), ErrorType> {
+    let len = input.len();
+    whitespace.clear();
+    whitespace.reserve(len / 64 + 1);
+
+    let mut prev_iter_ends_odd_backslash: u64 = 0;
+    let mut prev_iter_inside_quote: u64 = 0;
+    let mut error_mask: u64 = 0; // for unescaped characters within strings (ASCII code points < 0x20)
+
+    // the last block is padded with spaces, they are dropped like any other
+    // whitespace
+    let mut tmpbuf: [u8; 64] = [0x20; 64];
+
+    let mut idx: usize = 0;
+    while idx < len {
+        let input: SimdInput = if len - idx >= 64 {
+            fill_input(input.get_unchecked(idx..))
+        } else {
+            tmpbuf
+                .as_mut_ptr()
+                .copy_from(input.as_ptr().add(idx), len - idx);
+            fill_input(&tmpbuf)
+        };
+        let odd_ends: u64 = find_odd_backslash_sequences(&input, &mut prev_iter_ends_odd_backslash);
+        let mut quote_bits: u64 = 0;
+        let quote_mask: u64 = find_quote_mask_and_bits(
+            &input,
+            odd_ends,
+            &mut prev_iter_inside_quote,
+            &mut quote_bits,
+            &mut error_mask,
+        );
+        let mut ws: u64 = 0;
+        let mut structurals: u64 = 0;
+        find_whitespace_and_structurals(&input, &mut ws, &mut structurals);
+        whitespace.push(ws & !quote_mask);
+        idx += 64;
+    }
+    if prev_iter_inside_quote != 0 || error_mask != 0 {
+        return Err(ErrorType::Syntax);
+    }
+    if is_valid_utf8(input) {
+        Ok(())
+    } else {
+        Err(ErrorType::InvalidUTF8)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::non_ascii_literal)]
+    use super::*;
+
+    type Stage1Fn = unsafe fn(&[u8], &mut Vec<u32>) -> std::result::Result<(), ErrorType>;
+
+    // The SIMD kernels this CPU can run, on other platforms the fallback is
+    // only checked against `reference`.
+    fn kernels() -> Vec<(&'static str, Stage1Fn)> {
+        #[allow(unused_mut)]
+        let mut kernels: Vec<(&'static str, Stage1Fn)> = Vec::new();
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("pclmulqdq") {
+                kernels.push(("avx2", crate::avx2::stage1::find_structural_bits));
+            }
+            if is_x86_feature_detected!("sse4.2") && is_x86_feature_detected!("pclmulqdq") {
+                kernels.push(("sse4.2", crate::sse42::stage1::find_structural_bits));
+            }
+        }
+        kernels
+    }
+
+    // Runs stage 1 over a padded copy of `input` like the deserializer does
+    fn run(f: Stage1Fn, input: &[u8]) -> std::result::Result<Vec<u32>, ErrorType> {
+        let mut padded = input.to_vec();
+        padded.resize(input.len() + crate::SIMDJSON_PADDING, b' ');
+        let mut structurals = Vec::new();
+        unsafe { f(&padded[..input.len()], &mut structurals) }.map(|_| structurals)
+    }
+
+    // Runs the fallback over `input` and checks every kernel agrees with it
+    fn check(input: &[u8]) -> std::result::Result<Vec<u32>, ErrorType> {
+        let fallback = run(find_structural_bits, input);
+        for (name, f) in kernels() {
+            assert_eq!(fallback, run(f, input), "{} on {:?}", name, input);
+        }
+        fallback
+    }
+
+    // A byte at a time version of stage 1 for documents without escapes
+    // outside of strings
+    #[allow(clippy::cast_possible_truncation)]
+    fn reference(input: &[u8]) -> Vec<u32> {
+        let mut structurals = vec![0];
+        let (mut in_string, mut escaped, mut pred) = (false, false, true);
+        for (i, c) in input.iter().enumerate() {
+            if in_string {
+                if escaped {
+                    escaped = false;
+                } else if *c == b'\\' {
+                    escaped = true;
+                } else if *c == b'"' {
+                    in_string = false;
+                    pred = true;
+                }
+                continue;
+            }
+            match c {
+                b' ' | b'\n' | b'\t' | b'\r' => pred = true,
+                b'{' | b'}' | b'[' | b']' | b':' | b',' | b'"' => {
+                    structurals.push(i as u32);
+                    in_string = *c == b'"';
+                    pred = true;
+                }
+                _ => {
+                    if pred {
+                        structurals.push(i as u32);
+                    }
+                    pred = false;
+                }
+            }
+        }
+        structurals
+    }
+
+    #[test]
+    fn byte_masks() {
+        let bytes = *b"a\"\\\x1f \x7f\x80{";
+        let word = u64::from_le_bytes(bytes);
+        assert_eq!(movemask(eq_bytes(word, b'"')), 0b0000_0010);
+        assert_eq!(movemask(eq_bytes(word, b'\\')), 0b0000_0100);
+        assert_eq!(movemask(eq_bytes(word, 0x80)), 0b0100_0000);
+        assert_eq!(movemask(lteq_bytes(word, 0x1f)), 0b0000_1000);
+        assert_eq!(movemask(lteq_bytes(word, b' ')), 0b0001_1000);
+    }
+
+    #[test]
+    fn structurals_match() {
+        let input = r#"{"a": [1, 2.5, "x\"y", true, null], "b\\": {"c": "é"}}"#.as_bytes();
+        let mut fallback = Vec::new();
+        unsafe { find_structural_bits(input, &mut fallback) }.expect("fallback");
+        let mut simd = Vec::new();
+        unsafe { Deserializer::find_structural_bits(input, &mut simd) }.expect("simd");
+        assert_eq!(fallback, simd);
+    }
+
+    #[test]
+    fn structurals_across_blocks() {
+        // Move runs of backslashes, quotes and atoms over the block
+        // boundaries so every carry from one block to the next is taken.
+        for pad in 50..140 {
+            for run in 1..6 {
+                // odd runs escape the first quote after them
+                let escaped = if run % 2 == 1 { "\"" } else { "" };
+                let input = format!(
+                    "{}[\"a{}{}\", 1,true, \"{}\" ,{{\"b\":[null]}}]",
+                    " ".repeat(pad),
+                    "\\".repeat(run),
+                    escaped,
+                    "[x],".repeat(run)
+                );
+                let input = input.as_bytes();
+                assert_eq!(check(input), Ok(reference(input)), "{}/{}", pad, run);
+            }
+        }
+        // strings spanning several blocks
+        let s = r#"{"a": [1, "x\"]"], "b\\": 2}, "#.repeat(4);
+        let escaped = s.replace('\\', r"\\").replace('"', r#"\""#);
+        let input = format!("[{}\"{}\", {}]", s, escaped, "1 ".repeat(40));
+        let input = input.as_bytes();
+        assert!(input.len() > 256);
+        assert_eq!(check(input), Ok(reference(input)));
+    }
+
+    #[test]
+    fn errors_across_blocks() {
+        for pad in 58..70 {
+            let pad = " ".repeat(pad);
+            // unterminated strings and control characters in strings
+            assert_eq!(
+                check(format!("{}[\"a\", \"b]", pad).as_bytes()),
+                Err(ErrorType::Syntax)
+            );
+            assert_eq!(
+                check(format!("{}[\"a\\\"]", pad).as_bytes()),
+                Err(ErrorType::Syntax)
+            );
+            assert_eq!(
+                check(format!("{}[\"a\tb\"]", pad).as_bytes()),
+                Err(ErrorType::Syntax)
+            );
+            // multi byte characters split by a block boundary
+            for c in &["é", "€", "😀"] {
+                let input = format!("{}\"{}\"", pad, c);
+                assert_eq!(check(input.as_bytes()), Ok(reference(input.as_bytes())));
+            }
+            for bad in &[
+                &b"\xff"[..],
+                b"\xc3",
+                b"\xe2\x82",
+                b"\xc0\xaf",
+                b"\xed\xa0\x80",
+                b"\xf4\x90\x80\x80",
+            ] {
+                let mut input = format!("{}\"", pad).into_bytes();
+                input.extend_from_slice(bad);
+                input.extend_from_slice(b"\" ");
+                assert_eq!(check(&input), Err(ErrorType::InvalidUTF8), "{:?}", input);
+            }
+        }
+    }
+
+    #[test]
+    fn whitespace_match() {
+        let input = format!(
+            "{}{{\"a b\": [1 ,\t\"x\\\" {}\"],\r\n \"c\": null}}  ",
+            " ".repeat(61),
+            " ".repeat(70)
+        );
+        let mut fallback = Vec::new();
+        unsafe { find_whitespace_bits(input.as_bytes(), &mut fallback) }.expect("fallback");
+        let mut simd = Vec::new();
+        unsafe { Deserializer::find_whitespace_bits(input.as_bytes(), &mut simd) }.expect("simd");
+        assert_eq!(fallback.len(), 3);
+        assert_eq!(fallback, simd);
+    }
+}
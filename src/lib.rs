@@ -110,51 +110,114 @@ mod charutils;
 #[macro_use]
 mod macros;
 mod error;
+mod limits;
+mod minify;
 mod numberparse;
 mod parsedjson;
+mod parser;
+mod reader;
+mod stream;
 mod stringparse;
-
-#[cfg(target_feature = "avx2")]
+mod tokens;
+mod validation;
+mod visitor;
+
+// With runtime detection both x86 implementations are compiled in and the
+// fastest one supported by the CPU is picked the first time it is used.
+#[cfg(any(
+    target_feature = "avx2",
+    all(
+        feature = "runtime-detection",
+        any(target_arch = "x86", target_arch = "x86_64")
+    )
+))]
 mod avx2;
-#[cfg(target_feature = "avx2")]
-pub use crate::avx2::deser::*;
-#[cfg(target_feature = "avx2")]
-use crate::avx2::stage1::SIMDJSON_PADDING;
+// The avx2 padding is the largest of the x86 implementations so we use it
+// whenever avx2 might be picked.
+#[cfg(any(
+    target_feature = "avx2",
+    all(
+        feature = "runtime-detection",
+        any(target_arch = "x86", target_arch = "x86_64")
+    )
+))]
+pub(crate) use crate::avx2::stage1::SIMDJSON_PADDING;
 
 #[cfg(all(
     any(target_arch = "x86", target_arch = "x86_64"),
-    not(target_feature = "avx2")
+    any(feature = "runtime-detection", not(target_feature = "avx2"))
 ))]
 mod sse42;
 #[cfg(all(
     any(target_arch = "x86", target_arch = "x86_64"),
+    not(feature = "runtime-detection"),
     not(target_feature = "avx2")
 ))]
-pub use crate::sse42::deser::*;
-#[cfg(all(
-    any(target_arch = "x86", target_arch = "x86_64"),
-    not(target_feature = "avx2")
-))]
-use crate::sse42::stage1::SIMDJSON_PADDING;
+pub(crate) use crate::sse42::stage1::SIMDJSON_PADDING;
 
 #[cfg(all(target_feature = "neon", feature = "neon"))]
 mod neon;
 #[cfg(all(target_feature = "neon", feature = "neon"))]
-pub use crate::neon::deser::*;
-#[cfg(all(target_feature = "neon", feature = "neon"))]
-use crate::neon::stage1::SIMDJSON_PADDING;
+pub(crate) use crate::neon::stage1::SIMDJSON_PADDING;
+
+// The portable implementation is used on all other platforms, with runtime
+// detection it also serves x86 CPUs that lack SSE4.2.
+#[cfg(any(
+    all(
+        feature = "runtime-detection",
+        any(target_arch = "x86", target_arch = "x86_64")
+    ),
+    not(any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        all(target_feature = "neon", feature = "neon")
+    ))
+))]
+mod fallback;
+#[cfg(not(any(
+    target_arch = "x86",
+    target_arch = "x86_64",
+    all(target_feature = "neon", feature = "neon")
+)))]
+pub(crate) use crate::fallback::stage1::SIMDJSON_PADDING;
 
 mod stage2;
 /// simd-json JSON-DOM value
 pub mod value;
 
-use crate::numberparse::Number;
-#[cfg(not(target_feature = "neon"))]
+/// Lazy navigation of a document without building a DOM
+pub mod ondemand;
+/// JSON Patch and JSON Merge Patch
+pub mod patch;
+/// JSONPath queries over values
+pub mod query;
+
+use std::marker::PhantomData;
 use std::mem;
+use std::ptr;
+use std::slice;
 use std::str;
-
-pub use crate::error::{Error, ErrorType};
+#[cfg(all(
+    feature = "runtime-detection",
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+use crate::numberparse::{is_valid_number, Number};
+use crate::stringparse::unescaped_len;
+
+pub use crate::error::{Category, Error, ErrorType, Limit};
+pub use crate::limits::{Limits, DEFAULT_MAX_DEPTH};
+pub use crate::minify::{minify, minify_in_place};
+pub use crate::numberparse::Number as ParsedNumber;
+pub use crate::parsedjson::{ArrayIter, Cursor, ObjectIter, ParsedJson};
+pub use crate::parser::Parser;
+pub use crate::reader::{to_owned_value_from_reader, ReaderStream};
+pub use crate::stream::DocumentStream;
+pub use crate::tokens::{Token, Tokens};
+pub use crate::validation::{validate, validate_with_stats, Stats};
 pub use crate::value::*;
+pub use crate::visitor::{visit, JsonVisitor, Visit};
 
 /// simd-json Result type
 pub type Result<T> = std::result::Result<T, Error>;
@@ -164,29 +227,179 @@ mod known_key;
 #[cfg(feature = "known-key")]
 pub use known_key::{Error as KnownKeyError, KnownKey};
 
+/// Scratch buffers of a `Deserializer`, they can be carried over from one
+/// document to the next to avoid re-allocating them.
+#[derive(Default)]
+pub(crate) struct Buffers {
+    pub(crate) structural_indexes: Vec<u32>,
+    pub(crate) counts: Vec<(u32, u32)>,
+    pub(crate) strings: Vec<u8>,
+}
+
+/// A string parsed from the input
+pub(crate) enum ParsedStr<'de, 's> {
+    /// The string is part of the input
+    Borrowed(&'de str),
+    /// The string contained escapes and was unescaped into the strings
+    /// buffer, it is only valid until the next string is parsed.
+    Copied(&'s str),
+}
+
+impl<'de, 's> ParsedStr<'de, 's> {
+    #[cfg_attr(not(feature = "no-inline"), inline(always))]
+    pub(crate) fn as_str(&self) -> &str {
+        match self {
+            ParsedStr::Borrowed(s) => s,
+            ParsedStr::Copied(s) => s,
+        }
+    }
+
+    #[cfg_attr(not(feature = "no-inline"), inline(always))]
+    pub(crate) fn into_cow(self) -> std::borrow::Cow<'de, str> {
+        match self {
+            ParsedStr::Borrowed(s) => s.into(),
+            ParsedStr::Copied(s) => s.to_owned().into(),
+        }
+    }
+}
+
+/// The document a `Deserializer` works on. It is kept as a pointer rather
+/// than a slice: unescaped strings are written back into mutable input and
+/// no shared slice over those bytes may be alive while that happens. Only
+/// strings that are never written again are handed out for `'de`.
+#[derive(Clone, Copy)]
+pub(crate) struct Input<'de> {
+    ptr: *mut u8,
+    len: usize,
+    /// Set if we may rewrite the input, unescaped strings are then copied
+    /// back into it so they can be borrowed.
+    mutable: bool,
+    marker: PhantomData<&'de [u8]>,
+}
+
+impl<'de> Input<'de> {
+    pub(crate) fn from_mut(input: &'de mut [u8]) -> Self {
+        Self {
+            ptr: input.as_mut_ptr(),
+            len: input.len(),
+            mutable: true,
+            marker: PhantomData,
+        }
+    }
+
+    pub(crate) fn from_ref(input: &'de [u8]) -> Self {
+        Self {
+            ptr: input.as_ptr() as *mut u8,
+            len: input.len(),
+            mutable: false,
+            marker: PhantomData,
+        }
+    }
+
+    /// The whole document, the slice must not be held on to across a call
+    /// that may unescape a string.
+    #[cfg_attr(not(feature = "no-inline"), inline(always))]
+    pub(crate) fn as_slice(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
 pub(crate) struct Deserializer<'de> {
-    // This string starts with the input data and characters are truncated off
-    // the beginning as data is parsed.
-    input: &'de mut [u8],
+    input: Input<'de>,
     //data: Vec<u8>,
     strings: Vec<u8>,
     structural_indexes: Vec<u32>,
     idx: usize,
-    counts: Vec<usize>,
+    /// Number of elements and index of the closing structural for every
+    /// array or object, stored at the index of its opening structural
+    counts: Vec<(u32, u32)>,
     str_offset: usize,
     iidx: usize,
+    max_string_length: usize,
 }
 
 impl<'de> Deserializer<'de> {
     #[cfg_attr(not(feature = "no-inline"), inline(always))]
     fn error(&self, error: ErrorType) -> Error {
-        Error::new(self.idx, self.iidx, self.c() as char, error)
+        Error::new(self.idx, self.iidx, self.input.as_slice(), error)
+    }
+    /// Fills in the position of an error that is returned to the caller,
+    /// errors serde created without one are placed at the current value.
+    #[cold]
+    fn locate(&self, e: Error) -> Error {
+        e.locate_at(self.idx, self.iidx, self.input.as_slice())
     }
     // By convention, `Deserializer` constructors are named like `from_xyz`.
     // That way basic use cases are satisfied by something like
     // `serde_json::from_str(...)` while advanced use cases that require a
     // deserializer can make one with `serde_json::Deserializer::from_str(...)`.
     pub fn from_slice(input: &'de mut [u8]) -> Result<Self> {
+        Self::from_slice_with_buffers(input, &mut Buffers::default(), &Limits::default())
+    }
+
+    /// Same as `from_slice` but leaves the input untouched. Strings without
+    /// escapes are still borrowed from it, others are unescaped into the
+    /// strings buffer.
+    pub fn from_slice_ref(input: &'de [u8]) -> Result<Self> {
+        let mut buffers = Buffers::default();
+        if let Err(t) =
+            Deserializer::find_structural_bits_padded(input, &mut buffers.structural_indexes)
+        {
+            return Err(Deserializer::stage1_error(input, t));
+        }
+        Self::from_stage1_(Input::from_ref(input), &mut buffers, &Limits::default())
+    }
+
+    /// Same as `from_slice` but takes its scratch buffers from `buffers`
+    /// instead of allocating new ones, use `into_buffers` to get them back.
+    pub(crate) fn from_slice_with_buffers(
+        input: &'de mut [u8],
+        buffers: &mut Buffers,
+        limits: &Limits,
+    ) -> Result<Self> {
+        // Don't bother running stage 1 over documents we'd reject anyway
+        stry!(Deserializer::check_document_size(input, limits));
+        if let Err(t) =
+            Deserializer::find_structural_bits_padded(input, &mut buffers.structural_indexes)
+        {
+            return Err(Deserializer::stage1_error(input, t));
+        }
+
+        Self::from_stage1(input, buffers, limits)
+    }
+
+    #[cfg_attr(not(feature = "no-inline"), inline(always))]
+    fn check_document_size(input: &[u8], limits: &Limits) -> Result<()> {
+        if likely!(limits.max_document_size == usize::max_value()) {
+            Ok(())
+        } else if unlikely!(input.len() > limits.max_document_size) {
+            Err(Error::generic(ErrorType::LimitExceeded(
+                Limit::DocumentSize,
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Builds the error for a failed stage 1 run. Stage 1 only tells us what
+    /// went wrong, so we look for where it happened now that we know.
+    #[cold]
+    pub(crate) fn stage1_error(input: &[u8], error: ErrorType) -> Error {
+        let index = match error {
+            ErrorType::InvalidUTF8 => str::from_utf8(input).err().map_or(0, |e| e.valid_up_to()),
+            ErrorType::Syntax => find_string_error(input),
+            ErrorType::EOF => input.len(),
+            _ => 0,
+        };
+        Error::new(0, index, input, error).locate(input)
+    }
+
+    /// Runs stage 1 over `input`, copying it first if reading past its end
+    /// could cross a page boundary.
+    pub(crate) fn find_structural_bits_padded(
+        input: &[u8],
+        structural_indexes: &mut Vec<u32>,
+    ) -> std::result::Result<(), ErrorType> {
         // We have to pick an initial size of the structural indexes.
         // 6 is a heuristic that seems to work well for the benchmark
         // data and limit re-allocation frequency.
@@ -196,7 +409,7 @@ impl<'de> Deserializer<'de> {
         let buf_start: usize = input.as_ptr() as *const () as usize;
         let needs_relocation = (buf_start + input.len()) % page_size::get() < SIMDJSON_PADDING;
 
-        let s1_result: std::result::Result<Vec<u32>, ErrorType> = if needs_relocation {
+        if needs_relocation {
             let mut data: Vec<u8> = Vec::with_capacity(len + SIMDJSON_PADDING);
             unsafe {
                 data.set_len(len + 1);
@@ -205,52 +418,124 @@ impl<'de> Deserializer<'de> {
                     .clone_from_slice(input);
                 *(data.get_unchecked_mut(len)) = 0;
                 data.set_len(len);
-                Deserializer::find_structural_bits(&data)
+                Deserializer::find_structural_bits(&data, structural_indexes)
             }
         } else {
-            unsafe { Deserializer::find_structural_bits(input) }
-        };
-        let structural_indexes = match s1_result {
-            Ok(i) => i,
-            Err(t) => {
-                return Err(Error::generic(t));
-            }
-        };
+            unsafe { Deserializer::find_structural_bits(input, structural_indexes) }
+        }
+    }
 
-        let counts = Deserializer::validate(input, &structural_indexes)?;
+    /// Creates a deserializer for `input` whose structural indexes have
+    /// already been found and are stored in `buffers`.
+    pub(crate) fn from_stage1(
+        input: &'de mut [u8],
+        buffers: &mut Buffers,
+        limits: &Limits,
+    ) -> Result<Self> {
+        Self::from_stage1_(Input::from_mut(input), buffers, limits)
+    }
+
+    fn from_stage1_(input: Input<'de>, buffers: &mut Buffers, limits: &Limits) -> Result<Self> {
+        let len = input.len;
+        {
+            let input = input.as_slice();
+            stry!(Deserializer::check_document_size(input, limits));
+            stry!(Deserializer::validate(
+                input,
+                &buffers.structural_indexes,
+                &mut buffers.counts,
+                limits
+            )
+            .map_err(|e| e.locate(input)));
+        }
 
         // Set length to allow slice access in ARM code
-        let mut strings = Vec::with_capacity(len + SIMDJSON_PADDING);
+        let mut strings = mem::take(&mut buffers.strings);
+        strings.clear();
+        strings.reserve(len + SIMDJSON_PADDING);
         unsafe {
             strings.set_len(len + SIMDJSON_PADDING);
         }
 
         Ok(Deserializer {
-            counts,
-            structural_indexes,
+            counts: mem::take(&mut buffers.counts),
+            structural_indexes: mem::take(&mut buffers.structural_indexes),
             input,
             idx: 0,
             strings,
             str_offset: 0,
             iidx: 0,
+            max_string_length: limits.max_string_length,
         })
     }
 
+    /// Hands back the scratch buffers so they can be used for the next
+    /// document.
+    pub(crate) fn into_buffers(self) -> Buffers {
+        Buffers {
+            structural_indexes: self.structural_indexes,
+            counts: self.counts,
+            strings: self.strings,
+        }
+    }
+
     #[cfg_attr(not(feature = "no-inline"), inline(always))]
-    fn skip(&mut self) {
-        self.idx += 1;
-        self.iidx = unsafe { *self.structural_indexes.get_unchecked(self.idx) as usize };
+    pub(crate) fn parse_str_(&mut self) -> Result<ParsedStr<'de, '_>> {
+        // Strings are unlimited by default, don't pay for the check then
+        if likely!(self.max_string_length == usize::max_value()) {
+            self.parse_str_simd()
+        } else {
+            self.parse_str_limited()
+        }
+    }
+
+    #[inline(never)]
+    fn parse_str_limited(&mut self) -> Result<ParsedStr<'de, '_>> {
+        let (idx, iidx, input) = (self.idx, self.iidx, self.input);
+        let max = self.max_string_length;
+        let s = stry!(self.parse_str_simd());
+        if unlikely!(s.as_str().len() > max) {
+            let e = ErrorType::LimitExceeded(Limit::StringLength);
+            return Err(Error::new(idx, iidx, input.as_slice(), e));
+        }
+        Ok(s)
     }
 
+    /// Finishes parsing a string that contains escapes. `input[idx..idx + len]`
+    /// holds the part before the first escape, the start of the strings
+    /// buffer the unescaped rest of `dst_len` bytes.
     #[cfg_attr(not(feature = "no-inline"), inline(always))]
-    fn c(&self) -> u8 {
-        unsafe {
-            *self
-                .input
-                .get_unchecked(*self.structural_indexes.get_unchecked(self.idx) as usize)
+    pub(crate) unsafe fn unescaped_str(
+        &mut self,
+        idx: usize,
+        len: usize,
+        dst_len: usize,
+    ) -> ParsedStr<'de, '_> {
+        let input = self.input.ptr;
+        if self.input.mutable {
+            // The unescaped string is never longer than the escaped one so
+            // it can be written back over it. Nothing else reads these bytes
+            // afterwards, so they can be borrowed for `'de`.
+            ptr::copy_nonoverlapping(self.strings.as_ptr(), input.add(idx + len), dst_len);
+            self.str_offset += dst_len;
+            let v = slice::from_raw_parts(input.add(idx), len + dst_len);
+            ParsedStr::Borrowed(str::from_utf8_unchecked(v))
+        } else {
+            let strings = self.strings.as_mut_ptr();
+            ptr::copy(strings, strings.add(len), dst_len);
+            ptr::copy_nonoverlapping(input.add(idx), strings, len);
+            ParsedStr::Copied(str::from_utf8_unchecked(
+                self.strings.get_unchecked(..len + dst_len),
+            ))
         }
     }
 
+    #[cfg_attr(not(feature = "no-inline"), inline(always))]
+    fn skip(&mut self) {
+        self.idx += 1;
+        self.iidx = unsafe { *self.structural_indexes.get_unchecked(self.idx) as usize };
+    }
+
     // pull out the check so we don't need to
     // stry every time
     #[cfg_attr(not(feature = "no-inline"), inline(always))]
@@ -258,18 +543,80 @@ impl<'de> Deserializer<'de> {
         unsafe {
             self.idx += 1;
             self.iidx = *self.structural_indexes.get_unchecked(self.idx) as usize;
-            *self.input.get_unchecked(self.iidx)
+            *self.input.as_slice().get_unchecked(self.iidx)
+        }
+    }
+
+    #[cfg_attr(not(feature = "no-inline"), inline(always))]
+    fn peek_(&self) -> u8 {
+        unsafe {
+            let iidx = *self.structural_indexes.get_unchecked(self.idx + 1) as usize;
+            *self.input.as_slice().get_unchecked(iidx)
+        }
+    }
+
+    /// Skips the next value without building anything from it. Stage 1
+    /// and 2 leave the grammar of numbers and the escapes in strings
+    /// unchecked so those are still looked at, they are not decoded though.
+    #[cfg_attr(not(feature = "no-inline"), inline(always))]
+    pub(crate) fn skip_value(&mut self) -> Result<()> {
+        match self.next_() {
+            b'[' | b'{' => self.skip_container(),
+            _ => self.check_scalar(self.idx),
+        }
+    }
+
+    /// Jumps to the end of the array or object whose opening structural
+    /// is the current one, checking the numbers and strings in it on the
+    /// way.
+    pub(crate) fn skip_container(&mut self) -> Result<()> {
+        let end = self.container_end(self.idx);
+        for idx in self.idx + 1..end {
+            stry!(self.check_scalar(idx));
         }
+        self.idx = end;
+        self.iidx = unsafe { *self.structural_indexes.get_unchecked(end) as usize };
+        Ok(())
+    }
+
+    /// Checks the escapes and length of the string or the grammar of the
+    /// number starting at structural `idx` without decoding it. Anything
+    /// else was already checked by stage 2.
+    #[cfg_attr(not(feature = "no-inline"), inline(always))]
+    pub(crate) fn check_scalar(&self, idx: usize) -> Result<()> {
+        let iidx = unsafe { *self.structural_indexes.get_unchecked(idx) as usize };
+        let input = self.input.as_slice();
+        let src = unsafe { input.get_unchecked(iidx..) };
+        let error = match unsafe { *src.get_unchecked(0) } {
+            b'"' => match unescaped_len(src) {
+                None => ErrorType::InvalidEscape,
+                Some(len) if len > self.max_string_length => {
+                    ErrorType::LimitExceeded(Limit::StringLength)
+                }
+                Some(_) => return Ok(()),
+            },
+            b'-' | b'0'..=b'9' if !is_valid_number(src) => ErrorType::InvalidNumber,
+            _ => return Ok(()),
+        };
+        Err(Error::new(idx, iidx, input, error))
+    }
+
+    /// Index of the closing structural of the array or object opened at
+    /// `idx`.
+    #[cfg_attr(not(feature = "no-inline"), inline(always))]
+    pub(crate) fn container_end(&self, idx: usize) -> usize {
+        unsafe { self.counts.get_unchecked(idx).1 as usize }
     }
 
     #[cfg_attr(not(feature = "no-inline"), inline(always))]
     fn count_elements(&self) -> usize {
-        unsafe { *self.counts.get_unchecked(self.idx) }
+        unsafe { self.counts.get_unchecked(self.idx).0 as usize }
     }
 
     #[cfg_attr(not(feature = "no-inline"), inline(always))]
     fn parse_number_root(&mut self, minus: bool) -> Result<Number> {
-        let input = unsafe { &self.input.get_unchecked(self.iidx..) };
+        let input = self.input;
+        let input = unsafe { input.as_slice().get_unchecked(self.iidx..) };
         let len = input.len();
         let mut copy = vec![0_u8; len + SIMDJSON_PADDING];
         copy[len] = 0;
@@ -281,7 +628,8 @@ impl<'de> Deserializer<'de> {
 
     #[cfg_attr(not(feature = "no-inline"), inline(always))]
     fn parse_number(&mut self, minus: bool) -> Result<Number> {
-        let input = unsafe { &self.input.get_unchecked(self.iidx..) };
+        let input = self.input;
+        let input = unsafe { input.as_slice().get_unchecked(self.iidx..) };
         let len = input.len();
         if len < SIMDJSON_PADDING {
             let mut copy = vec![0_u8; len + SIMDJSON_PADDING];
@@ -296,30 +644,336 @@ impl<'de> Deserializer<'de> {
 
     #[cfg_attr(not(feature = "no-inline"), inline(always))]
     fn parse_number_(&mut self, minus: bool) -> Result<Number> {
-        let input = unsafe { &self.input.get_unchecked(self.iidx..) };
+        let input = self.input;
+        let input = unsafe { input.as_slice().get_unchecked(self.iidx..) };
         self.parse_number_int(input, minus)
     }
 }
 
+/// Finds the unescaped control character in a string or the start of an
+/// unterminated string that failed stage 1.
+fn find_string_error(input: &[u8]) -> usize {
+    let mut open = None;
+    let mut escaped = false;
+    for (i, c) in input.iter().enumerate() {
+        if open.is_none() {
+            if *c == b'"' {
+                open = Some(i);
+            }
+        } else if escaped {
+            escaped = false;
+        } else if *c == b'\\' {
+            escaped = true;
+        } else if *c == b'"' {
+            open = None;
+        } else if *c < 0x20 {
+            return i;
+        }
+    }
+    open.unwrap_or(0)
+}
+
+type FindStructuralBitsFn = unsafe fn(&[u8], &mut Vec<u32>) -> std::result::Result<(), ErrorType>;
+type FindWhitespaceBitsFn = unsafe fn(&[u8], &mut Vec<u64>) -> std::result::Result<(), ErrorType>;
+type ParseStrFn = for<'a, 'de> unsafe fn(&'a mut Deserializer<'de>) -> Result<ParsedStr<'de, 'a>>;
+
+// Runtime selected implementation: the CPU features are detected on the first
+// call, after that we go straight to the remembered function.
+#[cfg(all(
+    feature = "runtime-detection",
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+impl<'de> Deserializer<'de> {
+    pub(crate) unsafe fn find_structural_bits(
+        input: &[u8],
+        structural_indexes: &mut Vec<u32>,
+    ) -> std::result::Result<(), ErrorType> {
+        static FN: AtomicPtr<()> = AtomicPtr::new(get_fastest as FindStructuralBitsFn as *mut ());
+
+        unsafe fn get_fastest(
+            input: &[u8],
+            structural_indexes: &mut Vec<u32>,
+        ) -> std::result::Result<(), ErrorType> {
+            let fun: FindStructuralBitsFn = if is_x86_feature_detected!("avx2")
+                && is_x86_feature_detected!("pclmulqdq")
+            {
+                crate::avx2::stage1::find_structural_bits
+            } else if is_x86_feature_detected!("sse4.2") && is_x86_feature_detected!("pclmulqdq") {
+                crate::sse42::stage1::find_structural_bits
+            } else {
+                crate::fallback::stage1::find_structural_bits
+            };
+            FN.store(fun as *mut (), Ordering::Relaxed);
+            fun(input, structural_indexes)
+        }
+
+        let fun = FN.load(Ordering::Relaxed);
+        mem::transmute::<*mut (), FindStructuralBitsFn>(fun)(input, structural_indexes)
+    }
+
+    pub(crate) unsafe fn find_whitespace_bits(
+        input: &[u8],
+        whitespace: &mut Vec<u64>,
+    ) -> std::result::Result<(), ErrorType> {
+        static FN: AtomicPtr<()> = AtomicPtr::new(get_fastest as FindWhitespaceBitsFn as *mut ());
+
+        unsafe fn get_fastest(
+            input: &[u8],
+            whitespace: &mut Vec<u64>,
+        ) -> std::result::Result<(), ErrorType> {
+            let fun: FindWhitespaceBitsFn = if is_x86_feature_detected!("avx2")
+                && is_x86_feature_detected!("pclmulqdq")
+            {
+                crate::avx2::stage1::find_whitespace_bits
+            } else if is_x86_feature_detected!("sse4.2") && is_x86_feature_detected!("pclmulqdq") {
+                crate::sse42::stage1::find_whitespace_bits
+            } else {
+                crate::fallback::stage1::find_whitespace_bits
+            };
+            FN.store(fun as *mut (), Ordering::Relaxed);
+            fun(input, whitespace)
+        }
+
+        let fun = FN.load(Ordering::Relaxed);
+        mem::transmute::<*mut (), FindWhitespaceBitsFn>(fun)(input, whitespace)
+    }
+
+    fn parse_str_simd(&mut self) -> Result<ParsedStr<'de, '_>> {
+        static FN: AtomicPtr<()> = AtomicPtr::new(get_fastest as ParseStrFn as *mut ());
+
+        unsafe fn get_fastest<'a, 'de>(
+            de: &'a mut Deserializer<'de>,
+        ) -> Result<ParsedStr<'de, 'a>> {
+            let fun: ParseStrFn = if is_x86_feature_detected!("avx2") {
+                crate::avx2::deser::parse_str
+            } else if is_x86_feature_detected!("sse4.2") {
+                crate::sse42::deser::parse_str
+            } else {
+                crate::fallback::deser::parse_str
+            };
+            FN.store(fun as *mut (), Ordering::Relaxed);
+            fun(de)
+        }
+
+        unsafe {
+            let fun = FN.load(Ordering::Relaxed);
+            mem::transmute::<*mut (), ParseStrFn>(fun)(self)
+        }
+    }
+}
+
+// Compile time selected implementation
+#[cfg(not(all(
+    feature = "runtime-detection",
+    any(target_arch = "x86", target_arch = "x86_64")
+)))]
+impl<'de> Deserializer<'de> {
+    #[cfg_attr(not(feature = "no-inline"), inline(always))]
+    pub(crate) unsafe fn find_structural_bits(
+        input: &[u8],
+        structural_indexes: &mut Vec<u32>,
+    ) -> std::result::Result<(), ErrorType> {
+        #[cfg(target_feature = "avx2")]
+        let fun: FindStructuralBitsFn = crate::avx2::stage1::find_structural_bits;
+        #[cfg(all(
+            any(target_arch = "x86", target_arch = "x86_64"),
+            not(target_feature = "avx2")
+        ))]
+        let fun: FindStructuralBitsFn = crate::sse42::stage1::find_structural_bits;
+        #[cfg(all(target_feature = "neon", feature = "neon"))]
+        let fun: FindStructuralBitsFn = crate::neon::stage1::find_structural_bits;
+        #[cfg(not(any(
+            target_arch = "x86",
+            target_arch = "x86_64",
+            all(target_feature = "neon", feature = "neon")
+        )))]
+        let fun: FindStructuralBitsFn = crate::fallback::stage1::find_structural_bits;
+        fun(input, structural_indexes)
+    }
+
+    #[cfg_attr(not(feature = "no-inline"), inline(always))]
+    pub(crate) unsafe fn find_whitespace_bits(
+        input: &[u8],
+        whitespace: &mut Vec<u64>,
+    ) -> std::result::Result<(), ErrorType> {
+        #[cfg(target_feature = "avx2")]
+        let fun: FindWhitespaceBitsFn = crate::avx2::stage1::find_whitespace_bits;
+        #[cfg(all(
+            any(target_arch = "x86", target_arch = "x86_64"),
+            not(target_feature = "avx2")
+        ))]
+        let fun: FindWhitespaceBitsFn = crate::sse42::stage1::find_whitespace_bits;
+        #[cfg(all(target_feature = "neon", feature = "neon"))]
+        let fun: FindWhitespaceBitsFn = crate::neon::stage1::find_whitespace_bits;
+        #[cfg(not(any(
+            target_arch = "x86",
+            target_arch = "x86_64",
+            all(target_feature = "neon", feature = "neon")
+        )))]
+        let fun: FindWhitespaceBitsFn = crate::fallback::stage1::find_whitespace_bits;
+        fun(input, whitespace)
+    }
+
+    #[cfg_attr(not(feature = "no-inline"), inline(always))]
+    fn parse_str_simd(&mut self) -> Result<ParsedStr<'de, '_>> {
+        #[cfg(target_feature = "avx2")]
+        let fun: ParseStrFn = crate::avx2::deser::parse_str;
+        #[cfg(all(
+            any(target_arch = "x86", target_arch = "x86_64"),
+            not(target_feature = "avx2")
+        ))]
+        let fun: ParseStrFn = crate::sse42::deser::parse_str;
+        #[cfg(all(target_feature = "neon", feature = "neon"))]
+        let fun: ParseStrFn = crate::neon::deser::parse_str;
+        #[cfg(not(any(
+            target_arch = "x86",
+            target_arch = "x86_64",
+            all(target_feature = "neon", feature = "neon")
+        )))]
+        let fun: ParseStrFn = crate::fallback::deser::parse_str;
+        unsafe { fun(self) }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #![allow(clippy::unnecessary_operation, clippy::non_ascii_literal)]
-    use super::serde::from_slice;
+    use super::serde::{from_slice, from_slice_ref};
     use super::{
-        owned::to_value, owned::Object, owned::Value, to_borrowed_value, to_owned_value,
-        Deserializer,
+        owned::to_value, owned::Object, owned::Value, to_borrowed_value,
+        to_borrowed_value_from_ref, to_owned_value, to_owned_value_from_ref, Buffers, Deserializer,
+        Limits,
     };
     use halfbrown::HashMap;
     use proptest::prelude::*;
     use serde::Deserialize;
     use serde_json;
 
+    #[test]
+    fn from_ref() {
+        use super::BorrowedValue;
+        use std::borrow::Cow;
+        let d = br#"{"plain": "snot", "escaped": "bad\nger\u00e9", "a": ["\"", ""]}"#;
+        let v = to_borrowed_value_from_ref(d).expect("to_borrowed_value_from_ref");
+        if let BorrowedValue::String(Cow::Borrowed(s)) = &v["plain"] {
+            assert_eq!(s.as_ptr(), d[11..].as_ptr());
+        } else {
+            panic!("expected a borrowed string: {:?}", v["plain"]);
+        }
+        assert_eq!(
+            v["escaped"],
+            BorrowedValue::String(Cow::Owned("bad\ngeré".to_string()))
+        );
+        let mut d1 = d.to_vec();
+        assert_eq!(v, to_borrowed_value(&mut d1).expect("to_borrowed_value"));
+        assert_eq!(
+            to_owned_value_from_ref(d).expect("to_owned_value_from_ref"),
+            v
+        );
+        assert_eq!(
+            &d[..],
+            &br#"{"plain": "snot", "escaped": "bad\nger\u00e9", "a": ["\"", ""]}"#[..]
+        );
+    }
+
+    #[test]
+    fn from_slice_ref_borrows() {
+        use std::borrow::Cow;
+        #[derive(Deserialize)]
+        struct Doc<'a> {
+            plain: &'a str,
+            #[serde(borrow)]
+            escaped: Cow<'a, str>,
+        }
+        let d = br#"{"plain": "snot", "escaped": "bad\tger"}"#;
+        let doc: Doc = from_slice_ref(d).expect("from_slice_ref");
+        assert_eq!(doc.plain, "snot");
+        assert_eq!(doc.escaped, "bad\tger");
+        // escaped strings can't be borrowed from immutable input
+        assert!(from_slice_ref::<Doc>(br#"{"plain": "sn\"ot", "escaped": ""}"#).is_err());
+    }
+
+    #[test]
+    fn from_slice_ref_escaped_keys() {
+        use crate::{BorrowedValue, ValueTrait};
+        let v: BorrowedValue = from_slice_ref(br#"{"a\nb": 1, "c": {"d\"": 2}}"#).expect("parsed");
+        assert_eq!(v.get("a\nb"), Some(&BorrowedValue::from(1)));
+        let c = v.get("c").expect("plain key");
+        assert_eq!(c.get("d\""), Some(&BorrowedValue::from(2)));
+    }
+
+    #[test]
+    fn error_position() {
+        let e = to_borrowed_value_from_ref(b"{\n  \"a\": [1, 2,, 3]\n}").expect_err("parsed");
+        assert_eq!(
+            (e.line(), e.column(), e.character()),
+            (Some(2), Some(14), Some(','))
+        );
+        // the position is kept for stage 1 errors
+        let e = to_borrowed_value_from_ref(b"[\"a\",\n \"b\xff\"]").expect_err("parsed");
+        assert_eq!((e.line(), e.column()), (Some(2), Some(4)));
+        let e = to_borrowed_value_from_ref(b"[\"a\",\n \"b\tc\"]").expect_err("parsed");
+        assert_eq!((e.line(), e.column()), (Some(2), Some(4)));
+        let e = to_borrowed_value_from_ref(b"[1,\n \"b]").expect_err("parsed");
+        assert_eq!((e.line(), e.column()), (Some(2), Some(2)));
+        let e = to_borrowed_value_from_ref(b"  \n ").expect_err("parsed");
+        assert_eq!((e.line(), e.column()), (Some(2), Some(2)));
+        // and for errors found while building values
+        let e = to_owned_value(&mut b"[1,\n 2.x]".to_vec()).expect_err("parsed");
+        assert_eq!((e.line(), e.column()), (Some(2), Some(2)));
+        let e = to_borrowed_value_from_ref(b"[1,\n \"a\\x\"]").expect_err("parsed");
+        assert_eq!((e.line(), e.column()), (Some(2), Some(2)));
+        assert!(format!("{}", e).contains('^'));
+        let e = to_owned_value_from_ref(b"[1,\n 2.x]").expect_err("parsed");
+        assert_eq!((e.line(), e.column()), (Some(2), Some(2)));
+        let e = crate::serde::from_slice::<Vec<u64>>(&mut b"[1,\n \"2\"]".to_vec())
+            .expect_err("deserialized");
+        assert_eq!((e.line(), e.column()), (Some(2), Some(2)));
+    }
+
+    #[test]
+    fn serde_error_position() {
+        #[derive(Deserialize, Debug)]
+        enum Kind {
+            Num(u64),
+        }
+        #[derive(Deserialize, Debug)]
+        struct Inner {
+            c: char,
+            k: Kind,
+        }
+        #[derive(Deserialize, Debug)]
+        struct Outer {
+            inner: Inner,
+        }
+        let position = |d: &str| {
+            let mut d = d.as_bytes().to_vec();
+            let e = from_slice::<Outer>(&mut d).expect_err("deserialized");
+            assert!(e.is_data());
+            (e.line(), e.column())
+        };
+        // Type mismatches serde reports without a position
+        assert_eq!(
+            position("{\n  \"inner\": {\n    \"c\": \"ab\",\n    \"k\": {\"Num\": 1}\n  }\n}"),
+            (Some(3), Some(10))
+        );
+        assert_eq!(
+            position("{\n  \"inner\": {\n    \"c\": \"a\",\n    \"k\": \"Num\"\n  }\n}"),
+            (Some(4), Some(10))
+        );
+        // A missing field is reported at the end of its object
+        assert_eq!(
+            position("{\n  \"inner\": {\n    \"c\": \"a\"\n  }\n}"),
+            (Some(4), Some(3))
+        );
+    }
+
     #[test]
     fn count1() {
         let mut d = String::from("[]");
         let mut d = unsafe { d.as_bytes_mut() };
         let simd = Deserializer::from_slice(&mut d).expect("");
-        assert_eq!(simd.counts[1], 0);
+        assert_eq!(simd.counts[1].0, 0);
     }
 
     #[test]
@@ -327,7 +981,7 @@ mod tests {
         let mut d = String::from("[1]");
         let mut d = unsafe { d.as_bytes_mut() };
         let simd = Deserializer::from_slice(&mut d).expect("");
-        assert_eq!(simd.counts[1], 1);
+        assert_eq!(simd.counts[1].0, 1);
     }
 
     #[test]
@@ -335,7 +989,7 @@ mod tests {
         let mut d = String::from("[1,2]");
         let mut d = unsafe { d.as_bytes_mut() };
         let simd = Deserializer::from_slice(&mut d).expect("");
-        assert_eq!(simd.counts[1], 2);
+        assert_eq!(simd.counts[1].0, 2);
     }
 
     #[test]
@@ -343,8 +997,8 @@ mod tests {
         let mut d = String::from(" [ 1 , [ 3 ] , 2 ]");
         let mut d = unsafe { d.as_bytes_mut() };
         let simd = Deserializer::from_slice(&mut d).expect("");
-        assert_eq!(simd.counts[1], 3);
-        assert_eq!(simd.counts[4], 1);
+        assert_eq!(simd.counts[1].0, 3);
+        assert_eq!(simd.counts[4].0, 1);
     }
 
     #[test]
@@ -352,8 +1006,69 @@ mod tests {
         let mut d = String::from("[[],null,null]");
         let mut d = unsafe { d.as_bytes_mut() };
         let simd = Deserializer::from_slice(&mut d).expect("");
-        assert_eq!(simd.counts[1], 3);
-        assert_eq!(simd.counts[2], 0);
+        assert_eq!(simd.counts[1].0, 3);
+        assert_eq!(simd.counts[2].0, 0);
+    }
+
+    #[test]
+    fn skip_value() {
+        let mut d = String::from(r#" [ 1 , [ 3 , {"a": [], "b": "\n"} ] , 2 ]"#);
+        let mut d = unsafe { d.as_bytes_mut() };
+        let mut simd = Deserializer::from_slice(&mut d).expect("");
+        assert_eq!(simd.container_end(1), 20);
+        assert_eq!(simd.container_end(4), 17);
+        assert_eq!(simd.container_end(7), 16);
+        assert_eq!(simd.container_end(10), 11);
+        // skip the leading `[`, `1` and `,`
+        simd.idx = 3;
+        assert!(simd.skip_value().is_ok());
+        assert_eq!(simd.idx, 17);
+        assert_eq!(simd.next_(), b',');
+        assert!(simd.skip_value().is_ok());
+        assert_eq!(simd.next_(), b']');
+    }
+
+    #[test]
+    fn skip_value_checks_numbers_and_strings() {
+        for s in &[
+            r#"[[01], 1]"#,
+            r#"[{"a": -}, 1]"#,
+            r#"[["\q"], 1]"#,
+            r#"["\ud800", 1]"#,
+            r#"{"\x": 1}"#,
+            r#"1.e5"#,
+        ] {
+            let mut d = s.as_bytes().to_vec();
+            let mut simd = Deserializer::from_slice(&mut d).expect("structure is valid");
+            assert!(simd.skip_value().is_err(), "{}", s);
+        }
+        let mut d = br#"[["\u00e9\ud83d\ude00", -1.5e-3, 0, {"a": null}], 1]"#.to_vec();
+        let mut simd = Deserializer::from_slice(&mut d).expect("structure is valid");
+        simd.skip();
+        assert!(simd.skip_value().is_ok());
+        assert_eq!(simd.next_(), b',');
+    }
+
+    #[test]
+    fn skip_value_checks_string_length() {
+        let limits = Limits {
+            max_string_length: 3,
+            ..Limits::default()
+        };
+        for (s, ok) in &[
+            (r#"["abc"]"#, true),
+            (r#"["abcd"]"#, false),
+            (r#"{"abcd": 1}"#, false),
+            (r#"["\u00e9a"]"#, true),
+            (r#"["\u00e9ab"]"#, false),
+            (r#"["ab"   ]"#, true),
+        ] {
+            let mut d = s.as_bytes().to_vec();
+            let mut simd =
+                Deserializer::from_slice_with_buffers(&mut d, &mut Buffers::default(), &limits)
+                    .expect("structure is valid");
+            assert_eq!(simd.skip_value().is_ok(), *ok, "{}", s);
+        }
     }
 
     #[test]
@@ -942,6 +1657,15 @@ mod tests {
         assert_eq!(v_simd, v_serde)
     }
 
+    #[test]
+    fn obj_u64() {
+        let mut d = String::from(r#"{"a": 18446744073709551615, "b": 9223372036854775808}"#);
+        let mut d = unsafe { d.as_bytes_mut() };
+        let v_serde: Obj = serde_json::from_slice(d).expect("serde_json");
+        let v_simd: Obj = from_slice(&mut d).expect("simd_json");
+        assert_eq!(v_simd, v_serde)
+    }
+
     #[test]
     fn obj2() {
         let mut d =
@@ -1206,6 +1930,10 @@ mod tests {
                 dbg!(&v_simd_borrowed);
                 assert!(v_simd_borrowed.is_ok());
                 assert_eq!(v_simd_owned.expect("simd-error"), super::OwnedValue::from(v_simd_borrowed.expect("simd-error")));
+                let v_simd_ref = to_borrowed_value_from_ref(d.as_bytes()).expect("simd-error");
+                assert_eq!(super::OwnedValue::from(v_simd_ref), to_owned_value_from_ref(d.as_bytes()).expect("simd-error"));
+                let v_simd_serde_ref: serde_json::Value = from_slice_ref(d.as_bytes()).expect("simd-error");
+                assert_eq!(v_simd_serde_ref, v_serde);
             }
 
         }
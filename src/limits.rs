@@ -0,0 +1,173 @@
+/// Default for `Limits::max_depth`, the same as `serde_json` uses
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// Limits enforced while parsing to protect against hostile input. A document
+/// breaching one of them is rejected with `ErrorType::LimitExceeded` before
+/// it can exhaust the stack or memory.
+///
+/// Only the nesting depth is limited by default, deeply nested documents
+/// would otherwise overflow the stack when building values from them.
+///
+/// ```
+/// use simd_json::{ErrorType, Limit, Limits, Parser};
+///
+/// let mut parser = Parser::new().with_limits(Limits {
+///     max_depth: 2,
+///     ..Limits::default()
+/// });
+/// let mut d = br#"[[1], [2]]"#.to_vec();
+/// assert!(parser.parse_owned(&mut d).is_ok());
+/// let mut d = br#"[[[1]]]"#.to_vec();
+/// let e = parser.parse_owned(&mut d).unwrap_err();
+/// assert_eq!(e.error_type(), &ErrorType::LimitExceeded(Limit::Depth));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum nesting depth of arrays and objects
+    pub max_depth: usize,
+    /// Maximum size of a document in bytes
+    pub max_document_size: usize,
+    /// Maximum length of a string in bytes, after unescaping
+    pub max_string_length: usize,
+    /// Maximum number of elements in a single array or object
+    pub max_elements: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_document_size: usize::max_value(),
+            max_string_length: usize::max_value(),
+            max_elements: usize::max_value(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Limits, DEFAULT_MAX_DEPTH};
+    use crate::{to_borrowed_value, to_owned_value, ErrorType, Limit, Parser};
+    use serde::de::IgnoredAny;
+    use std::collections::HashMap;
+
+    fn nested(depth: usize) -> Vec<u8> {
+        let mut d = "[".repeat(depth);
+        d.push_str(&"]".repeat(depth));
+        d.into_bytes()
+    }
+
+    fn limit_exceeded(e: crate::Error) -> Option<Limit> {
+        match e.error_type() {
+            ErrorType::LimitExceeded(l) => Some(*l),
+            _ => None,
+        }
+    }
+
+    /// Runs `f` on a thread with a stack of known size instead of whatever
+    /// the test harness uses
+    fn with_stack(size: usize, f: fn()) {
+        std::thread::Builder::new()
+            .stack_size(size)
+            .spawn(f)
+            .expect("spawn")
+            .join()
+            .expect("thread panicked");
+    }
+
+    #[test]
+    fn default_depth() {
+        with_stack(64 << 20, || {
+            let mut d = nested(DEFAULT_MAX_DEPTH);
+            assert!(to_owned_value(&mut d.clone()).is_ok());
+            assert!(to_borrowed_value(&mut d.clone()).is_ok());
+            assert!(crate::serde::from_slice::<serde_json::Value>(&mut d).is_ok());
+
+            let d = nested(DEFAULT_MAX_DEPTH + 1);
+            let e = to_owned_value(&mut d.clone()).expect_err("parsed");
+            assert_eq!(limit_exceeded(e), Some(Limit::Depth));
+
+            let d = nested(100_000);
+            let e = to_owned_value(&mut d.clone()).expect_err("parsed");
+            assert_eq!(limit_exceeded(e), Some(Limit::Depth));
+            let e = to_borrowed_value(&mut d.clone()).expect_err("parsed");
+            assert_eq!(limit_exceeded(e), Some(Limit::Depth));
+            let e =
+                crate::serde::from_slice::<serde_json::Value>(&mut d.clone()).expect_err("parsed");
+            assert_eq!(limit_exceeded(e), Some(Limit::Depth));
+        });
+    }
+
+    #[test]
+    fn depth() {
+        let mut parser = Parser::new().with_limits(Limits {
+            max_depth: 3,
+            ..Limits::default()
+        });
+        let mut d = br#"{"a": [{"b": 1}], "c": [[2]]}"#.to_vec();
+        assert!(parser.parse_owned(&mut d).is_ok());
+        let mut d = br#"{"a": [{"b": []}]}"#.to_vec();
+        let e = parser.parse_owned(&mut d).expect_err("parsed");
+        assert_eq!(e.index(), 13);
+        assert_eq!(limit_exceeded(e), Some(Limit::Depth));
+    }
+
+    #[test]
+    fn document_size() {
+        let mut parser = Parser::new().with_limits(Limits {
+            max_document_size: 8,
+            ..Limits::default()
+        });
+        assert!(parser.parse_owned(&mut b"[1,2,3]".to_vec()).is_ok());
+        let e = parser
+            .parse_owned(&mut b"[1,2,3,4]".to_vec())
+            .expect_err("parsed");
+        assert_eq!(limit_exceeded(e), Some(Limit::DocumentSize));
+    }
+
+    #[test]
+    fn string_length() {
+        let mut parser = Parser::new().with_limits(Limits {
+            max_string_length: 4,
+            ..Limits::default()
+        });
+        // the limit applies to the unescaped string and to keys
+        let mut d = br#"{"snot": "\n\n\n\n", "a": "b"}"#.to_vec();
+        assert!(parser.parse_borrowed(&mut d).is_ok());
+        let mut d = br#"{"badger": 1}"#.to_vec();
+        let e = parser.parse_owned(&mut d).expect_err("parsed");
+        assert_eq!(limit_exceeded(e), Some(Limit::StringLength));
+        let mut d = br#"["snot\n"]"#.to_vec();
+        let e = parser.parse_borrowed(&mut d).expect_err("parsed");
+        assert_eq!(limit_exceeded(e), Some(Limit::StringLength));
+        let mut d = br#"["snot", "badger"]"#.to_vec();
+        let e = parser
+            .deserialize::<Vec<String>>(&mut d)
+            .expect_err("parsed");
+        assert_eq!(limit_exceeded(e), Some(Limit::StringLength));
+        // skipped strings count too
+        let mut d = br#"{"a": 1, "b": ["badger"]}"#.to_vec();
+        let e = parser
+            .deserialize::<HashMap<String, IgnoredAny>>(&mut d)
+            .expect_err("parsed");
+        assert_eq!(limit_exceeded(e), Some(Limit::StringLength));
+    }
+
+    #[test]
+    fn elements() {
+        let mut parser = Parser::new().with_limits(Limits {
+            max_elements: 2,
+            ..Limits::default()
+        });
+        let mut d = br#"{"a": [1, 2], "b": {}}"#.to_vec();
+        assert!(parser.parse_owned(&mut d).is_ok());
+        let mut d = br#"[[1, 2, 3]]"#.to_vec();
+        let e = parser.parse_owned(&mut d).expect_err("parsed");
+        assert_eq!(limit_exceeded(e), Some(Limit::Elements));
+        let mut d = br#"{"a": 1, "b": 2, "c": 3}"#.to_vec();
+        let e = parser
+            .deserialize::<serde_json::Value>(&mut d)
+            .expect_err("parsed");
+        assert_eq!(limit_exceeded(e), Some(Limit::Elements));
+    }
+}
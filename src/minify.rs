@@ -0,0 +1,238 @@
+use crate::charutils::is_not_structural_or_whitespace;
+use crate::{Deserializer, Error, ErrorType, Result};
+
+/// Runs stage 1 over `input` and returns its whitespace masks, one per 64
+/// bytes with the bits of the whitespace outside of strings set.
+fn whitespace_bits(input: &[u8]) -> Result<Vec<u64>> {
+    let mut whitespace = Vec::new();
+    if let Err(t) = unsafe { Deserializer::find_whitespace_bits(input, &mut whitespace) } {
+        return Err(Deserializer::stage1_error(input, t));
+    }
+    Ok(whitespace)
+}
+
+/// Calls `f` with the start and end of every run of bytes that are kept, the
+/// runs are read from the masks with `trailing_zeros` so a block without
+/// whitespace is handled in one step. Runs that continue in the next block
+/// are merged.
+fn for_each_run<F>(len: usize, whitespace: &[u64], mut f: F) -> Result<()>
+where
+    F: FnMut(usize, usize) -> Result<()>,
+{
+    let mut run = (0, 0);
+    for (block, ws) in whitespace.iter().enumerate() {
+        let base = block * 64;
+        let mut keep = !ws;
+        while keep != 0 {
+            let start = keep.trailing_zeros() as usize;
+            let n = (!(keep >> start)).trailing_zeros() as usize;
+            if base + start == run.1 {
+                run.1 += n;
+            } else {
+                if run.1 > run.0 {
+                    stry!(f(run.0, run.1));
+                }
+                run = (base + start, base + start + n);
+            }
+            keep = if start + n == 64 {
+                0
+            } else {
+                keep & (u64::max_value() << (start + n))
+            };
+        }
+    }
+    // the padding of the last block is whitespace so runs never reach
+    // past `len`
+    if run.1 > run.0 {
+        stry!(f(run.0, run.1.min(len)));
+    }
+    Ok(())
+}
+
+/// Values only separated by whitespace are rejected as removing it would
+/// merge them, `[1 2]` would turn into `[12]` and `[tru e]` into `[true]`.
+/// `last` is the last byte of the previous run.
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+fn check_separated(input: &[u8], last: &mut Option<u8>, start: usize, end: usize) -> Result<()> {
+    let is_value = |c: u8| c == b'"' || is_not_structural_or_whitespace(c) == 1;
+    if let Some(l) = *last {
+        if is_value(l) && is_value(input[start]) {
+            return Err(Error::new(0, start, input, ErrorType::UnexpectedCharacter).locate(input));
+        }
+    }
+    *last = Some(input[end - 1]);
+    Ok(())
+}
+
+/// Appends `input` to `out` with all whitespace outside of strings removed.
+/// Strings and numbers are copied byte for byte and the order of keys is
+/// kept.
+///
+/// Only stage 1 runs, so unterminated strings, invalid UTF-8 and values
+/// that aren't separated are rejected but the structure isn't checked
+/// otherwise, use `validate` for that.
+///
+/// The whitespace is found with stage 1's whitespace and quote masks, the
+/// bytes between it are copied in runs.
+///
+/// ```
+/// let mut out = Vec::new();
+/// simd_json::minify(b"{\n  \"a b\": [1.50, true],\n  \"c\": null\n}\n", &mut out).unwrap();
+/// assert_eq!(out, br#"{"a b":[1.50,true],"c":null}"#.to_vec());
+/// ```
+pub fn minify(input: &[u8], out: &mut Vec<u8>) -> Result<()> {
+    let whitespace = stry!(whitespace_bits(input));
+    let len = out.len();
+    out.reserve(input.len());
+    let mut last = None;
+    let res = for_each_run(input.len(), &whitespace, |start, end| {
+        stry!(check_separated(input, &mut last, start, end));
+        out.extend_from_slice(&input[start..end]);
+        Ok(())
+    });
+    if res.is_ok() && last.is_none() {
+        return Err(Deserializer::stage1_error(input, ErrorType::EOF));
+    }
+    if res.is_err() {
+        out.truncate(len);
+    }
+    res
+}
+
+/// Same as `minify` but removes the whitespace in place, the minified
+/// document is `input[..len]` where `len` is the length returned. `input`
+/// is left untouched if an error is returned.
+pub fn minify_in_place(input: &mut [u8]) -> Result<usize> {
+    let whitespace = stry!(whitespace_bits(input));
+    // check before moving anything so errors point into the original input
+    let mut last = None;
+    stry!(for_each_run(input.len(), &whitespace, |start, end| {
+        check_separated(input, &mut last, start, end)
+    }));
+    if last.is_none() {
+        return Err(Deserializer::stage1_error(input, ErrorType::EOF));
+    }
+    let mut len = 0;
+    stry!(for_each_run(input.len(), &whitespace, |start, end| {
+        input.copy_within(start..end, len);
+        len += end - start;
+        Ok(())
+    }));
+    Ok(len)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{minify, minify_in_place};
+    use crate::{to_owned_value, ErrorType};
+
+    fn minified(d: &str) -> String {
+        let mut out = Vec::new();
+        minify(d.as_bytes(), &mut out).expect("minify");
+        let mut d = d.as_bytes().to_vec();
+        let len = minify_in_place(&mut d).expect("minify_in_place");
+        assert_eq!(&d[..len], out.as_slice());
+        String::from_utf8(out).expect("utf8")
+    }
+
+    #[test]
+    fn whitespace() {
+        assert_eq!(minified(" 1 "), "1");
+        assert_eq!(minified("\t\"snot\"\r\n"), r#""snot""#);
+        assert_eq!(minified("[ ]"), "[]");
+        assert_eq!(
+            minified("{ \"a\" :\t[ 1 , -2.50e+3 ,\n true,null ] ,\r\n \"b\" : { } }"),
+            r#"{"a":[1,-2.50e+3,true,null],"b":{}}"#
+        );
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(
+            minified(r#"[ " a b ", "\" ]", "é é", "\\" , "" ]"#),
+            r#"[" a b ","\" ]","é é","\\",""]"#
+        );
+        assert_eq!(
+            minified("{ \"key with spaces \" : \" \\t \" }"),
+            "{\"key with spaces \":\" \\t \"}"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let mut out = Vec::new();
+        assert!(minify(b"", &mut out).is_err());
+        assert!(minify(br#"[ "unterminated ]"#, &mut out).is_err());
+        assert!(minify(&[b'"', 0xff, b'"'], &mut out).is_err());
+        // whitespace is all that separates these values
+        for d in &["[1 2]", "[tru e]", r#"{"a" "b"}"#, r#"["a" 1]"#, "1 2"] {
+            let e = minify(d.as_bytes(), &mut out).expect_err(d);
+            assert_eq!(e.error_type(), &ErrorType::UnexpectedCharacter);
+            assert!(minify_in_place(&mut d.as_bytes().to_vec()).is_err());
+        }
+        assert!(out.is_empty());
+        let mut d = b"[1, \n 2 3]".to_vec();
+        assert!(minify_in_place(&mut d).is_err());
+        assert_eq!(d, b"[1, \n 2 3]");
+        let e = minify(b"[1, \n 2 3]", &mut out).expect_err("merged");
+        assert_eq!((e.line(), e.column()), (Some(2), Some(4)));
+    }
+
+    /// Drops whitespace outside of strings one byte at a time
+    fn reference(d: &str) -> String {
+        let mut out = String::new();
+        let (mut in_string, mut escaped) = (false, false);
+        for c in d.chars() {
+            if in_string {
+                in_string = escaped || c != '"';
+                escaped = !escaped && c == '\\';
+            } else if c == '"' {
+                in_string = true;
+            } else if c.is_ascii_whitespace() {
+                continue;
+            }
+            out.push(c);
+        }
+        out
+    }
+
+    #[test]
+    fn blocks() {
+        // runs, strings and escapes crossing the 64 byte blocks at every
+        // offset
+        for pad in 0..130 {
+            let d = format!(
+                "{}{{\"{}\": [\"a \\\\\\\" b\", {}1.5 ,\ttrue]}}{}",
+                " ".repeat(pad),
+                "k ".repeat(pad % 70),
+                "\n".repeat(pad % 13),
+                "\r\n".repeat(pad % 40)
+            );
+            assert_eq!(minified(&d), reference(&d), "{}", d);
+        }
+        let long = format!("[\"{}\"]", "x".repeat(200));
+        assert_eq!(minified(&format!(" {} ", long)), long);
+        let d = format!("{}[1 2]", " ".repeat(62));
+        let e = minify(d.as_bytes(), &mut Vec::new()).expect_err("merged");
+        assert_eq!(e.index(), 65);
+    }
+
+    #[test]
+    fn mesh() {
+        let pretty = std::fs::read("data/mesh.pretty.json").expect("data/mesh.pretty.json");
+        let mut out = Vec::new();
+        minify(&pretty, &mut out).expect("minify");
+        assert!(out.len() < pretty.len() / 2);
+        assert!(!out.iter().any(|&c| c == b' ' || c == b'\n'));
+
+        let mut again = Vec::new();
+        minify(&out, &mut again).expect("minify");
+        assert_eq!(again, out);
+
+        let mut pretty = pretty;
+        assert_eq!(
+            to_owned_value(&mut out).expect("minified"),
+            to_owned_value(&mut pretty).expect("pretty")
+        );
+    }
+}
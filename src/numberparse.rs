@@ -2,9 +2,15 @@ use crate::charutils::*;
 use crate::unlikely;
 use crate::*;
 
-#[cfg(target_arch = "x86")]
+#[cfg(all(
+    target_arch = "x86",
+    any(feature = "runtime-detection", target_feature = "sse4.1")
+))]
 use std::arch::x86::*;
-#[cfg(target_arch = "x86_64")]
+#[cfg(all(
+    target_arch = "x86_64",
+    any(feature = "runtime-detection", target_feature = "sse4.1")
+))]
 use std::arch::x86_64::*;
 
 const POWER_OF_TEN: [f64; 632] = [
@@ -66,6 +72,55 @@ pub fn is_integer(c: u8) -> bool {
     c >= b'0' && c <= b'9'
 }
 
+/// Checks the grammar of the number at the start of `buf` without
+/// converting it, so numbers out of range still pass.
+pub(crate) fn is_valid_number(buf: &[u8]) -> bool {
+    // The end of the input terminates the number like whitespace does
+    match number_len(buf) {
+        Some(i) => is_structural_or_whitespace(buf.get(i).copied().unwrap_or(b' ')) == 1,
+        None => false,
+    }
+}
+
+/// Length of the number following the JSON grammar at the start of `buf`,
+/// `None` if there is none.
+pub(crate) fn number_len(buf: &[u8]) -> Option<usize> {
+    // Anything past the end stops the number
+    let at = |i: usize| buf.get(i).copied().unwrap_or(b' ');
+    let digits = |mut i: usize| {
+        while buf.len() >= i + 8 && is_made_of_eight_digits_fast(&buf[i..]) {
+            i += 8;
+        }
+        while is_integer(at(i)) {
+            i += 1;
+        }
+        i
+    };
+    let mut i = if at(0) == b'-' { 1 } else { 0 };
+    i = match at(i) {
+        b'0' => i + 1,
+        b'1'..=b'9' => digits(i),
+        _ => return None,
+    };
+    if at(i) == b'.' {
+        if !is_integer(at(i + 1)) {
+            return None;
+        }
+        i = digits(i + 1);
+    }
+    if at(i) == b'e' || at(i) == b'E' {
+        i += 1;
+        if at(i) == b'+' || at(i) == b'-' {
+            i += 1;
+        }
+        if !is_integer(at(i)) {
+            return None;
+        }
+        i = digits(i);
+    }
+    Some(i)
+}
+
 // We need to check that the character following a zero is valid. This is
 // probably frequent and it is hard than it looks. We are building all of this
 // just to differentiate between 0x1 (invalid), 0,1 (valid) 0e1 (valid)...
@@ -103,7 +158,7 @@ fn is_not_structural_or_whitespace_or_exponent_or_decimal(c: u8) -> bool {
 fn is_made_of_eight_digits_fast(chars: &[u8]) -> bool {
     // We know what we're doing right? :P
     #[allow(clippy::cast_ptr_alignment)]
-    let val: u64 = unsafe { *(chars.as_ptr() as *const u64) };
+    let val: u64 = unsafe { std::ptr::read_unaligned(chars.as_ptr() as *const u64) };
 
     //    let val: __m64 = *(chars as *const __m64);
     // a branchy method might be faster:
@@ -115,16 +170,108 @@ fn is_made_of_eight_digits_fast(chars: &[u8]) -> bool {
         == 0x3333_3333_3333_3333)
 }
 
+/// The number of significant decimal digits an `f64` can hold without loss
+#[cfg(feature = "arbitrary_precision")]
+const F64_SIGNIFICANT_DIGITS: usize = 15;
+
+/// Splits a valid number literal into its sign, significant digits and the
+/// decimal exponent of the first of them so literals with the same value
+/// compare equal regardless of their notation.
+#[cfg(feature = "arbitrary_precision")]
+fn normalize_literal(s: &[u8]) -> (bool, Vec<u8>, i64) {
+    let negative = s.first() == Some(&b'-');
+    let mut digits = Vec::with_capacity(s.len());
+    let mut point: i64 = 0;
+    let mut seen_point = false;
+    let mut exponent: i64 = 0;
+    for (i, c) in s.iter().enumerate() {
+        match *c {
+            b'0'..=b'9' => {
+                if !digits.is_empty() || *c != b'0' {
+                    digits.push(*c);
+                    if !seen_point {
+                        point += 1;
+                    }
+                } else if seen_point {
+                    point -= 1;
+                }
+            }
+            b'.' => seen_point = true,
+            b'e' | b'E' => {
+                exponent = std::str::from_utf8(&s[i + 1..])
+                    .ok()
+                    .and_then(|e| e.parse().ok())
+                    .unwrap_or(i64::max_value());
+                break;
+            }
+            _ => (),
+        }
+    }
+    while digits.last() == Some(&b'0') {
+        digits.pop();
+    }
+    (negative, digits, point.saturating_add(exponent))
+}
+
+/// A number as it was parsed from the input, exported as `ParsedNumber` to
+/// tell it apart from `serde::Number`
+#[derive(Debug, Clone, PartialEq)]
 pub enum Number {
+    /// A floating point number
     F64(f64),
+    /// A negative integer or a positive one that fits into an `i64`
     I64(i64),
+    /// A positive integer too large for an `i64`
+    U64(u64),
+    /// The literal of a number that can't be represented by any of the
+    /// above without loss of precision
+    #[cfg(feature = "arbitrary_precision")]
+    Raw(String),
+}
+
+#[cfg_attr(not(feature = "no-inline"), inline)]
+#[cfg(all(
+    any(target_arch = "x86", target_arch = "x86_64"),
+    target_feature = "sse4.1"
+))]
+fn parse_eight_digits_unrolled(chars: &[u8]) -> u32 {
+    unsafe { parse_eight_digits_sse41(chars) }
 }
 
+// Without SSE4.1 at compile time the CPU is asked for it at runtime, the
+// answer is cached so this is only a load and a branch.
 #[cfg_attr(not(feature = "no-inline"), inline)]
-#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[cfg(all(
+    feature = "runtime-detection",
+    any(target_arch = "x86", target_arch = "x86_64"),
+    not(target_feature = "sse4.1")
+))]
 fn parse_eight_digits_unrolled(chars: &[u8]) -> u32 {
+    if is_x86_feature_detected!("sse4.1") {
+        unsafe { parse_eight_digits_sse41(chars) }
+    } else {
+        parse_eight_digits_swar(chars)
+    }
+}
+
+#[cfg_attr(not(feature = "no-inline"), inline)]
+#[cfg(not(all(
+    any(target_arch = "x86", target_arch = "x86_64"),
+    any(feature = "runtime-detection", target_feature = "sse4.1")
+)))]
+fn parse_eight_digits_unrolled(chars: &[u8]) -> u32 {
+    parse_eight_digits_swar(chars)
+}
+
+#[cfg_attr(not(feature = "no-inline"), inline)]
+#[cfg(all(
+    any(target_arch = "x86", target_arch = "x86_64"),
+    any(feature = "runtime-detection", target_feature = "sse4.1")
+))]
+#[target_feature(enable = "sse4.1")]
+unsafe fn parse_eight_digits_sse41(chars: &[u8]) -> u32 {
     #[allow(clippy::cast_sign_loss, clippy::cast_possible_wrap)]
-    unsafe {
+    {
         // this actually computes *16* values so we are being wasteful.
         let ascii0: __m128i = _mm_set1_epi8(b'0' as i8);
         let mul_1_10: __m128i =
@@ -134,7 +281,7 @@ fn parse_eight_digits_unrolled(chars: &[u8]) -> u32 {
         // We know what we're doing right? :P
         #[allow(clippy::cast_ptr_alignment)]
         let input: __m128i = _mm_sub_epi8(
-            _mm_loadu_si128(chars.get_unchecked(0..16).as_ptr() as *const __m128i),
+            _mm_loadl_epi64(chars.get_unchecked(0..8).as_ptr() as *const __m128i),
             ascii0,
         );
         let t1: __m128i = _mm_maddubs_epi16(input, mul_1_10);
@@ -145,15 +292,22 @@ fn parse_eight_digits_unrolled(chars: &[u8]) -> u32 {
     }
 }
 
+// SWAR version for everything that can't use the SSE4.1 one above, the digits
+// have to be read little endian for the multiplications to line up.
 #[cfg_attr(not(feature = "no-inline"), inline)]
-#[cfg(target_feature = "neon")]
-fn parse_eight_digits_unrolled(chars: &[u8]) -> u32 {
-    let val: u64 = unsafe { *(chars.as_ptr() as *const u64) };
-    //    memcpy(&val, chars, sizeof(u64));
-    let val = (val & 0x0F0F0F0F0F0F0F0F).wrapping_mul(2561) >> 8;
-    let val = (val & 0x00FF00FF00FF00FF).wrapping_mul(6553601) >> 16;
-
-    return ((val & 0x0000FFFF0000FFFF).wrapping_mul(42949672960001) >> 32) as u32;
+#[cfg(not(all(
+    any(target_arch = "x86", target_arch = "x86_64"),
+    target_feature = "sse4.1"
+)))]
+#[allow(clippy::cast_possible_truncation)]
+fn parse_eight_digits_swar(chars: &[u8]) -> u32 {
+    let mut bytes = [0_u8; 8];
+    bytes.copy_from_slice(unsafe { chars.get_unchecked(0..8) });
+    let val = u64::from_le_bytes(bytes);
+    let val = (val & 0x0F0F_0F0F_0F0F_0F0F).wrapping_mul(2561) >> 8;
+    let val = (val & 0x00FF_00FF_00FF_00FF).wrapping_mul(6_553_601) >> 16;
+
+    ((val & 0x0000_FFFF_0000_FFFF).wrapping_mul(42_949_672_960_001) >> 32) as u32
 }
 
 impl<'de> Deserializer<'de> {
@@ -283,6 +437,78 @@ impl<'de> Deserializer<'de> {
         }
     }
 
+    /// called instead of `parse_float` or raising an overflow when the
+    /// `arbitrary_precision` feature is enabled. Integers reaching this
+    /// overflowed and floats that can't be turned into a `f64` and back
+    /// without changing their value are kept verbatim, everything else is
+    /// parsed as a `f64`.
+    ///
+    /// This function will almost never be called!!!
+    ///
+    #[cfg(feature = "arbitrary_precision")]
+    #[inline(never)]
+    fn parse_arbitrary_precision(&self, buf: &[u8], negative: bool) -> Result<Number> {
+        let mut byte_count = if negative { 1 } else { 0 };
+        let mut float = false;
+        let mut d = unsafe { *buf.get_unchecked(byte_count) };
+        if d == b'0' {
+            byte_count += 1;
+            d = unsafe { *buf.get_unchecked(byte_count) };
+        } else if is_integer(d) {
+            while is_integer(d) {
+                byte_count += 1;
+                d = unsafe { *buf.get_unchecked(byte_count) };
+            }
+        } else {
+            return Err(self.error(ErrorType::InvalidNumber));
+        }
+        if d == b'.' {
+            float = true;
+            byte_count += 1;
+            d = unsafe { *buf.get_unchecked(byte_count) };
+            if !is_integer(d) {
+                return Err(self.error(ErrorType::InvalidNumber));
+            }
+            while is_integer(d) {
+                byte_count += 1;
+                d = unsafe { *buf.get_unchecked(byte_count) };
+            }
+        }
+        if (d == b'e') || (d == b'E') {
+            float = true;
+            byte_count += 1;
+            d = unsafe { *buf.get_unchecked(byte_count) };
+            if (d == b'-') || (d == b'+') {
+                byte_count += 1;
+                d = unsafe { *buf.get_unchecked(byte_count) };
+            }
+            if !is_integer(d) {
+                return Err(self.error(ErrorType::InvalidNumber));
+            }
+            while is_integer(d) {
+                byte_count += 1;
+                d = unsafe { *buf.get_unchecked(byte_count) };
+            }
+        }
+        if is_structural_or_whitespace(d) == 0 {
+            return Err(self.error(ErrorType::InvalidNumber));
+        }
+        // We validated the literal above so it is pure ASCII
+        let raw = unsafe { std::str::from_utf8_unchecked(buf.get_unchecked(..byte_count)) };
+        if float {
+            if let Ok(f) = raw.parse::<f64>() {
+                let mut shortest = ryu::Buffer::new();
+                if f.is_finite()
+                    && normalize_literal(shortest.format_finite(f).as_bytes())
+                        == normalize_literal(raw.as_bytes())
+                {
+                    return Ok(Number::F64(f));
+                }
+            }
+        }
+        Ok(Number::Raw(raw.to_owned()))
+    }
+
     /// called by parse_number when we know that the output is an integer,
     /// but where there might be some integer overflow.
     /// we want to catch overflows!
@@ -318,6 +544,9 @@ impl<'de> Deserializer<'de> {
                 {
                     i = i1;
                 } else {
+                    #[cfg(feature = "arbitrary_precision")]
+                    return self.parse_arbitrary_precision(buf, negative);
+                    #[cfg(not(feature = "arbitrary_precision"))]
                     return Err(self.error(ErrorType::Overflow));
                 }
                 digitcount += 1;
@@ -325,19 +554,21 @@ impl<'de> Deserializer<'de> {
             }
         }
 
-        if negative {
-            if i >= 9_223_372_036_854_775_808 {
-                //i64::min_value() * -1
-                return Err(self.error(ErrorType::Overflow));
-            }
-        } else if i > i64::max_value() as u64 {
+        if negative && i > 9_223_372_036_854_775_808 {
+            //i64::min_value() * -1
+            #[cfg(feature = "arbitrary_precision")]
+            return self.parse_arbitrary_precision(buf, negative);
+            #[cfg(not(feature = "arbitrary_precision"))]
             return Err(self.error(ErrorType::Overflow));
         }
 
         if is_structural_or_whitespace(d) == 0 {
             Err(self.error(ErrorType::InvalidNumber))
         } else if negative {
-            Ok(Number::I64(-(i as i64)))
+            Ok(Number::I64((i as i64).wrapping_neg()))
+        } else if i > i64::max_value() as u64 {
+            // only positive numbers that don't fit into a i64 become a u64
+            Ok(Number::U64(i))
         } else {
             Ok(Number::I64(i as i64))
         }
@@ -380,6 +611,9 @@ impl<'de> Deserializer<'de> {
                 if let Some(i1) = i.checked_add(u64::from(digit)) {
                     i = i1;
                 } else {
+                    #[cfg(feature = "arbitrary_precision")]
+                    return self.parse_arbitrary_precision(buf, negative);
+                    #[cfg(not(feature = "arbitrary_precision"))]
                     return Err(self.error(ErrorType::Overflow));
                 }
                 //i = 10 * i + u64::from(digit); // might overflow
@@ -405,10 +639,9 @@ impl<'de> Deserializer<'de> {
 
             #[cfg(feature = "swar-number-parsing")]
             {
-                // FIXME
-                // can we omit this: buf.len() - byte_count >= 8
-
-                if is_made_of_eight_digits_fast(unsafe { buf.get_unchecked(byte_count..) }) {
+                if buf.len() - byte_count >= 8
+                    && is_made_of_eight_digits_fast(unsafe { buf.get_unchecked(byte_count..) })
+                {
                     i = i.wrapping_mul(100_000_000).wrapping_add(u64::from(
                         parse_eight_digits_unrolled(unsafe { buf.get_unchecked(byte_count..) }),
                     ));
@@ -471,6 +704,13 @@ impl<'de> Deserializer<'de> {
             exponent += i64::from(if negexp { -expnumber } else { expnumber });
         }
         let v = if (exponent != 0) || (expnumber != 0) {
+            #[cfg(feature = "arbitrary_precision")]
+            {
+                let digits = byte_count - ignore_count as usize - if negative { 1 } else { 0 };
+                if unlikely!(digits > F64_SIGNIFICANT_DIGITS) {
+                    return self.parse_arbitrary_precision(buf, negative);
+                }
+            }
             if unlikely!((byte_count - ignore_count as usize) >= 19) {
                 // this is uncommon!!!
                 // this is almost never going to get called!!!
@@ -484,13 +724,23 @@ impl<'de> Deserializer<'de> {
                 Number::F64(0.0)
             } else {
                 if (exponent > 308) || (exponent < -323) {
+                    #[cfg(feature = "arbitrary_precision")]
+                    return self.parse_arbitrary_precision(buf, negative);
                     //FIXME Parse it as a expensive float perhaps
+                    #[cfg(not(feature = "arbitrary_precision"))]
                     return self.parse_float(buf, negative);
                 }
 
                 #[allow(clippy::cast_precision_loss)]
                 let mut d1: f64 = i as f64;
                 d1 *= POWER_OF_TEN[(323 + exponent) as usize];
+                // overflows and subnormals might not hold the literal's value
+                #[cfg(feature = "arbitrary_precision")]
+                {
+                    if unlikely!(!d1.is_normal()) {
+                        return self.parse_arbitrary_precision(buf, negative);
+                    }
+                }
                 Number::F64(if negative { d1 * -1.0 } else { d1 })
             }
         } else {
@@ -513,10 +763,41 @@ impl<'de> Deserializer<'de> {
 mod test {
     #![allow(clippy::default_trait_access)]
     use crate::value::owned::to_value;
-    use crate::value::ValueTrait;
+    use crate::value::{ValueTrait, ValueType};
     use float_cmp::approx_eq;
 
     #[test]
+    fn valid_number() {
+        use super::is_valid_number;
+        for n in &[
+            "0", "-0", "12", "1.5", "-0.5e-3", "2E+10", "1e400", "7]", "3,",
+        ] {
+            assert!(is_valid_number(n.as_bytes()), "{}", n);
+        }
+        for n in &["-", "01", "1.", ".5", "1e", "1e+", "2.x", "-a", "1x"] {
+            assert!(!is_valid_number(n.as_bytes()), "{}", n);
+        }
+    }
+
+    #[test]
+    #[cfg(all(
+        feature = "runtime-detection",
+        any(target_arch = "x86", target_arch = "x86_64"),
+        not(target_feature = "sse4.1")
+    ))]
+    fn eight_digits() {
+        use super::{parse_eight_digits_sse41, parse_eight_digits_swar};
+        for d in &["00000000", "12345678", "99999999", "10000001"] {
+            let n = parse_eight_digits_swar(d.as_bytes());
+            assert_eq!(n, d.parse::<u32>().expect("digits"));
+            if is_x86_feature_detected!("sse4.1") {
+                assert_eq!(unsafe { parse_eight_digits_sse41(d.as_bytes()) }, n);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "arbitrary_precision"))]
     fn bad_exp() {
         let mut too_big = String::from("1e309");
         let mut too_big = unsafe { too_big.as_bytes_mut() };
@@ -540,6 +821,76 @@ mod test {
         assert!(r.is_err());
     }
 
+    #[test]
+    fn large_unsigned() {
+        let mut i = String::from("18446744073709551615");
+        let mut i = unsafe { i.as_bytes_mut() };
+        let r = to_value(&mut i).expect("failed to decode");
+        assert_eq!(r.value_type(), ValueType::U64);
+        assert_eq!(r.as_u64(), Some(u64::max_value()));
+        assert_eq!(r.as_i64(), None);
+
+        let mut i = String::from("9223372036854775807");
+        let mut i = unsafe { i.as_bytes_mut() };
+        let r = to_value(&mut i).expect("failed to decode");
+        assert_eq!(r.value_type(), ValueType::I64);
+        assert_eq!(r.as_i64(), Some(i64::max_value()));
+
+        let mut i = String::from("-9223372036854775808");
+        let mut i = unsafe { i.as_bytes_mut() };
+        let r = to_value(&mut i).expect("failed to decode");
+        assert_eq!(r.as_i64(), Some(i64::min_value()));
+    }
+
+    #[test]
+    #[cfg(not(feature = "arbitrary_precision"))]
+    fn integer_overflow() {
+        let mut i = String::from("18446744073709551616");
+        let mut i = unsafe { i.as_bytes_mut() };
+        assert!(to_value(&mut i).is_err());
+        let mut i = String::from("-9223372036854775809");
+        let mut i = unsafe { i.as_bytes_mut() };
+        assert!(to_value(&mut i).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "arbitrary_precision")]
+    fn arbitrary_precision() {
+        use crate::OwnedValue;
+        for raw in &[
+            "18446744073709551616",
+            "-9223372036854775809",
+            "123456789012345678901234567890",
+            "0.10000000000000000001",
+            "-1.234567890123456789e-5",
+            "1e400",
+            "-2.5E+309",
+            "1e-400",
+            "1.23456789012345e-320",
+        ] {
+            let mut i = String::from(*raw);
+            let mut i = unsafe { i.as_bytes_mut() };
+            let r = to_value(&mut i).expect("failed to decode");
+            assert_eq!(r.value_type(), ValueType::Number);
+            assert_eq!(r, OwnedValue::Number(raw.to_string()));
+            assert_eq!(r.encode(), *raw);
+        }
+        // numbers a f64 can hold are still parsed as such
+        for (raw, f) in &[
+            ("2.3250706903316115e307", 2.325_070_690_331_611_5e307),
+            ("0.1000000000000000000", 0.1),
+            ("1.5", 1.5),
+            ("1e308", 1e308),
+            ("5e-324", 5e-324),
+            ("1.2345e-310", 1.2345e-310),
+        ] {
+            let mut i = String::from(*raw);
+            let mut i = unsafe { i.as_bytes_mut() };
+            let r = to_value(&mut i).expect("failed to decode");
+            assert_eq!(r.as_f64(), Some(*f));
+        }
+    }
+
     #[test]
     fn zero() {
         let mut i = String::from("0");
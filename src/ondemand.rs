@@ -0,0 +1,406 @@
+use crate::numberparse::Number;
+use crate::{Deserializer, Error, ErrorType, Result};
+use std::borrow::Cow;
+
+/// A document that is navigated lazily instead of being parsed into a DOM.
+///
+/// Only stage 1 and the structural validation run up front, strings and
+/// numbers are decoded when they are accessed and everything that isn't
+/// looked at is jumped over. Navigation is forward only, fields of an
+/// object have to be looked up in the order they appear in the document.
+///
+/// ```
+/// use simd_json::ondemand::Document;
+///
+/// let d = br#"{"user": {"name": "snot", "id": 42}, "tags": [1, 2]}"#;
+/// let mut doc = Document::from_slice(d).unwrap();
+/// let id = doc
+///     .object()
+///     .and_then(|mut o| o.find_field("user")?.object()?.find_field("id")?.get_u64());
+/// assert_eq!(id.unwrap(), 42);
+/// ```
+pub struct Document<'de> {
+    de: Deserializer<'de>,
+}
+
+impl<'de> Document<'de> {
+    /// Finds the structure of `input`, the input itself is left untouched.
+    pub fn from_slice(input: &'de [u8]) -> Result<Self> {
+        let de = stry!(Deserializer::from_slice_ref(input));
+        Ok(Self { de })
+    }
+
+    /// The root value of the document, every call starts from the
+    /// beginning again.
+    pub fn root(&mut self) -> Value<'_, 'de> {
+        Value {
+            de: &mut self.de,
+            idx: 1,
+        }
+    }
+
+    /// Same as `root().object()`
+    pub fn object(&mut self) -> Result<Object<'_, 'de>> {
+        self.root().object()
+    }
+
+    /// Same as `root().array()`
+    pub fn array(&mut self) -> Result<Array<'_, 'de>> {
+        self.root().array()
+    }
+}
+
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+fn char_at(de: &Deserializer, idx: usize) -> u8 {
+    unsafe {
+        let iidx = *de.structural_indexes.get_unchecked(idx) as usize;
+        *de.input.as_slice().get_unchecked(iidx)
+    }
+}
+
+/// Moves the deserializer onto the structural at `idx`
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+fn seek(de: &mut Deserializer, idx: usize) {
+    de.idx = idx;
+    de.iidx = unsafe { *de.structural_indexes.get_unchecked(idx) as usize };
+}
+
+/// Index of the last structural of the value starting at `idx`
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+fn value_end(de: &Deserializer, idx: usize) -> usize {
+    match char_at(de, idx) {
+        b'[' | b'{' => de.container_end(idx),
+        _ => idx,
+    }
+}
+
+/// Index of the element following the value starting at `idx`, or of the
+/// closing structural if it was the last one
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+fn next_element(de: &Deserializer, idx: usize) -> usize {
+    let last = value_end(de, idx);
+    if char_at(de, last + 1) == b',' {
+        last + 2
+    } else {
+        last + 1
+    }
+}
+
+/// A value that hasn't been decoded yet
+pub struct Value<'d, 'de> {
+    de: &'d mut Deserializer<'de>,
+    idx: usize,
+}
+
+impl<'d, 'de> Value<'d, 'de> {
+    #[cold]
+    fn error(&mut self, error: ErrorType) -> Error {
+        seek(self.de, self.idx);
+        let e = self.de.error(error);
+        self.de.locate(e)
+    }
+
+    /// Returns true if the value is `null`
+    pub fn is_null(&self) -> bool {
+        char_at(self.de, self.idx) == b'n'
+    }
+
+    /// Decodes a boolean
+    pub fn get_bool(mut self) -> Result<bool> {
+        match char_at(self.de, self.idx) {
+            b't' => Ok(true),
+            b'f' => Ok(false),
+            _ => Err(self.error(ErrorType::ExpectedBoolean)),
+        }
+    }
+
+    /// Decodes a string, strings without escapes are borrowed from the input
+    pub fn get_str(mut self) -> Result<&'d str> {
+        if char_at(self.de, self.idx) != b'"' {
+            return Err(self.error(ErrorType::ExpectedString));
+        }
+        seek(self.de, self.idx);
+        let input = self.de.input;
+        Ok(
+            match stry!(self.de.parse_str_().map_err(|e| e.locate(input.as_slice()))) {
+                crate::ParsedStr::Borrowed(s) => s,
+                crate::ParsedStr::Copied(s) => s,
+            },
+        )
+    }
+
+    fn get_number(&mut self, expected: ErrorType) -> Result<Number> {
+        seek(self.de, self.idx);
+        // a number at the root isn't followed by any padding
+        let root = self.idx == 1;
+        let n = match char_at(self.de, self.idx) {
+            b'-' if root => self.de.parse_number_root(true),
+            b'-' => self.de.parse_number(true),
+            b'0'..=b'9' if root => self.de.parse_number_root(false),
+            b'0'..=b'9' => self.de.parse_number(false),
+            _ => Err(self.de.error(expected)),
+        };
+        n.map_err(|e| self.de.locate(e))
+    }
+
+    /// Decodes a signed integer
+    pub fn get_i64(mut self) -> Result<i64> {
+        match stry!(self.get_number(ErrorType::ExpectedSigned)) {
+            Number::I64(n) => Ok(n),
+            _ => Err(self.error(ErrorType::ExpectedSigned)),
+        }
+    }
+
+    /// Decodes an unsigned integer
+    #[allow(clippy::cast_sign_loss)]
+    pub fn get_u64(mut self) -> Result<u64> {
+        match stry!(self.get_number(ErrorType::ExpectedUnsigned)) {
+            Number::I64(n) if n >= 0 => Ok(n as u64),
+            Number::U64(n) => Ok(n),
+            _ => Err(self.error(ErrorType::ExpectedUnsigned)),
+        }
+    }
+
+    /// Decodes a number as float
+    #[allow(clippy::cast_precision_loss)]
+    pub fn get_f64(mut self) -> Result<f64> {
+        match stry!(self.get_number(ErrorType::ExpectedFloat)) {
+            Number::F64(n) => Ok(n),
+            Number::I64(n) => Ok(n as f64),
+            Number::U64(n) => Ok(n as f64),
+            #[cfg(feature = "arbitrary_precision")]
+            Number::Raw(n) => n.parse().map_err(|_| self.error(ErrorType::ExpectedFloat)),
+        }
+    }
+
+    /// Enters an object
+    pub fn object(mut self) -> Result<Object<'d, 'de>> {
+        if char_at(self.de, self.idx) != b'{' {
+            return Err(self.error(ErrorType::ExpectedMap));
+        }
+        Ok(Object {
+            start: self.idx,
+            pos: self.idx + 1,
+            end: value_end(self.de, self.idx),
+            de: self.de,
+        })
+    }
+
+    /// Enters an array
+    pub fn array(mut self) -> Result<Array<'d, 'de>> {
+        if char_at(self.de, self.idx) != b'[' {
+            return Err(self.error(ErrorType::ExpectedArray));
+        }
+        Ok(Array {
+            start: self.idx,
+            pos: self.idx + 1,
+            end: value_end(self.de, self.idx),
+            de: self.de,
+        })
+    }
+}
+
+/// An object whose fields are visited in document order
+pub struct Object<'d, 'de> {
+    de: &'d mut Deserializer<'de>,
+    start: usize,
+    /// Index of the next key or of the closing `}`
+    pos: usize,
+    end: usize,
+}
+
+impl<'d, 'de> Object<'d, 'de> {
+    /// Number of fields in the object
+    pub fn len(&self) -> usize {
+        unsafe { self.de.counts.get_unchecked(self.start).0 as usize }
+    }
+
+    /// Returns true if the object has no fields
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Moves past the next field, returns the index of its key and value
+    #[cfg_attr(not(feature = "no-inline"), inline(always))]
+    fn advance(&mut self) -> (usize, usize) {
+        let key = self.pos;
+        let value = key + 2;
+        self.pos = next_element(self.de, value);
+        (key, value)
+    }
+
+    /// Looks for the field `key` after the fields already visited, the
+    /// values of the fields in between are skipped without decoding them.
+    pub fn find_field(&mut self, key: &str) -> Result<Value<'_, 'de>> {
+        let input = self.de.input;
+        while self.pos < self.end {
+            let (k, v) = self.advance();
+            seek(self.de, k);
+            if stry!(self.de.parse_str_().map_err(|e| e.locate(input.as_slice()))).as_str() == key {
+                return Ok(Value {
+                    de: self.de,
+                    idx: v,
+                });
+            }
+        }
+        seek(self.de, self.end);
+        let e = self.de.error(ErrorType::NoSuchField);
+        Err(self.de.locate(e))
+    }
+
+    /// Returns the next field, or `None` once all fields are visited
+    pub fn next_field(&mut self) -> Result<Option<(Cow<'de, str>, Value<'_, 'de>)>> {
+        if self.pos >= self.end {
+            return Ok(None);
+        }
+        let (k, v) = self.advance();
+        seek(self.de, k);
+        let input = self.de.input;
+        let key = stry!(self.de.parse_str_().map_err(|e| e.locate(input.as_slice()))).into_cow();
+        Ok(Some((
+            key,
+            Value {
+                de: self.de,
+                idx: v,
+            },
+        )))
+    }
+}
+
+/// An array whose elements are visited in document order
+pub struct Array<'d, 'de> {
+    de: &'d mut Deserializer<'de>,
+    start: usize,
+    /// Index of the next element or of the closing `]`
+    pos: usize,
+    end: usize,
+}
+
+impl<'d, 'de> Array<'d, 'de> {
+    /// Number of elements in the array
+    pub fn len(&self) -> usize {
+        unsafe { self.de.counts.get_unchecked(self.start).0 as usize }
+    }
+
+    /// Returns true if the array has no elements
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the next element, or `None` once all elements are visited
+    pub fn next_element(&mut self) -> Option<Value<'_, 'de>> {
+        if self.pos >= self.end {
+            return None;
+        }
+        let idx = self.pos;
+        self.pos = next_element(self.de, idx);
+        Some(Value { de: self.de, idx })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Document;
+    use crate::ErrorType;
+
+    const DOC: &[u8] = br#"{
+        "skip": {"a": [1, 2, {"b": "c"}], "d": "e\"f"},
+        "user": {"name": "s\nnot", "id": 42, "neg": -3},
+        "tags": [1.5, true, null, [], {}, "x"],
+        "big": 18446744073709551615
+    }"#;
+
+    #[test]
+    fn find_field() {
+        let mut doc = Document::from_slice(DOC).expect("valid");
+        let mut o = doc.object().expect("object");
+        assert_eq!(o.len(), 4);
+        {
+            let mut user = o.find_field("user").and_then(|v| v.object()).expect("user");
+            let name = user.find_field("name").and_then(|v| v.get_str());
+            assert_eq!(name.expect("name"), "s\nnot");
+            assert_eq!(
+                user.find_field("neg").and_then(|v| v.get_i64()).ok(),
+                Some(-3)
+            );
+            let e = user.find_field("id").err().expect("already passed");
+            assert_eq!(e.error_type(), &ErrorType::NoSuchField);
+        }
+        let big = o.find_field("big").and_then(|v| v.get_u64());
+        assert_eq!(big.ok(), Some(u64::max_value()));
+        assert!(o.find_field("tags").is_err());
+
+        let mut o = doc.object().expect("restart");
+        let id = o
+            .find_field("user")
+            .and_then(|v| v.object()?.find_field("id")?.get_u64());
+        assert_eq!(id.ok(), Some(42));
+    }
+
+    #[test]
+    fn iterate() {
+        let mut doc = Document::from_slice(DOC).expect("valid");
+        let mut o = doc.object().expect("object");
+        let mut keys = Vec::new();
+        while let Some((k, _)) = o.next_field().expect("field") {
+            keys.push(k);
+        }
+        assert_eq!(keys, vec!["skip", "user", "tags", "big"]);
+
+        let mut o = doc.object().expect("object");
+        let mut tags = o.find_field("tags").and_then(|v| v.array()).expect("tags");
+        assert_eq!(tags.len(), 6);
+        let e = tags.next_element().expect("element");
+        assert_eq!(e.get_f64().ok(), Some(1.5));
+        let e = tags.next_element().expect("element");
+        assert_eq!(e.get_bool().ok(), Some(true));
+        assert!(tags.next_element().expect("element").is_null());
+        let e = tags.next_element().expect("element");
+        assert!(e.array().expect("array").next_element().is_none());
+        let e = tags.next_element().expect("element");
+        assert!(e.object().expect("object").is_empty());
+        let e = tags.next_element().expect("element");
+        assert_eq!(e.get_str().ok(), Some("x"));
+        assert!(tags.next_element().is_none());
+    }
+
+    #[test]
+    fn scalars() {
+        let mut doc = Document::from_slice(b"-12").expect("valid");
+        assert_eq!(doc.root().get_i64().ok(), Some(-12));
+        assert_eq!(doc.root().get_f64().ok(), Some(-12.0));
+        let e = doc.root().get_u64().expect_err("signed");
+        assert_eq!(e.error_type(), &ErrorType::ExpectedUnsigned);
+        let e = doc.object().err().expect("not an object");
+        assert_eq!(e.error_type(), &ErrorType::ExpectedMap);
+
+        let mut doc = Document::from_slice(br#""snot""#).expect("valid");
+        assert_eq!(doc.root().get_str().ok(), Some("snot"));
+        assert!(doc.root().get_bool().is_err());
+        assert!(doc.array().is_err());
+
+        assert!(Document::from_slice(b"[1, 2").is_err());
+    }
+
+    #[test]
+    fn long_root_number() {
+        // there is no padding after a number at the end of the input
+        let d = format!("{:0<40}", "1.");
+        let mut doc = Document::from_slice(d.as_bytes()).expect("valid");
+        assert_eq!(doc.root().get_f64().ok(), Some(1.0));
+        let d = "1".repeat(40);
+        let mut doc = Document::from_slice(d.as_bytes()).expect("valid");
+        assert_eq!(
+            doc.root().get_f64().is_ok(),
+            cfg!(feature = "arbitrary_precision")
+        );
+    }
+
+    #[test]
+    fn invalid_number() {
+        let mut doc = Document::from_slice(b"[1, 2.x]").expect("structure is valid");
+        let mut a = doc.array().expect("array");
+        assert_eq!(a.next_element().and_then(|v| v.get_u64().ok()), Some(1));
+        assert!(a.next_element().expect("element").get_f64().is_err());
+    }
+}
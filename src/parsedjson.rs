@@ -1 +1,635 @@
+use crate::numberparse::Number;
+use crate::value::{borrowed, owned};
+use crate::{
+    BorrowedValue, Buffers, Deserializer, ErrorType, Limit, Limits, OwnedValue, Result, ValueType,
+};
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str;
 
+const TYPE_SHIFT: u64 = 56;
+const PAYLOAD_MASK: u64 = (1 << TYPE_SHIFT) - 1;
+const COUNT_SHIFT: u64 = 32;
+const COUNT_MAX: usize = 0xFF_FFFF;
+const INDEX_MASK: u64 = (1 << COUNT_SHIFT) - 1;
+
+/// A document parsed into the flat tape format of the C++ simdjson library.
+/// Instead of allocating per container the whole document is stored in a
+/// single `Vec<u64>` tape and a buffer holding all strings.
+///
+/// Every tape word has its type in the top 8 bits and a 56 bit payload:
+///
+/// * `r` the root, the first word points to the last and the other way
+///   around, the document's value starts at index 1
+/// * `[` and `{` start a container, the low 32 bits hold the index after
+///   the matching end and the next 24 bits the number of elements
+/// * `]` and `}` end a container and point back to its start
+/// * `"` a string, the payload is its offset in the string buffer where a
+///   little endian `u32` length is followed by the bytes
+/// * `l`, `u` and `d` an `i64`, `u64` or `f64`, the value is the next word
+/// * `n`, `t` and `f` for `null`, `true` and `false`
+///
+/// Object keys are stored as a string right before their value.
+///
+/// ```
+/// use simd_json::ParsedJson;
+///
+/// let mut d = br#"{"name": "snot", "tags": [1, 2, 3]}"#.to_vec();
+/// let pj = ParsedJson::from_slice(&mut d).unwrap();
+/// let root = pj.root();
+/// assert_eq!(root.get("name").and_then(|c| c.as_str()), Some("snot"));
+/// let tags: Vec<_> = root.get("tags").unwrap().array_iter().unwrap().collect();
+/// assert_eq!(tags.len(), 3);
+/// assert_eq!(tags[2].as_u64(), Some(3));
+/// ```
+#[derive(Clone, PartialEq)]
+pub struct ParsedJson {
+    tape: Vec<u64>,
+    strings: Vec<u8>,
+}
+
+impl fmt::Debug for ParsedJson {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ParsedJson")
+            .field("tape", &self.tape.len())
+            .field("strings", &self.strings.len())
+            .finish()
+    }
+}
+
+impl ParsedJson {
+    /// Parses a slice of bytes into a tape. This function will rewrite the
+    /// slice to de-escape strings, the tape does not reference it though.
+    pub fn from_slice(input: &mut [u8]) -> Result<Self> {
+        Self::from_slice_with_buffers(input, &mut Buffers::default(), &Limits::default())
+    }
+
+    /// Same as `from_slice` but re-uses the scratch buffers passed in and
+    /// enforces the given limits.
+    pub(crate) fn from_slice_with_buffers(
+        input: &mut [u8],
+        buffers: &mut Buffers,
+        limits: &Limits,
+    ) -> Result<Self> {
+        let de = stry!(Deserializer::from_slice_with_buffers(
+            input, buffers, limits
+        ));
+        let mut builder = TapeBuilder {
+            tape: Vec::with_capacity(de.structural_indexes.len() + 2),
+            strings: Vec::new(),
+            de,
+        };
+        let res = builder.parse().map_err(|e| builder.de.locate(e));
+        let TapeBuilder { de, tape, strings } = builder;
+        *buffers = de.into_buffers();
+        res.map(|()| Self { tape, strings })
+    }
+
+    /// The raw tape, see `ParsedJson` for its format
+    pub fn tape(&self) -> &[u64] {
+        &self.tape
+    }
+
+    /// Returns a cursor at the root value of the document
+    pub fn root(&self) -> Cursor<'_> {
+        Cursor { pj: self, idx: 1 }
+    }
+
+    #[cfg_attr(not(feature = "no-inline"), inline(always))]
+    fn str_at(&self, offset: usize) -> &str {
+        let mut len = [0; 4];
+        len.copy_from_slice(&self.strings[offset..offset + 4]);
+        let len = u32::from_le_bytes(len) as usize;
+        // strings were validated as UTF-8 during parsing
+        unsafe { str::from_utf8_unchecked(&self.strings[offset + 4..offset + 4 + len]) }
+    }
+}
+
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+fn word(tag: u8, payload: u64) -> u64 {
+    u64::from(tag) << TYPE_SHIFT | payload
+}
+
+/// Appends a string to the string buffer, returns false if it is too long
+/// for its length to be stored
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+fn push_str(tape: &mut Vec<u64>, strings: &mut Vec<u8>, tag: u8, s: &str) -> bool {
+    if let Ok(len) = u32::try_from(s.len()) {
+        tape.push(word(tag, strings.len() as u64));
+        strings.extend_from_slice(&len.to_le_bytes());
+        strings.extend_from_slice(s.as_bytes());
+        true
+    } else {
+        false
+    }
+}
+
+struct TapeBuilder<'de> {
+    de: Deserializer<'de>,
+    tape: Vec<u64>,
+    strings: Vec<u8>,
+}
+
+impl<'de> TapeBuilder<'de> {
+    fn parse(&mut self) -> Result<()> {
+        self.tape.push(0);
+        stry!(self.parse_value(true));
+        let last = self.tape.len();
+        self.tape[0] = word(b'r', last as u64);
+        self.tape.push(word(b'r', 0));
+        Ok(())
+    }
+
+    #[cfg_attr(not(feature = "no-inline"), inline(always))]
+    fn parse_number(&mut self, root: bool, minus: bool) -> Result<()> {
+        let n = if root {
+            stry!(self.de.parse_number_root(minus))
+        } else {
+            stry!(self.de.parse_number_(minus))
+        };
+        match n {
+            Number::I64(i) => {
+                self.tape.push(word(b'l', 0));
+                self.tape.push(i as u64);
+            }
+            Number::U64(u) => {
+                self.tape.push(word(b'u', 0));
+                self.tape.push(u);
+            }
+            Number::F64(f) => {
+                self.tape.push(word(b'd', 0));
+                self.tape.push(f.to_bits());
+            }
+            #[cfg(feature = "arbitrary_precision")]
+            Number::Raw(n) => {
+                if !push_str(&mut self.tape, &mut self.strings, b'N', &n) {
+                    return Err(self.de.error(ErrorType::InvalidNumber));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_str(&mut self) -> Result<()> {
+        let s = stry!(self.de.parse_str_());
+        if push_str(&mut self.tape, &mut self.strings, b'"', s.as_str()) {
+            Ok(())
+        } else {
+            Err(self.de.error(ErrorType::LimitExceeded(Limit::StringLength)))
+        }
+    }
+
+    fn parse_value(&mut self, root: bool) -> Result<()> {
+        match self.de.next_() {
+            b'"' => self.parse_str(),
+            b'-' => self.parse_number(root, true),
+            b'0'..=b'9' => self.parse_number(root, false),
+            b'n' => {
+                self.tape.push(word(b'n', 0));
+                Ok(())
+            }
+            b't' => {
+                self.tape.push(word(b't', 0));
+                Ok(())
+            }
+            b'f' => {
+                self.tape.push(word(b'f', 0));
+                Ok(())
+            }
+            b'[' => self.parse_container(b'[', b']'),
+            b'{' => self.parse_container(b'{', b'}'),
+            _c => Err(self.de.error(ErrorType::UnexpectedCharacter)),
+        }
+    }
+
+    fn parse_container(&mut self, open: u8, close: u8) -> Result<()> {
+        let es = self.de.count_elements();
+        let start = self.tape.len();
+        self.tape.push(0);
+        if unlikely!(es == 0) {
+            self.de.skip();
+        }
+        for _ in 0..es {
+            if open == b'{' {
+                self.de.skip();
+                stry!(self.parse_str());
+                self.de.skip();
+            }
+            stry!(self.parse_value(false));
+            self.de.skip();
+        }
+        let end = self.tape.len();
+        self.tape.push(word(close, start as u64));
+        let count = es.min(COUNT_MAX) as u64;
+        self.tape[start] = word(open, count << COUNT_SHIFT | (end + 1) as u64);
+        Ok(())
+    }
+}
+
+/// A position on the tape of a `ParsedJson` pointing at a value
+#[derive(Clone, Copy)]
+pub struct Cursor<'t> {
+    pj: &'t ParsedJson,
+    idx: usize,
+}
+
+impl<'t> fmt::Debug for Cursor<'t> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Cursor({}, {:?})", self.idx, self.value_type())
+    }
+}
+
+impl<'t> Cursor<'t> {
+    #[cfg_attr(not(feature = "no-inline"), inline(always))]
+    fn tag(&self) -> u8 {
+        (self.pj.tape[self.idx] >> TYPE_SHIFT) as u8
+    }
+
+    #[cfg_attr(not(feature = "no-inline"), inline(always))]
+    fn payload(&self) -> u64 {
+        self.pj.tape[self.idx] & PAYLOAD_MASK
+    }
+
+    #[cfg_attr(not(feature = "no-inline"), inline(always))]
+    fn number(&self) -> u64 {
+        self.pj.tape[self.idx + 1]
+    }
+
+    /// Index of the first tape word after this value, containers are
+    /// jumped over as a whole.
+    #[cfg_attr(not(feature = "no-inline"), inline(always))]
+    fn next_index(&self) -> usize {
+        match self.tag() {
+            b'[' | b'{' => (self.payload() & INDEX_MASK) as usize,
+            b'l' | b'u' | b'd' => self.idx + 2,
+            _ => self.idx + 1,
+        }
+    }
+
+    /// Position of the value on the tape
+    pub fn index(&self) -> usize {
+        self.idx
+    }
+
+    /// Returns the type of the value
+    pub fn value_type(&self) -> ValueType {
+        match self.tag() {
+            b'n' => ValueType::Null,
+            b't' | b'f' => ValueType::Bool,
+            b'l' => ValueType::I64,
+            b'u' => ValueType::U64,
+            b'd' => ValueType::F64,
+            #[cfg(feature = "arbitrary_precision")]
+            b'N' => ValueType::Number,
+            b'"' => ValueType::String,
+            b'[' => ValueType::Array,
+            _ => ValueType::Object,
+        }
+    }
+
+    /// returns true if the value is null
+    pub fn is_null(&self) -> bool {
+        self.tag() == b'n'
+    }
+
+    /// Tries to represent the value as a bool
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.tag() {
+            b't' => Some(true),
+            b'f' => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Tries to represent the value as an i64
+    #[allow(clippy::cast_possible_wrap)]
+    pub fn as_i64(&self) -> Option<i64> {
+        match self.tag() {
+            b'l' => Some(self.number() as i64),
+            b'u' => i64::try_from(self.number()).ok(),
+            _ => None,
+        }
+    }
+
+    /// Tries to represent the value as an u64
+    pub fn as_u64(&self) -> Option<u64> {
+        #[allow(clippy::cast_possible_wrap)]
+        match self.tag() {
+            b'l' => u64::try_from(self.number() as i64).ok(),
+            b'u' => Some(self.number()),
+            _ => None,
+        }
+    }
+
+    /// Tries to represent the value as a f64
+    pub fn as_f64(&self) -> Option<f64> {
+        match self.tag() {
+            b'd' => Some(f64::from_bits(self.number())),
+            _ => None,
+        }
+    }
+
+    /// Tries to represent the value as a str, the string lives as long as
+    /// the tape
+    pub fn as_str(&self) -> Option<&'t str> {
+        match self.tag() {
+            b'"' => Some(self.pj.str_at(self.payload() as usize)),
+            _ => None,
+        }
+    }
+
+    /// returns true if the value is an array
+    pub fn is_array(&self) -> bool {
+        self.tag() == b'['
+    }
+
+    /// returns true if the value is an object
+    pub fn is_object(&self) -> bool {
+        self.tag() == b'{'
+    }
+
+    /// Number of elements of an array or object
+    pub fn len(&self) -> Option<usize> {
+        match self.tag() {
+            b'[' | b'{' => {
+                let count = (self.payload() >> COUNT_SHIFT) as usize;
+                if count < COUNT_MAX {
+                    Some(count)
+                } else if self.is_array() {
+                    self.array_iter().map(Iterator::count)
+                } else {
+                    self.object_iter().map(Iterator::count)
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// returns true if the value is an empty array or object
+    pub fn is_empty(&self) -> bool {
+        self.len() == Some(0)
+    }
+
+    /// Iterates over the elements of an array
+    pub fn array_iter(&self) -> Option<ArrayIter<'t>> {
+        if self.is_array() {
+            Some(ArrayIter {
+                pj: self.pj,
+                idx: self.idx + 1,
+                end: self.next_index() - 1,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Iterates over the keys and values of an object
+    pub fn object_iter(&self) -> Option<ObjectIter<'t>> {
+        if self.is_object() {
+            Some(ObjectIter {
+                pj: self.pj,
+                idx: self.idx + 1,
+                end: self.next_index() - 1,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Looks up a key in an object, the values of other keys are jumped over
+    /// without looking at them
+    pub fn get(&self, key: &str) -> Option<Cursor<'t>> {
+        self.object_iter()
+            .and_then(|mut i| i.find(|(k, _)| *k == key))
+            .map(|(_, v)| v)
+    }
+
+    /// Returns the element at index `i` of an array
+    pub fn get_idx(&self, i: usize) -> Option<Cursor<'t>> {
+        self.array_iter().and_then(|mut a| a.nth(i))
+    }
+
+    /// Converts the value and all its children into an `OwnedValue`
+    pub fn to_owned_value(&self) -> OwnedValue {
+        match self.tag() {
+            b'n' => OwnedValue::Null,
+            b't' => OwnedValue::Bool(true),
+            b'f' => OwnedValue::Bool(false),
+            b'l' | b'u' | b'd' => self.to_number().into(),
+            #[cfg(feature = "arbitrary_precision")]
+            b'N' => OwnedValue::Number(self.pj.str_at(self.payload() as usize).to_owned()),
+            b'"' => OwnedValue::String(self.pj.str_at(self.payload() as usize).to_owned()),
+            b'[' => OwnedValue::Array(
+                self.array_iter()
+                    .map_or_else(Vec::new, |a| a.map(|v| v.to_owned_value()).collect()),
+            ),
+            _ => {
+                let mut res = owned::Object::with_capacity(self.len().unwrap_or_default());
+                if let Some(o) = self.object_iter() {
+                    for (k, v) in o {
+                        res.insert_nocheck(k.to_owned(), v.to_owned_value());
+                    }
+                }
+                OwnedValue::Object(res)
+            }
+        }
+    }
+
+    /// Converts the value and all its children into a `BorrowedValue`, its
+    /// strings reference the tape's string buffer
+    pub fn to_borrowed_value(&self) -> BorrowedValue<'t> {
+        match self.tag() {
+            b'n' => BorrowedValue::Null,
+            b't' => BorrowedValue::Bool(true),
+            b'f' => BorrowedValue::Bool(false),
+            b'l' | b'u' | b'd' => self.to_number().into(),
+            #[cfg(feature = "arbitrary_precision")]
+            b'N' => BorrowedValue::Number(Cow::Borrowed(self.pj.str_at(self.payload() as usize))),
+            b'"' => BorrowedValue::String(Cow::Borrowed(self.pj.str_at(self.payload() as usize))),
+            b'[' => BorrowedValue::Array(
+                self.array_iter()
+                    .map_or_else(Vec::new, |a| a.map(|v| v.to_borrowed_value()).collect()),
+            ),
+            _ => {
+                let mut res = borrowed::Object::with_capacity(self.len().unwrap_or_default());
+                if let Some(o) = self.object_iter() {
+                    for (k, v) in o {
+                        res.insert_nocheck(Cow::Borrowed(k), v.to_borrowed_value());
+                    }
+                }
+                BorrowedValue::Object(res)
+            }
+        }
+    }
+
+    #[allow(clippy::cast_possible_wrap)]
+    fn to_number(self) -> Number {
+        match self.tag() {
+            b'l' => Number::I64(self.number() as i64),
+            b'u' => Number::U64(self.number()),
+            _ => Number::F64(f64::from_bits(self.number())),
+        }
+    }
+}
+
+/// Iterator over the elements of an array on a tape
+pub struct ArrayIter<'t> {
+    pj: &'t ParsedJson,
+    idx: usize,
+    end: usize,
+}
+
+impl<'t> Iterator for ArrayIter<'t> {
+    type Item = Cursor<'t>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.idx < self.end {
+            let c = Cursor {
+                pj: self.pj,
+                idx: self.idx,
+            };
+            self.idx = c.next_index();
+            Some(c)
+        } else {
+            None
+        }
+    }
+}
+
+/// Iterator over the keys and values of an object on a tape
+pub struct ObjectIter<'t> {
+    pj: &'t ParsedJson,
+    idx: usize,
+    end: usize,
+}
+
+impl<'t> Iterator for ObjectIter<'t> {
+    type Item = (&'t str, Cursor<'t>);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.idx < self.end {
+            let k = Cursor {
+                pj: self.pj,
+                idx: self.idx,
+            };
+            let v = Cursor {
+                pj: self.pj,
+                idx: self.idx + 1,
+            };
+            self.idx = v.next_index();
+            Some((k.as_str().unwrap_or_default(), v))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Cursor, ParsedJson};
+    use crate::{to_owned_value, BorrowedValue, ErrorType, Parser, ValueType};
+
+    fn parse(s: &str) -> ParsedJson {
+        ParsedJson::from_slice(&mut s.as_bytes().to_vec()).expect("parse")
+    }
+
+    #[test]
+    fn tape_layout() {
+        let pj = parse(r#"{"a": [1, -2, "x"], "b": null}"#);
+        let t = |w: u64| (w >> 56) as u8 as char;
+        let tags: String = pj.tape().iter().map(|w| t(*w)).collect();
+        // the number values take up a word each, small integers have a `\0` tag
+        assert_eq!(
+            tags.replace(&['\0', '\u{ff}'][..], "_"),
+            r#"r{"[l_l_"]"n}r"#
+        );
+        let root = pj.root();
+        assert_eq!(root.index(), 1);
+        assert_eq!(pj.tape()[0] & 0xFF_FFFF, pj.tape().len() as u64 - 1);
+        // containers point past their end so they can be skipped
+        let a = root.get("a").expect("a");
+        assert_eq!(a.index(), 3);
+        assert_eq!(a.next_index(), 10);
+        assert_eq!(root.get("b").map(|b| b.index()), Some(11));
+    }
+
+    #[test]
+    fn cursor() {
+        let pj = parse(
+            r#"{"s": "snot\nbadger", "i": -1, "u": 18446744073709551615, "f": 0.5,
+                "t": true, "n": null, "a": [[], {}, [1, [2]]], "o": {"k": {"l": 1}}}"#,
+        );
+        let root = pj.root();
+        assert_eq!(root.value_type(), ValueType::Object);
+        assert_eq!(root.len(), Some(8));
+        let get = |k| root.get(k).expect(k);
+        assert_eq!(get("s").as_str(), Some("snot\nbadger"));
+        assert_eq!(get("i").as_i64(), Some(-1));
+        assert_eq!(get("i").as_u64(), None);
+        assert_eq!(get("u").as_u64(), Some(u64::max_value()));
+        assert_eq!(get("u").as_i64(), None);
+        assert_eq!(get("f").as_f64(), Some(0.5));
+        assert_eq!(get("t").as_bool(), Some(true));
+        assert!(get("n").is_null());
+        assert!(root.get("x").is_none());
+
+        let a = get("a");
+        assert_eq!(a.len(), Some(3));
+        let empty = |c: Option<Cursor>| c.map(|c| (c.value_type(), c.is_empty()));
+        assert_eq!(empty(a.get_idx(0)), Some((ValueType::Array, true)));
+        assert_eq!(empty(a.get_idx(1)), Some((ValueType::Object, true)));
+        let inner = a
+            .get_idx(2)
+            .and_then(|c| c.get_idx(1))
+            .and_then(|c| c.get_idx(0));
+        assert_eq!(inner.and_then(|c| c.as_u64()), Some(2));
+        assert!(a.get_idx(3).is_none());
+        let keys: Vec<_> = root
+            .object_iter()
+            .expect("object")
+            .map(|(k, _)| k)
+            .collect();
+        assert_eq!(keys, vec!["s", "i", "u", "f", "t", "n", "a", "o"]);
+        let l = get("o").get("k").and_then(|k| k.get("l"));
+        assert_eq!(l.and_then(|l| l.as_u64()), Some(1));
+    }
+
+    #[test]
+    fn to_values() {
+        let docs = [
+            r#"{"a": [1, 2.5, -3, "b\"c", true, false, null, {}, []], "d": {"e": {"f": 1}}}"#,
+            r#"[1, [2, [3, [4]]], {"a": {"b": {}}}]"#,
+            r#""snot""#,
+            "42",
+            "null",
+        ];
+        for d in docs.iter() {
+            let pj = parse(d);
+            let v = to_owned_value(&mut d.as_bytes().to_vec()).expect("parse");
+            assert_eq!(pj.root().to_owned_value(), v);
+            assert_eq!(
+                pj.root().to_borrowed_value(),
+                BorrowedValue::from(v.clone())
+            );
+        }
+        let pj = parse(r#"{"a": [1, {"b": 2}]}"#);
+        let sub = pj.root().get("a").and_then(|a| a.get_idx(1)).expect("sub");
+        assert_eq!(sub.to_owned_value(), json!({"b": 2}));
+        assert_eq!(sub.to_borrowed_value(), json!({"b": 2}));
+    }
+
+    #[test]
+    fn errors() {
+        assert!(ParsedJson::from_slice(&mut b"[1, 2".to_vec()).is_err());
+        assert!(ParsedJson::from_slice(&mut br#"{"a" 1}"#.to_vec()).is_err());
+        let e = ParsedJson::from_slice(&mut b"[1, 2x]".to_vec()).expect_err("parsed");
+        assert_eq!(e.error_type(), &ErrorType::InvalidNumber);
+
+        let mut parser = Parser::new();
+        for d in &["[1]", r#"{"a": "b"}"#] {
+            let pj = parser
+                .parse_tape(&mut d.as_bytes().to_vec())
+                .expect("parse");
+            assert_eq!(pj, parse(d));
+        }
+    }
+}
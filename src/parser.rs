@@ -0,0 +1,244 @@
+use crate::value::{borrowed, owned};
+#[cfg(feature = "serde_impl")]
+use crate::Deserializer;
+use crate::{
+    validation, visitor, BorrowedValue, Buffers, JsonVisitor, Limits, OwnedValue, ParsedJson,
+    Result, Stats, SIMDJSON_PADDING,
+};
+#[cfg(feature = "serde_impl")]
+use serde_ext::Deserialize;
+
+/// A parser that keeps the buffers used during parsing around so they can
+/// be re-used for the next document. When parsing many documents in a row
+/// this saves allocating the stage 1 indexes and string buffer for each of
+/// them.
+///
+/// ```
+/// use simd_json::{Parser, ValueTrait};
+///
+/// let mut parser = Parser::new();
+/// for doc in &[r#"{"id": 1}"#, r#"{"id": 2}"#] {
+///     let mut d = doc.as_bytes().to_vec();
+///     let v = parser.parse_owned(&mut d).unwrap();
+///     assert!(v["id"].is_i64());
+/// }
+/// ```
+#[derive(Default)]
+pub struct Parser {
+    buffers: Buffers,
+    max_capacity: Option<usize>,
+    limits: Limits,
+}
+
+impl Parser {
+    /// Creates a new parser, buffers are allocated on first use.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new parser that releases its buffers after parsing a
+    /// document larger than `max_capacity` bytes instead of holding on
+    /// to them, so a single large document doesn't pin its memory.
+    pub fn with_max_capacity(max_capacity: usize) -> Self {
+        Self {
+            max_capacity: Some(max_capacity),
+            ..Self::default()
+        }
+    }
+
+    /// Sets the limits documents are checked against, see `Limits`.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Parses a slice of bytes into a `BorrowedValue`. This function will
+    /// rewrite the slice to de-escape strings.
+    pub fn parse_borrowed<'v>(&mut self, s: &'v mut [u8]) -> Result<BorrowedValue<'v>> {
+        let value = borrowed::to_value_with_buffers(s, &mut self.buffers, &self.limits);
+        self.shrink();
+        value
+    }
+
+    /// Parses a slice of bytes into an `OwnedValue`. This function will
+    /// rewrite the slice to de-escape strings.
+    pub fn parse_owned(&mut self, s: &mut [u8]) -> Result<OwnedValue> {
+        let value = owned::to_value_with_buffers(s, &mut self.buffers, &self.limits);
+        self.shrink();
+        value
+    }
+
+    /// Parses a slice of bytes into a `ParsedJson` tape. This function will
+    /// rewrite the slice to de-escape strings.
+    pub fn parse_tape(&mut self, s: &mut [u8]) -> Result<ParsedJson> {
+        let tape = ParsedJson::from_slice_with_buffers(s, &mut self.buffers, &self.limits);
+        self.shrink();
+        tape
+    }
+
+    /// Checks that a slice of bytes is valid JSON without building any
+    /// values, see `validate_with_stats`. The slice isn't modified.
+    pub fn validate(&mut self, s: &[u8]) -> Result<Stats> {
+        let stats = validation::validate_with_buffers(s, &mut self.buffers, &self.limits);
+        self.shrink();
+        stats
+    }
+
+    /// Walks a slice of bytes calling `visitor` for every value, see
+    /// `visit`. This function will rewrite the slice to de-escape strings.
+    pub fn visit<V: JsonVisitor>(&mut self, s: &mut [u8], visitor: &mut V) -> Result<bool> {
+        let res = visitor::visit_with_buffers(s, visitor, &mut self.buffers, &self.limits);
+        self.shrink();
+        res
+    }
+
+    /// Deserializes a slice of bytes using serde. This function will
+    /// rewrite the slice to de-escape strings.
+    #[cfg(feature = "serde_impl")]
+    pub fn deserialize<'de, T>(&mut self, s: &'de mut [u8]) -> Result<T>
+    where
+        T: Deserialize<'de>,
+    {
+        let value = match Deserializer::from_slice_with_buffers(s, &mut self.buffers, &self.limits)
+        {
+            Ok(mut de) => {
+                let value = T::deserialize(&mut de).map_err(|e| de.locate(e));
+                self.buffers = de.into_buffers();
+                value
+            }
+            Err(e) => Err(e),
+        };
+        self.shrink();
+        value
+    }
+
+    /// Releases buffers that grew beyond what a document of `max_capacity`
+    /// bytes needs.
+    fn shrink(&mut self) {
+        if let Some(max) = self.max_capacity {
+            let b = &mut self.buffers;
+            if b.strings.capacity() > max + SIMDJSON_PADDING {
+                b.strings = Vec::new();
+            }
+            // There can't be more structural characters than bytes
+            if b.structural_indexes.capacity() > max + 1 {
+                b.structural_indexes = Vec::new();
+            }
+            if b.counts.capacity() > max + 1 {
+                b.counts = Vec::new();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Parser;
+    use crate::{to_owned_value, BorrowedValue, JsonVisitor, ValueTrait, Visit};
+
+    #[test]
+    fn reuse() {
+        let mut parser = Parser::new();
+        for d in &[
+            r#"{"a": [1, 2, 3], "b": "snot\nbadger"}"#,
+            "[]",
+            r#""é""#,
+            "42",
+            r#"{"a": {"b": {"c": [true, false, null]}}}"#,
+        ] {
+            let mut d1 = d.as_bytes().to_vec();
+            let mut d2 = d.as_bytes().to_vec();
+            let mut d3 = d.as_bytes().to_vec();
+            let expected = to_owned_value(&mut d1).expect("to_owned_value");
+            assert_eq!(parser.parse_owned(&mut d2).expect("parse_owned"), expected);
+            let v: BorrowedValue = parser.parse_borrowed(&mut d3).expect("parse_borrowed");
+            assert_eq!(expected, v);
+        }
+    }
+
+    #[test]
+    fn reuse_after_error() {
+        let mut parser = Parser::new();
+        let mut d = br#"{"a": [1, 2"#.to_vec();
+        assert!(parser.parse_owned(&mut d).is_err());
+        let mut d = br#"{"a": 1}"#.to_vec();
+        assert_eq!(
+            parser.parse_owned(&mut d).expect("parse_owned")["a"].as_i64(),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn max_capacity() {
+        let mut parser = Parser::with_max_capacity(64);
+        let mut d = br#"[1,2,3]"#.to_vec();
+        parser.parse_owned(&mut d).expect("parse_owned");
+        assert!(parser.buffers.strings.capacity() > 0);
+        let mut d = format!("[{}1]", "1,".repeat(100)).into_bytes();
+        parser.parse_owned(&mut d).expect("parse_owned");
+        assert_eq!(parser.buffers.strings.capacity(), 0);
+        assert_eq!(parser.buffers.structural_indexes.capacity(), 0);
+        assert_eq!(parser.buffers.counts.capacity(), 0);
+    }
+
+    #[test]
+    fn validate() {
+        let mut parser = Parser::new().with_limits(crate::Limits {
+            max_depth: 2,
+            ..crate::Limits::default()
+        });
+        let stats = parser.validate(br#"[{"a": 1}, 2]"#).expect("valid");
+        assert_eq!(stats.max_depth, 2);
+        assert_eq!(stats.elements, 4);
+        assert!(parser.validate(br#"[[[1]]]"#).is_err());
+        assert!(parser.validate(br#"[1, "\x"]"#).is_err());
+        assert!(parser.validate(br#"[]"#).is_ok());
+    }
+
+    #[test]
+    fn visit() {
+        struct Strings(usize);
+        impl JsonVisitor for Strings {
+            fn string(&mut self, _s: &str) -> Visit {
+                self.0 += 1;
+                Visit::Continue
+            }
+        }
+        let mut parser = Parser::new();
+        let mut strings = Strings(0);
+        for d in &[r#"["a", "b\nc"]"#, r#"{"k": "v"}"#, "1"] {
+            let mut d = d.as_bytes().to_vec();
+            assert!(parser.visit(&mut d, &mut strings).expect("visit"));
+        }
+        assert_eq!(strings.0, 3);
+    }
+
+    #[cfg(feature = "serde_impl")]
+    #[test]
+    fn deserialize() {
+        use serde::Deserialize;
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Doc {
+            id: u64,
+            name: String,
+        }
+        let mut parser = Parser::new();
+        for i in 0..3 {
+            let mut d = format!(r#"{{"id": {}, "name": "doc\t{}"}}"#, i, i).into_bytes();
+            let doc: Doc = parser.deserialize(&mut d).expect("deserialize");
+            assert_eq!(
+                doc,
+                Doc {
+                    id: i,
+                    name: format!("doc\t{}", i)
+                }
+            );
+        }
+
+        // Buffers are released even if the document is rejected
+        let mut parser = Parser::with_max_capacity(64);
+        let mut d = format!("[{}1", "1,".repeat(100)).into_bytes();
+        assert!(parser.deserialize::<Vec<u64>>(&mut d).is_err());
+        assert_eq!(parser.buffers.structural_indexes.capacity(), 0);
+    }
+}
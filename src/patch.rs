@@ -0,0 +1,496 @@
+//! JSON Patch (RFC 6902) and JSON Merge Patch (RFC 7386) for any
+//! `ValueTrait` implementor.
+//!
+//! ```
+//! use simd_json::{json, patch, OwnedValue};
+//!
+//! let mut v: OwnedValue = json!({"name": "snot", "tags": ["a"]});
+//! let p: OwnedValue = json!([
+//!     {"op": "replace", "path": "/name", "value": "badger"},
+//!     {"op": "add", "path": "/tags/-", "value": "b"}
+//! ]);
+//! patch::apply(&mut v, &p).unwrap();
+//! assert_eq!(v, json!({"name": "badger", "tags": ["a", "b"]}));
+//!
+//! patch::merge_patch(&mut v, &json!({"name": null, "size": 1}));
+//! assert_eq!(v, json!({"tags": ["a", "b"], "size": 1}));
+//! ```
+
+mod diff;
+
+pub use self::diff::{diff, to_patch, ArrayDiff, Operation, MAX_LCS_TABLE};
+use crate::query::equal;
+use crate::{PointerError, ValueTrait};
+use halfbrown::HashMap;
+use std::borrow::Borrow;
+use std::fmt;
+use std::hash::Hash;
+
+/// Error while applying a JSON Patch
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    index: usize,
+    error: ErrorType,
+}
+
+impl Error {
+    /// Index of the operation that failed
+    pub fn index(&self) -> usize {
+        self.index
+    }
+    /// Returns the type of the error
+    pub fn error_type(&self) -> &ErrorType {
+        &self.error
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} in operation {}", self.error, self.index)
+    }
+}
+impl std::error::Error for Error {}
+
+/// Types of JSON Patch errors
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorType {
+    /// The patch isn't an array of operations
+    NotAnArray,
+    /// The operation isn't an object, or a member is missing or invalid
+    InvalidOperation(&'static str),
+    /// A pointer of the operation couldn't be resolved
+    Pointer(PointerError),
+    /// The value of a `test` operation didn't match
+    TestFailed,
+    /// A value can't be moved into one of its own children
+    MoveIntoChild,
+}
+
+impl fmt::Display for ErrorType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NotAnArray => write!(f, "JSON Patch has to be an array"),
+            Self::InvalidOperation(reason) => write!(f, "Invalid operation: {}", reason),
+            Self::Pointer(e) => write!(f, "{}", e),
+            Self::TestFailed => write!(f, "Test failed"),
+            Self::MoveIntoChild => write!(f, "A value can not be moved into itself"),
+        }
+    }
+}
+
+enum Op<'p, V> {
+    Add(&'p str, &'p V),
+    Remove(&'p str),
+    Replace(&'p str, &'p V),
+    Move(&'p str, &'p str),
+    Copy(&'p str, &'p str),
+    Test(&'p str, &'p V),
+}
+
+/// Reverts an operation, pointers are resolved so `-` never appears in them
+enum Undo<V> {
+    Remove(String),
+    Insert(String, V),
+    Replace(String, V),
+}
+
+fn member<'p, V>(op: &'p V, key: &'static str, reason: &'static str) -> Result<&'p V, ErrorType>
+where
+    V: ValueTrait,
+    V::Key: Borrow<str> + Hash + Eq,
+{
+    op.get(key).ok_or(ErrorType::InvalidOperation(reason))
+}
+
+fn pointer<'p, V>(op: &'p V, key: &'static str, reason: &'static str) -> Result<&'p str, ErrorType>
+where
+    V: ValueTrait,
+    V::Key: Borrow<str> + Hash + Eq,
+{
+    op.get(key)
+        .and_then(ValueTrait::as_str)
+        .ok_or(ErrorType::InvalidOperation(reason))
+}
+
+fn parse<V>(op: &V) -> Result<Op<'_, V>, ErrorType>
+where
+    V: ValueTrait,
+    V::Key: Borrow<str> + Hash + Eq,
+{
+    if !op.is_object() {
+        return Err(ErrorType::InvalidOperation("not an object"));
+    }
+    let path = stry!(pointer(op, "path", "`path` has to be a string"));
+    let value = || member(op, "value", "missing `value`");
+    let from = || pointer(op, "from", "`from` has to be a string");
+    match op.get("op").and_then(ValueTrait::as_str) {
+        Some("add") => Ok(Op::Add(path, stry!(value()))),
+        Some("remove") => Ok(Op::Remove(path)),
+        Some("replace") => Ok(Op::Replace(path, stry!(value()))),
+        Some("move") => Ok(Op::Move(stry!(from()), path)),
+        Some("copy") => Ok(Op::Copy(stry!(from()), path)),
+        Some("test") => Ok(Op::Test(path, stry!(value()))),
+        Some(_) => Err(ErrorType::InvalidOperation("unknown `op`")),
+        None => Err(ErrorType::InvalidOperation("`op` has to be a string")),
+    }
+}
+
+/// Inserts `value` and records how to undo it
+fn add<V>(target: &mut V, path: &str, value: V, undo: &mut Vec<Undo<V>>) -> Result<(), ErrorType>
+where
+    V: ValueTrait,
+    V::Key: Borrow<str> + Hash + Eq + From<String>,
+{
+    // `-` appends so undoing it has to remove the current last index
+    let mut resolved = path.to_string();
+    if let Some(parent) = path.strip_suffix("/-") {
+        if let Some(a) = target.pointer(parent).ok().and_then(ValueTrait::as_array) {
+            resolved = format!("{}/{}", parent, a.len());
+        }
+    }
+    match target.pointer_insert(path, value) {
+        Ok(Some(old)) => undo.push(Undo::Insert(resolved, old)),
+        Ok(None) => undo.push(Undo::Remove(resolved)),
+        Err(e) => return Err(ErrorType::Pointer(e)),
+    }
+    Ok(())
+}
+
+fn apply_op<V>(target: &mut V, op: Op<V>, undo: &mut Vec<Undo<V>>) -> Result<(), ErrorType>
+where
+    V: ValueTrait + Clone,
+    V::Key: Borrow<str> + Hash + Eq + From<String>,
+{
+    match op {
+        Op::Add(path, value) => add(target, path, value.clone(), undo),
+        Op::Remove(path) => {
+            let old = stry!(target.pointer_remove(path).map_err(ErrorType::Pointer));
+            undo.push(Undo::Insert(path.to_string(), old));
+            Ok(())
+        }
+        Op::Replace(path, value) => {
+            let v = stry!(target.pointer_mut(path).map_err(ErrorType::Pointer));
+            let old = std::mem::replace(v, value.clone());
+            undo.push(Undo::Replace(path.to_string(), old));
+            Ok(())
+        }
+        Op::Move(from, path) => {
+            if from == path {
+                return target.pointer(from).map(|_| ()).map_err(ErrorType::Pointer);
+            }
+            if path.starts_with(from) && path[from.len()..].starts_with('/') {
+                return Err(ErrorType::MoveIntoChild);
+            }
+            let v = stry!(target.pointer_remove(from).map_err(ErrorType::Pointer));
+            undo.push(Undo::Insert(from.to_string(), v.clone()));
+            add(target, path, v, undo)
+        }
+        Op::Copy(from, path) => {
+            let v = stry!(target.pointer(from).map_err(ErrorType::Pointer)).clone();
+            add(target, path, v, undo)
+        }
+        Op::Test(path, value) => {
+            if equal(
+                stry!(target.pointer(path).map_err(ErrorType::Pointer)),
+                value,
+            ) {
+                Ok(())
+            } else {
+                Err(ErrorType::TestFailed)
+            }
+        }
+    }
+}
+
+/// Applies a JSON Patch, an array of operation objects, to `target`. The
+/// patch is applied atomically, if any operation fails all previous ones
+/// are rolled back and `target` is left unchanged.
+pub fn apply<V>(target: &mut V, patch: &V) -> Result<(), Error>
+where
+    V: ValueTrait + Clone,
+    V::Key: Borrow<str> + Hash + Eq + From<String>,
+{
+    let ops = stry!(patch.as_array().ok_or(Error {
+        index: 0,
+        error: ErrorType::NotAnArray,
+    }));
+    let mut parsed = Vec::with_capacity(ops.len());
+    for (index, op) in ops.iter().enumerate() {
+        parsed.push(stry!(parse(op).map_err(|error| Error { index, error })));
+    }
+    let mut undo = Vec::new();
+    for (index, op) in parsed.into_iter().enumerate() {
+        if let Err(error) = apply_op(target, op, &mut undo) {
+            rollback(target, undo);
+            return Err(Error { index, error });
+        }
+    }
+    Ok(())
+}
+
+fn rollback<V>(target: &mut V, undo: Vec<Undo<V>>)
+where
+    V: ValueTrait,
+    V::Key: Borrow<str> + Hash + Eq + From<String>,
+{
+    // every step reverts an operation that succeeded so none of them can fail
+    for u in undo.into_iter().rev() {
+        let _ = match u {
+            Undo::Remove(path) => target.pointer_remove(&path).map(|_| ()),
+            Undo::Insert(path, v) => target.pointer_insert(&path, v).map(|_| ()),
+            Undo::Replace(path, v) => target.pointer_mut(&path).map(|t| *t = v),
+        };
+    }
+}
+
+/// Applies a JSON Merge Patch to `target`. Objects in `patch` are merged
+/// recursively with `null` members removing keys, any other value replaces
+/// the target.
+pub fn merge_patch<V>(target: &mut V, patch: &V)
+where
+    V: ValueTrait + Clone + From<HashMap<<V as ValueTrait>::Key, V>>,
+    V::Key: Borrow<str> + Hash + Eq + Clone,
+{
+    let p = if let Some(p) = patch.as_object() {
+        p
+    } else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = V::from(HashMap::new());
+    }
+    if let Some(o) = target.as_object_mut() {
+        for (k, v) in p {
+            if v.is_null() {
+                o.remove(k.borrow());
+            } else if let Some(t) = o.get_mut(k.borrow()) {
+                merge_patch(t, v);
+            } else {
+                let mut t = V::from(());
+                merge_patch(&mut t, v);
+                o.insert(k.clone(), t);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{apply, merge_patch, ErrorType};
+    use crate::{BorrowedValue, OwnedValue, PointerError};
+
+    fn check(doc: OwnedValue, patch: OwnedValue, expected: OwnedValue) {
+        let mut v = doc.clone();
+        apply(&mut v, &patch).expect("patch");
+        assert_eq!(v, expected);
+        let mut v: BorrowedValue = doc.into();
+        apply(&mut v, &patch.into()).expect("patch");
+        assert_eq!(v, BorrowedValue::from(expected));
+    }
+
+    fn check_err(doc: OwnedValue, patch: OwnedValue, index: usize, error: ErrorType) {
+        let mut v = doc.clone();
+        let e = apply(&mut v, &patch).expect_err("patched");
+        assert_eq!((e.index(), e.error_type()), (index, &error));
+        assert_eq!(v, doc);
+    }
+
+    #[test]
+    fn rfc6902_examples() {
+        check(
+            json!({"foo": "bar"}),
+            json!([{ "op": "add", "path": "/baz", "value": "qux" }]),
+            json!({"baz": "qux", "foo": "bar"}),
+        );
+        check(
+            json!({"foo": ["bar", "baz"]}),
+            json!([{ "op": "add", "path": "/foo/1", "value": "qux" }]),
+            json!({"foo": ["bar", "qux", "baz"]}),
+        );
+        check(
+            json!({"baz": "qux", "foo": "bar"}),
+            json!([{ "op": "remove", "path": "/baz" }]),
+            json!({"foo": "bar"}),
+        );
+        check(
+            json!({"foo": ["bar", "qux", "baz"]}),
+            json!([{ "op": "remove", "path": "/foo/1" }]),
+            json!({"foo": ["bar", "baz"]}),
+        );
+        check(
+            json!({"baz": "qux", "foo": "bar"}),
+            json!([{ "op": "replace", "path": "/baz", "value": "boo" }]),
+            json!({"baz": "boo", "foo": "bar"}),
+        );
+        check(
+            json!({"foo": {"bar": "baz", "waldo": "fred"}, "qux": {"corge": "grault"}}),
+            json!([{ "op": "move", "from": "/foo/waldo", "path": "/qux/thud" }]),
+            json!({"foo": {"bar": "baz"}, "qux": {"corge": "grault", "thud": "fred"}}),
+        );
+        check(
+            json!({"foo": ["all", "grass", "cows", "eat"]}),
+            json!([{ "op": "move", "from": "/foo/1", "path": "/foo/3" }]),
+            json!({"foo": ["all", "cows", "eat", "grass"]}),
+        );
+        check(
+            json!({"baz": "qux", "foo": ["a", 2, "c"]}),
+            json!([
+                { "op": "test", "path": "/baz", "value": "qux" },
+                { "op": "test", "path": "/foo/1", "value": 2.0 }
+            ]),
+            json!({"baz": "qux", "foo": ["a", 2, "c"]}),
+        );
+        check(
+            json!({"foo": "bar"}),
+            json!([{ "op": "add", "path": "/child", "value": { "grandchild": { } } }]),
+            json!({"foo": "bar", "child": {"grandchild": {}}}),
+        );
+        check(
+            json!({"foo": ["bar"]}),
+            json!([{ "op": "add", "path": "/foo/-", "value": ["abc", "def"] }]),
+            json!({"foo": ["bar", ["abc", "def"]]}),
+        );
+        check(
+            json!({"/": 9, "~1": 10}),
+            json!([
+                {"op": "test", "path": "/~01", "value": 10},
+                {"op": "copy", "from": "/~1", "path": "/a"},
+                {"op": "replace", "path": "", "value": [1]}
+            ]),
+            json!([1]),
+        );
+    }
+
+    #[test]
+    fn errors() {
+        check_err(
+            json!({"baz": "qux"}),
+            json!([{ "op": "test", "path": "/baz", "value": "bar" }]),
+            0,
+            ErrorType::TestFailed,
+        );
+        check_err(
+            json!({"foo": "bar"}),
+            json!([{ "op": "add", "path": "/baz/bat", "value": "qux" }]),
+            0,
+            ErrorType::Pointer(PointerError::NotFound),
+        );
+        check_err(json!({}), json!({}), 0, ErrorType::NotAnArray);
+        check_err(
+            json!({}),
+            json!([{ "op": "remove", "path": "" }, 1]),
+            1,
+            ErrorType::InvalidOperation("not an object"),
+        );
+        check_err(
+            json!({}),
+            json!([{ "op": "snot", "path": "" }]),
+            0,
+            ErrorType::InvalidOperation("unknown `op`"),
+        );
+        check_err(
+            json!({}),
+            json!([{ "op": "add", "path": "/a" }]),
+            0,
+            ErrorType::InvalidOperation("missing `value`"),
+        );
+        check_err(
+            json!({"a": {"b": 1}}),
+            json!([{ "op": "move", "from": "/a", "path": "/a/b" }]),
+            0,
+            ErrorType::MoveIntoChild,
+        );
+        check_err(
+            json!({"a": [1]}),
+            json!([{ "op": "remove", "path": "/a/-" }]),
+            0,
+            ErrorType::Pointer(PointerError::NotFound),
+        );
+    }
+
+    #[test]
+    fn rollback() {
+        let doc = json!({
+            "a": [1, 2, 3],
+            "b": {"c": "d"},
+            "e": 1
+        });
+        check_err(
+            doc,
+            json!([
+                {"op": "add", "path": "/a/-", "value": 4},
+                {"op": "add", "path": "/a/0", "value": 0},
+                {"op": "remove", "path": "/a/2"},
+                {"op": "replace", "path": "/e", "value": 2},
+                {"op": "add", "path": "/b/c", "value": "x"},
+                {"op": "add", "path": "/b/f", "value": "y"},
+                {"op": "move", "from": "/b", "path": "/a/1"},
+                {"op": "move", "from": "/e", "path": "/a/-"},
+                {"op": "copy", "from": "/a", "path": "/g"},
+                {"op": "replace", "path": "", "value": {"h": [1]}},
+                {"op": "add", "path": "/h/0", "value": 0},
+                {"op": "test", "path": "/h", "value": [1, 0]}
+            ]),
+            11,
+            ErrorType::TestFailed,
+        );
+    }
+
+    #[test]
+    fn merge() {
+        let cases = vec![
+            (json!({"a": "b"}), json!({"a": "c"}), json!({"a": "c"})),
+            (
+                json!({"a": "b"}),
+                json!({"b": "c"}),
+                json!({"a": "b", "b": "c"}),
+            ),
+            (json!({"a": "b"}), json!({"a": null}), json!({})),
+            (
+                json!({"a": "b", "b": "c"}),
+                json!({"a": null}),
+                json!({"b": "c"}),
+            ),
+            (json!({"a": ["b"]}), json!({"a": "c"}), json!({"a": "c"})),
+            (json!({"a": "c"}), json!({"a": ["b"]}), json!({"a": ["b"]})),
+            (
+                json!({"a": {"b": "c"}}),
+                json!({"a": {"b": "d", "c": null}}),
+                json!({"a": {"b": "d"}}),
+            ),
+            (
+                json!({"a": [{"b": "c"}]}),
+                json!({"a": [1]}),
+                json!({"a": [1]}),
+            ),
+            (json!(["a", "b"]), json!(["c", "d"]), json!(["c", "d"])),
+            (json!({"a": "b"}), json!(["c"]), json!(["c"])),
+            (json!({"a": "foo"}), json!(null), json!(null)),
+            (json!({"a": "foo"}), json!("bar"), json!("bar")),
+            (
+                json!({"e": null}),
+                json!({"a": 1}),
+                json!({"e": null, "a": 1}),
+            ),
+            (
+                json!([1, 2]),
+                json!({"a": "b", "c": null}),
+                json!({"a": "b"}),
+            ),
+            (
+                json!({}),
+                json!({"a": {"bb": {"ccc": null}}}),
+                json!({"a": {"bb": {}}}),
+            ),
+        ];
+        for (doc, patch, expected) in cases {
+            let mut v: BorrowedValue = doc.clone().into();
+            merge_patch(&mut v, &patch.clone().into());
+            assert_eq!(v, BorrowedValue::from(expected.clone()));
+            let mut v = doc;
+            merge_patch(&mut v, &patch);
+            assert_eq!(v, expected);
+        }
+    }
+}
@@ -0,0 +1,414 @@
+use crate::{OwnedValue, ValueTrait};
+use std::borrow::Borrow;
+use std::hash::Hash;
+
+/// How `diff` compares arrays
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArrayDiff {
+    /// Elements are compared by their index, cheap but an element inserted at
+    /// the front changes every element after it
+    Index,
+    /// Elements are matched by their longest common subsequence so inserts
+    /// and removes only touch the elements involved, this takes quadratic
+    /// time and memory in the length of the changed part of the arrays.
+    /// Arrays whose table would exceed `MAX_LCS_TABLE` entries are compared
+    /// by index instead.
+    Lcs,
+}
+
+/// Largest table, in entries, `ArrayDiff::Lcs` builds for a pair of arrays
+pub const MAX_LCS_TABLE: usize = 1 << 20;
+
+/// A JSON Patch operation produced by `diff`, values are borrowed from the
+/// target document
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation<'v, V> {
+    /// Adds `value` at `path`
+    Add {
+        /// JSON Pointer to the location to add to
+        path: String,
+        /// the added value
+        value: &'v V,
+    },
+    /// Removes the value at `path`
+    Remove {
+        /// JSON Pointer to the removed value
+        path: String,
+    },
+    /// Replaces the value at `path` with `value`
+    Replace {
+        /// JSON Pointer to the replaced value
+        path: String,
+        /// the new value
+        value: &'v V,
+    },
+}
+
+impl<'v, V> Operation<'v, V> {
+    /// JSON Pointer the operation applies to
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Add { path, .. } | Self::Remove { path } | Self::Replace { path, .. } => path,
+        }
+    }
+
+    /// Returns the operation as a JSON Patch operation object
+    pub fn to_value(&self) -> OwnedValue
+    where
+        V: Clone + Into<OwnedValue>,
+    {
+        let (op, value) = match self {
+            Self::Add { value, .. } => ("add", Some(*value)),
+            Self::Remove { .. } => ("remove", None),
+            Self::Replace { value, .. } => ("replace", Some(*value)),
+        };
+        let mut o = vec![
+            ("op", OwnedValue::from(op)),
+            ("path", OwnedValue::from(self.path())),
+        ];
+        if let Some(v) = value {
+            o.push(("value", v.clone().into()));
+        }
+        o.into_iter().collect()
+    }
+}
+
+/// Returns the operations as a JSON Patch document that can be passed to
+/// `patch::apply` or serialized
+pub fn to_patch<V>(ops: &[Operation<V>]) -> OwnedValue
+where
+    V: Clone + Into<OwnedValue>,
+{
+    ops.iter().map(Operation::to_value).collect()
+}
+
+/// Computes the add, remove and replace operations turning `from` into
+/// `to`. The two values can be of different types as long as they can be
+/// compared, for example an `OwnedValue` with a `BorrowedValue`.
+///
+/// ```
+/// use simd_json::{json, patch, OwnedValue};
+///
+/// let a: OwnedValue = json!({"version": 1, "plugins": ["a", "b"]});
+/// let b: OwnedValue = json!({"version": 2, "plugins": ["a", "c", "b"]});
+/// let ops = patch::diff(&a, &b, patch::ArrayDiff::Lcs);
+/// assert_eq!(
+///     patch::to_patch(&ops),
+///     json!([
+///         {"op": "replace", "path": "/version", "value": 2},
+///         {"op": "add", "path": "/plugins/1", "value": "c"}
+///     ])
+/// );
+/// ```
+pub fn diff<'v, A, B>(from: &A, to: &'v B, arrays: ArrayDiff) -> Vec<Operation<'v, B>>
+where
+    A: ValueTrait + PartialEq<B>,
+    A::Key: Borrow<str> + Hash + Eq,
+    B: ValueTrait,
+    B::Key: Borrow<str> + Hash + Eq,
+{
+    let mut ops = Vec::new();
+    let mut path = String::new();
+    diff_value(&mut path, from, to, arrays, &mut ops);
+    ops
+}
+
+fn push_token(path: &mut String, token: &str) {
+    path.push('/');
+    for c in token.chars() {
+        match c {
+            '~' => path.push_str("~0"),
+            '/' => path.push_str("~1"),
+            c => path.push(c),
+        }
+    }
+}
+
+fn push_index(path: &mut String, i: usize) {
+    path.push('/');
+    path.push_str(itoa::Buffer::new().format(i));
+}
+
+fn diff_value<'v, A, B>(
+    path: &mut String,
+    from: &A,
+    to: &'v B,
+    arrays: ArrayDiff,
+    ops: &mut Vec<Operation<'v, B>>,
+) where
+    A: ValueTrait + PartialEq<B>,
+    A::Key: Borrow<str> + Hash + Eq,
+    B: ValueTrait,
+    B::Key: Borrow<str> + Hash + Eq,
+{
+    if from == to {
+        return;
+    }
+    let len = path.len();
+    if let (Some(a), Some(b)) = (from.as_object(), to.as_object()) {
+        for (k, v) in a {
+            let k: &str = k.borrow();
+            push_token(path, k);
+            if let Some(t) = b.get(k) {
+                diff_value(path, v, t, arrays, ops);
+            } else {
+                ops.push(Operation::Remove { path: path.clone() });
+            }
+            path.truncate(len);
+        }
+        for (k, v) in b {
+            let k: &str = k.borrow();
+            if !a.contains_key(k) {
+                push_token(path, k);
+                ops.push(Operation::Add {
+                    path: path.clone(),
+                    value: v,
+                });
+                path.truncate(len);
+            }
+        }
+    } else if let (Some(a), Some(b)) = (from.as_array(), to.as_array()) {
+        match arrays {
+            ArrayDiff::Index => diff_index(path, a, b, arrays, ops),
+            ArrayDiff::Lcs => diff_lcs(path, a, b, ops),
+        }
+    } else {
+        ops.push(Operation::Replace {
+            path: path.clone(),
+            value: to,
+        });
+    }
+}
+
+fn diff_index<'v, A, B>(
+    path: &mut String,
+    a: &[A],
+    b: &'v [B],
+    arrays: ArrayDiff,
+    ops: &mut Vec<Operation<'v, B>>,
+) where
+    A: ValueTrait + PartialEq<B>,
+    A::Key: Borrow<str> + Hash + Eq,
+    B: ValueTrait,
+    B::Key: Borrow<str> + Hash + Eq,
+{
+    let len = path.len();
+    for (i, (x, y)) in a.iter().zip(b).enumerate() {
+        push_index(path, i);
+        diff_value(path, x, y, arrays, ops);
+        path.truncate(len);
+    }
+    for (i, value) in b.iter().enumerate().skip(a.len()) {
+        push_index(path, i);
+        ops.push(Operation::Add {
+            path: path.clone(),
+            value,
+        });
+        path.truncate(len);
+    }
+    // remove from the back so the indexes stay valid
+    for i in (b.len()..a.len()).rev() {
+        push_index(path, i);
+        ops.push(Operation::Remove { path: path.clone() });
+        path.truncate(len);
+    }
+}
+
+fn diff_lcs<'v, A, B>(path: &mut String, a: &[A], b: &'v [B], ops: &mut Vec<Operation<'v, B>>)
+where
+    A: ValueTrait + PartialEq<B>,
+    A::Key: Borrow<str> + Hash + Eq,
+    B: ValueTrait,
+    B::Key: Borrow<str> + Hash + Eq,
+{
+    // common prefixes and suffixes are cheap to skip and keep the table small
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (n, m) = (a.len() - prefix - suffix, b.len() - prefix - suffix);
+    if (n + 1).saturating_mul(m + 1) > MAX_LCS_TABLE {
+        return diff_index(path, a, b, ArrayDiff::Lcs, ops);
+    }
+    let a = &a[prefix..a.len() - suffix];
+    let b = &b[prefix..b.len() - suffix];
+
+    // lcs[i * (m + 1) + j] is the length of the LCS of a[i..] and b[j..]
+    let mut lcs = vec![0_usize; (n + 1) * (m + 1)];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i * (m + 1) + j] = if a[i] == b[j] {
+                lcs[(i + 1) * (m + 1) + j + 1] + 1
+            } else {
+                lcs[(i + 1) * (m + 1) + j].max(lcs[i * (m + 1) + j + 1])
+            };
+        }
+    }
+    let at = |i: usize, j: usize| lcs[i * (m + 1) + j];
+
+    let len = path.len();
+    // `k` is the index in the array as patched so far
+    let (mut i, mut j, mut k) = (0, 0, prefix);
+    while i < n || j < m {
+        push_index(path, k);
+        if i < n && j < m && a[i] == b[j] {
+            i += 1;
+            j += 1;
+            k += 1;
+        } else if i < n && j < m && at(i + 1, j + 1) == at(i, j) {
+            // neither element is part of the LCS so change one into the other
+            diff_value(path, &a[i], &b[j], ArrayDiff::Lcs, ops);
+            i += 1;
+            j += 1;
+            k += 1;
+        } else if j == m || (i < n && at(i + 1, j) >= at(i, j + 1)) {
+            ops.push(Operation::Remove { path: path.clone() });
+            i += 1;
+        } else {
+            ops.push(Operation::Add {
+                path: path.clone(),
+                value: &b[j],
+            });
+            j += 1;
+            k += 1;
+        }
+        path.truncate(len);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{diff, to_patch, ArrayDiff};
+    use crate::patch::apply;
+    use crate::{BorrowedValue, OwnedValue, ValueTrait};
+
+    fn roundtrip(a: &OwnedValue, b: &OwnedValue, arrays: ArrayDiff) -> OwnedValue {
+        let ops = diff(a, b, arrays);
+        let patch = to_patch(&ops);
+        let mut v = a.clone();
+        apply(&mut v, &patch).expect("patch");
+        assert_eq!(&v, b);
+
+        // borrowed and owned values can be diffed against each other, the
+        // order of operations on sibling keys depends on the map though
+        let bb: BorrowedValue = b.clone().into();
+        let mut v = a.clone();
+        apply(&mut v, &to_patch(&diff(a, &bb, arrays))).expect("patch");
+        assert_eq!(&v, b);
+        let ba: BorrowedValue = a.clone().into();
+        let ops = diff(&ba, b, arrays);
+        let mut v = ba.clone();
+        apply(&mut v, &to_patch(&ops).into()).expect("patch");
+        assert_eq!(&v, b);
+        patch
+    }
+
+    #[test]
+    fn objects() {
+        let a = json!({"a": 1, "b": {"c": [1, 2], "d": "x"}, "e/f": null, "g~h": 1});
+        let b = json!({"a": 1, "b": {"c": [1, 3], "i": true}, "g~h": 2});
+        let patch = roundtrip(&a, &b, ArrayDiff::Index);
+        assert_eq!(patch.as_array().map(Vec::len), Some(5));
+        let paths: Vec<_> = diff(&a, &b, ArrayDiff::Index)
+            .iter()
+            .map(|o| o.path().to_string())
+            .collect();
+        assert!(paths.contains(&"/e~1f".to_string()));
+        assert!(paths.contains(&"/g~0h".to_string()));
+        assert!(paths.contains(&"/b/c/1".to_string()));
+        assert!(paths.contains(&"/b/i".to_string()));
+        assert!(paths.contains(&"/b/d".to_string()));
+    }
+
+    #[test]
+    fn scalars() {
+        assert_eq!(roundtrip(&json!(1), &json!(1), ArrayDiff::Lcs), json!([]));
+        assert_eq!(
+            roundtrip(&json!(1), &json!("1"), ArrayDiff::Lcs),
+            json!([{"op": "replace", "path": "", "value": "1"}])
+        );
+        assert_eq!(
+            roundtrip(&json!({"a": [1]}), &json!({"a": {}}), ArrayDiff::Lcs),
+            json!([{"op": "replace", "path": "/a", "value": {}}])
+        );
+    }
+
+    #[test]
+    fn arrays_by_index() {
+        let a = json!([1, 2, 3, 4]);
+        let b = json!([0, 1, 2]);
+        assert_eq!(
+            roundtrip(&a, &b, ArrayDiff::Index),
+            json!([
+                {"op": "replace", "path": "/0", "value": 0},
+                {"op": "replace", "path": "/1", "value": 1},
+                {"op": "replace", "path": "/2", "value": 2},
+                {"op": "remove", "path": "/3"}
+            ])
+        );
+        assert_eq!(
+            roundtrip(&b, &a, ArrayDiff::Index),
+            json!([
+                {"op": "replace", "path": "/0", "value": 1},
+                {"op": "replace", "path": "/1", "value": 2},
+                {"op": "replace", "path": "/2", "value": 3},
+                {"op": "add", "path": "/3", "value": 4}
+            ])
+        );
+        roundtrip(&json!([1, 2, 3, 4, 5]), &json!([1]), ArrayDiff::Index);
+    }
+
+    #[test]
+    fn arrays_by_lcs() {
+        let a = json!([1, 2, 3, 4]);
+        let b = json!([0, 1, 2]);
+        assert_eq!(
+            roundtrip(&a, &b, ArrayDiff::Lcs),
+            json!([
+                {"op": "add", "path": "/0", "value": 0},
+                {"op": "remove", "path": "/3"},
+                {"op": "remove", "path": "/3"}
+            ])
+        );
+        assert_eq!(
+            roundtrip(
+                &json!([{"a": 1}, "x", {"b": 1}]),
+                &json!([{"a": 2}, "x", {"b": 1}, "y"]),
+                ArrayDiff::Lcs
+            ),
+            json!([
+                {"op": "replace", "path": "/0/a", "value": 2},
+                {"op": "add", "path": "/3", "value": "y"}
+            ])
+        );
+        let cases = [
+            (json!([]), json!([1, 2])),
+            (json!([1, 2]), json!([])),
+            (json!(["a", "b", "c", "d"]), json!(["d", "c", "b", "a"])),
+            (json!([1, 2, 3, 4, 5, 6]), json!([2, 9, 4, 6, 7])),
+            (json!([[1, 2], [3]]), json!([[1, 2, 3], [3], [4]])),
+        ];
+        for (a, b) in cases.iter() {
+            roundtrip(a, b, ArrayDiff::Lcs);
+            roundtrip(b, a, ArrayDiff::Lcs);
+            roundtrip(a, b, ArrayDiff::Index);
+        }
+    }
+
+    #[test]
+    fn lcs_table_limit() {
+        // 2001 * 2001 entries are above the limit so the arrays are
+        // compared by index
+        let a: OwnedValue = (0..2000).collect::<Vec<i64>>().into();
+        let b: OwnedValue = (1..2001).collect::<Vec<i64>>().into();
+        assert_eq!(diff(&a, &b, ArrayDiff::Lcs), diff(&a, &b, ArrayDiff::Index));
+        roundtrip(&a, &b, ArrayDiff::Lcs);
+        // below it only the changed elements are touched
+        let a: OwnedValue = (0..500).collect::<Vec<i64>>().into();
+        let b: OwnedValue = (1..501).collect::<Vec<i64>>().into();
+        assert_eq!(diff(&a, &b, ArrayDiff::Lcs).len(), 2);
+    }
+}
@@ -0,0 +1,1363 @@
+//! Compiled JSONPath queries following RFC 9535.
+//!
+//! A `JsonPath` is compiled once and can then be evaluated against any
+//! `ValueTrait` implementor, returning references to the selected nodes in
+//! the order the RFC defines.
+//!
+//! ```
+//! use simd_json::{json, query::JsonPath, OwnedValue};
+//!
+//! let v: OwnedValue = json!({"store": {"book": [
+//!     {"title": "Sayings of the Century", "price": 8.95},
+//!     {"title": "Sword of Honour", "price": 12.99}
+//! ]}});
+//! let path = JsonPath::compile("$.store.book[?(@.price < 10)].title").unwrap();
+//! assert_eq!(path.select(&v), vec![&OwnedValue::from("Sayings of the Century")]);
+//! ```
+//!
+//! Supported are name, wildcard, index, slice and filter selectors, unions
+//! and descendant segments. Filters support comparisons, existence tests,
+//! `!`, `&&`, `||` and the `length`, `count`, `value`, `match` and `search`
+//! functions. The latter two take I-Regexp (RFC 9485) patterns and need the
+//! `jsonpath-regex` feature.
+
+#[cfg(feature = "jsonpath-regex")]
+mod iregexp;
+
+#[cfg(feature = "jsonpath-regex")]
+use self::iregexp::{iregexp, Regexp};
+use crate::{ValueTrait, ValueType};
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::Hash;
+use std::str::FromStr;
+
+/// Largest integer allowed in indices and slices, RFC 9535 restricts them to
+/// the I-JSON range
+const MAX_INT: i64 = (1 << 53) - 1;
+
+/// Error while compiling a JSONPath expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    index: usize,
+    reason: &'static str,
+}
+
+impl Error {
+    /// Byte offset in the expression at which the error occurred
+    pub fn index(&self) -> usize {
+        self.index
+    }
+    /// Description of the error
+    pub fn reason(&self) -> &'static str {
+        self.reason
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at character {}", self.reason, self.index)
+    }
+}
+impl std::error::Error for Error {}
+
+/// A compiled JSONPath expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath(Query);
+
+impl JsonPath {
+    /// Compiles a JSONPath expression such as `$.store.book[*].author`
+    pub fn compile(path: &str) -> Result<Self, Error> {
+        let mut p = Parser {
+            input: path.as_bytes(),
+            idx: 0,
+        };
+        if !p.eat(b'$') {
+            return Err(p.err("expected `$`"));
+        }
+        let q = stry!(p.query(false));
+        if p.idx == p.input.len() {
+            Ok(Self(q))
+        } else {
+            Err(p.err("unexpected character"))
+        }
+    }
+
+    /// Returns references to all nodes in `value` selected by the query
+    pub fn select<'v, V>(&self, value: &'v V) -> Vec<&'v V>
+    where
+        V: ValueTrait,
+        V::Key: Borrow<str> + Hash + Eq,
+    {
+        self.0.eval(value, value)
+    }
+}
+
+impl FromStr for JsonPath {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Self::compile(s)
+    }
+}
+
+/// Compiles `path` and evaluates it against `value`, use `JsonPath` to
+/// evaluate the same expression more than once.
+pub fn query<'v, V>(path: &str, value: &'v V) -> Result<Vec<&'v V>, Error>
+where
+    V: ValueTrait,
+    V::Key: Borrow<str> + Hash + Eq,
+{
+    Ok(stry!(JsonPath::compile(path)).select(value))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Query {
+    relative: bool,
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Segment {
+    descendant: bool,
+    selectors: Vec<Selector>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Selector {
+    Name(String),
+    Wildcard,
+    Index(i64),
+    Slice(Option<i64>, Option<i64>, Option<i64>),
+    Filter(Expr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Or(Vec<Expr>),
+    And(Vec<Expr>),
+    Not(Box<Expr>),
+    Test(Query),
+    Cmp(Comparable, CmpOp, Comparable),
+    #[cfg(feature = "jsonpath-regex")]
+    Match(Box<Match>),
+}
+
+/// A call to `match`, or `search` if `search` is set
+#[cfg(feature = "jsonpath-regex")]
+#[derive(Debug, Clone, PartialEq)]
+struct Match {
+    search: bool,
+    value: Comparable,
+    pattern: Pattern,
+}
+
+#[cfg(feature = "jsonpath-regex")]
+#[derive(Debug, Clone, PartialEq)]
+enum Pattern {
+    /// Patterns given as a literal are compiled along with the query, `None`
+    /// if it isn't a valid I-Regexp
+    Compiled(Option<Regexp>),
+    /// Patterns taken from the document are compiled for every test
+    Dynamic(Comparable),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Comparable {
+    Literal(Literal),
+    Query(Query),
+    Length(Box<Comparable>),
+    Count(Query),
+    Value(Query),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Null,
+    Bool(bool),
+    Number(Num),
+    String(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Num {
+    Int(i128),
+    Float(f64),
+}
+
+impl Num {
+    #[allow(clippy::cast_precision_loss)]
+    fn as_f64(self) -> f64 {
+        match self {
+            Self::Int(i) => i as f64,
+            Self::Float(f) => f,
+        }
+    }
+    fn cmp(self, other: Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Int(a), Self::Int(b)) => Some(a.cmp(&b)),
+            (a, b) => a.as_f64().partial_cmp(&b.as_f64()),
+        }
+    }
+}
+
+/// Result of evaluating one side of a comparison
+enum Operand<'a, V> {
+    Nothing,
+    Node(&'a V),
+    Literal(&'a Literal),
+    Count(usize),
+}
+
+/// Primitive view on a node or literal used for comparisons
+enum Scalar<'a> {
+    Null,
+    Bool(bool),
+    Num(Num),
+    Str(&'a str),
+    Container,
+}
+
+impl<'a> Scalar<'a> {
+    fn of<V: ValueTrait>(v: &'a V) -> Self {
+        let n = match v.value_type() {
+            ValueType::Null => return Scalar::Null,
+            ValueType::Bool => v.as_bool().map(Scalar::Bool),
+            ValueType::String => v.as_str().map(Scalar::Str),
+            ValueType::Array | ValueType::Object => None,
+            ValueType::I64 => v.as_i64().map(|i| Scalar::Num(Num::Int(i.into()))),
+            ValueType::U64 => v.as_u64().map(|u| Scalar::Num(Num::Int(u.into()))),
+            _ => v.cast_f64().map(|f| Scalar::Num(Num::Float(f))),
+        };
+        n.unwrap_or(Scalar::Container)
+    }
+
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Scalar::Null, Scalar::Null) => true,
+            (Scalar::Bool(a), Scalar::Bool(b)) => a == b,
+            (Scalar::Num(a), Scalar::Num(b)) => a.cmp(*b) == Some(Ordering::Equal),
+            (Scalar::Str(a), Scalar::Str(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    fn lt(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Scalar::Num(a), Scalar::Num(b)) => a.cmp(*b) == Some(Ordering::Less),
+            // UTF-8 byte order is the same as Unicode scalar value order
+            (Scalar::Str(a), Scalar::Str(b)) => a < b,
+            _ => false,
+        }
+    }
+}
+
+impl<'a, V: ValueTrait> Operand<'a, V> {
+    fn scalar(&self) -> Scalar<'a> {
+        match self {
+            Operand::Nothing => Scalar::Container,
+            Operand::Node(v) => Scalar::of(*v),
+            Operand::Literal(Literal::Null) => Scalar::Null,
+            Operand::Literal(Literal::Bool(b)) => Scalar::Bool(*b),
+            Operand::Literal(Literal::Number(n)) => Scalar::Num(*n),
+            Operand::Literal(Literal::String(s)) => Scalar::Str(s),
+            Operand::Count(c) => Scalar::Num(Num::Int(*c as i128)),
+        }
+    }
+}
+
+/// Structural equality as JSONPath and JSON Patch define it, numbers are
+/// compared by value so `1` equals `1.0`
+pub(crate) fn equal<V>(a: &V, b: &V) -> bool
+where
+    V: ValueTrait,
+    V::Key: Borrow<str> + Hash + Eq,
+{
+    if let (Some(a), Some(b)) = (a.as_array(), b.as_array()) {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| equal(a, b))
+    } else if let (Some(a), Some(b)) = (a.as_object(), b.as_object()) {
+        a.len() == b.len()
+            && a.iter()
+                .all(|(k, a)| matches!(b.get(k.borrow()), Some(b) if equal(a, b)))
+    } else {
+        Scalar::of(a).eq(&Scalar::of(b))
+    }
+}
+
+fn operands_equal<V>(a: &Operand<V>, b: &Operand<V>) -> bool
+where
+    V: ValueTrait,
+    V::Key: Borrow<str> + Hash + Eq,
+{
+    match (a, b) {
+        (Operand::Nothing, Operand::Nothing) => true,
+        (Operand::Nothing, _) | (_, Operand::Nothing) => false,
+        (Operand::Node(a), Operand::Node(b)) => equal(*a, *b),
+        (a, b) => a.scalar().eq(&b.scalar()),
+    }
+}
+
+fn operand_less<V: ValueTrait>(a: &Operand<V>, b: &Operand<V>) -> bool {
+    match (a, b) {
+        (Operand::Nothing, _) | (_, Operand::Nothing) => false,
+        (a, b) => a.scalar().lt(&b.scalar()),
+    }
+}
+
+fn descendants<'v, V: ValueTrait>(v: &'v V, out: &mut Vec<&'v V>) {
+    out.push(v);
+    if let Some(a) = v.as_array() {
+        for e in a {
+            descendants(e, out);
+        }
+    } else if let Some(o) = v.as_object() {
+        for e in o.values() {
+            descendants(e, out);
+        }
+    }
+}
+
+fn children<'v, V: ValueTrait>(v: &'v V, out: &mut Vec<&'v V>) {
+    if let Some(a) = v.as_array() {
+        out.extend(a.iter());
+    } else if let Some(o) = v.as_object() {
+        out.extend(o.values());
+    }
+}
+
+impl Query {
+    fn eval<'v, V>(&self, root: &'v V, current: &'v V) -> Vec<&'v V>
+    where
+        V: ValueTrait,
+        V::Key: Borrow<str> + Hash + Eq,
+    {
+        let mut nodes = vec![if self.relative { current } else { root }];
+        for segment in &self.segments {
+            let mut inputs = Vec::new();
+            if segment.descendant {
+                for n in nodes {
+                    descendants(n, &mut inputs);
+                }
+            } else {
+                inputs = nodes;
+            }
+            nodes = Vec::new();
+            for n in inputs {
+                for selector in &segment.selectors {
+                    selector.select(root, n, &mut nodes);
+                }
+            }
+        }
+        nodes
+    }
+
+    /// A singular query selects at most one node
+    fn is_singular(&self) -> bool {
+        self.segments.iter().all(|s| {
+            !s.descendant
+                && matches!(
+                    s.selectors.as_slice(),
+                    [Selector::Name(_)] | [Selector::Index(_)]
+                )
+        })
+    }
+
+    fn single<'v, V>(&self, root: &'v V, current: &'v V) -> Operand<'v, V>
+    where
+        V: ValueTrait,
+        V::Key: Borrow<str> + Hash + Eq,
+    {
+        match self.eval(root, current).as_slice() {
+            [n] => Operand::Node(*n),
+            _ => Operand::Nothing,
+        }
+    }
+}
+
+impl Selector {
+    fn select<'v, V>(&self, root: &'v V, v: &'v V, out: &mut Vec<&'v V>)
+    where
+        V: ValueTrait,
+        V::Key: Borrow<str> + Hash + Eq,
+    {
+        match self {
+            Selector::Name(name) => {
+                if let Some(n) = v.get(name.as_str()) {
+                    out.push(n)
+                }
+            }
+            Selector::Wildcard => children(v, out),
+            Selector::Index(i) => {
+                if let Some(a) = v.as_array() {
+                    let len = a.len() as i64;
+                    let i = if *i < 0 { len + i } else { *i };
+                    if i >= 0 && i < len {
+                        out.push(&a[i as usize]);
+                    }
+                }
+            }
+            Selector::Slice(start, end, step) => {
+                if let Some(a) = v.as_array() {
+                    slice(a, *start, *end, step.unwrap_or(1), out)
+                }
+            }
+            Selector::Filter(expr) => {
+                let mut c = Vec::new();
+                children(v, &mut c);
+                out.extend(c.into_iter().filter(|c| expr.test(root, c)));
+            }
+        }
+    }
+}
+
+#[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+fn slice<'v, V>(a: &'v [V], start: Option<i64>, end: Option<i64>, step: i64, out: &mut Vec<&'v V>) {
+    let len = a.len() as i64;
+    let normalize = |i: i64| if i >= 0 { i } else { len + i };
+    if step > 0 {
+        let lower = normalize(start.unwrap_or(0)).max(0).min(len);
+        let upper = normalize(end.unwrap_or(len)).max(0).min(len);
+        let mut i = lower;
+        while i < upper {
+            out.push(&a[i as usize]);
+            i += step;
+        }
+    } else if step < 0 {
+        let upper = normalize(start.unwrap_or(len - 1)).max(-1).min(len - 1);
+        let lower = normalize(end.unwrap_or(-len - 1)).max(-1).min(len - 1);
+        let mut i = upper;
+        while lower < i {
+            out.push(&a[i as usize]);
+            i += step;
+        }
+    }
+}
+
+impl Expr {
+    fn test<V>(&self, root: &V, current: &V) -> bool
+    where
+        V: ValueTrait,
+        V::Key: Borrow<str> + Hash + Eq,
+    {
+        match self {
+            Expr::Or(es) => es.iter().any(|e| e.test(root, current)),
+            Expr::And(es) => es.iter().all(|e| e.test(root, current)),
+            Expr::Not(e) => !e.test(root, current),
+            Expr::Test(q) => !q.eval(root, current).is_empty(),
+            Expr::Cmp(lhs, op, rhs) => {
+                let lhs = lhs.eval(root, current);
+                let rhs = rhs.eval(root, current);
+                match op {
+                    CmpOp::Eq => operands_equal(&lhs, &rhs),
+                    CmpOp::Ne => !operands_equal(&lhs, &rhs),
+                    CmpOp::Lt => operand_less(&lhs, &rhs),
+                    CmpOp::Le => operand_less(&lhs, &rhs) || operands_equal(&lhs, &rhs),
+                    CmpOp::Gt => operand_less(&rhs, &lhs),
+                    CmpOp::Ge => operand_less(&rhs, &lhs) || operands_equal(&lhs, &rhs),
+                }
+            }
+            #[cfg(feature = "jsonpath-regex")]
+            Expr::Match(m) => m.test(root, current),
+        }
+    }
+}
+
+#[cfg(feature = "jsonpath-regex")]
+impl Match {
+    fn test<V>(&self, root: &V, current: &V) -> bool
+    where
+        V: ValueTrait,
+        V::Key: Borrow<str> + Hash + Eq,
+    {
+        let value = self.value.eval(root, current);
+        let s = match value.scalar() {
+            Scalar::Str(s) => s,
+            _ => return false,
+        };
+        // patterns that aren't strings or valid I-Regexps match nothing
+        match &self.pattern {
+            Pattern::Compiled(re) => matches!(re, Some(re) if re.is_match(s)),
+            Pattern::Dynamic(p) => match p.eval(root, current).scalar() {
+                Scalar::Str(p) => matches!(iregexp(p, self.search), Some(re) if re.is_match(s)),
+                _ => false,
+            },
+        }
+    }
+}
+
+impl Comparable {
+    fn eval<'a, V>(&'a self, root: &'a V, current: &'a V) -> Operand<'a, V>
+    where
+        V: ValueTrait,
+        V::Key: Borrow<str> + Hash + Eq,
+    {
+        match self {
+            Comparable::Literal(l) => Operand::Literal(l),
+            Comparable::Query(q) | Comparable::Value(q) => q.single(root, current),
+            Comparable::Count(q) => Operand::Count(q.eval(root, current).len()),
+            Comparable::Length(arg) => match arg.eval(root, current) {
+                Operand::Literal(Literal::String(s)) => Operand::Count(s.chars().count()),
+                Operand::Node(v) => {
+                    if let Some(s) = v.as_str() {
+                        Operand::Count(s.chars().count())
+                    } else if let Some(a) = v.as_array() {
+                        Operand::Count(a.len())
+                    } else if let Some(o) = v.as_object() {
+                        Operand::Count(o.len())
+                    } else {
+                        Operand::Nothing
+                    }
+                }
+                _ => Operand::Nothing,
+            },
+        }
+    }
+
+    fn is_value(&self) -> bool {
+        match self {
+            Comparable::Query(q) => q.is_singular(),
+            _ => true,
+        }
+    }
+}
+
+struct Parser<'p> {
+    input: &'p [u8],
+    idx: usize,
+}
+
+fn is_name_first(b: u8) -> bool {
+    b.is_ascii_alphabetic() || b == b'_' || b >= 0x80
+}
+
+impl<'p> Parser<'p> {
+    fn err(&self, reason: &'static str) -> Error {
+        Error {
+            index: self.idx,
+            reason,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.idx).copied()
+    }
+
+    fn eat(&mut self, b: u8) -> bool {
+        if self.peek() == Some(b) {
+            self.idx += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_str(&mut self, s: &str) -> bool {
+        if self.input[self.idx..].starts_with(s.as_bytes()) {
+            self.idx += s.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, b: u8, reason: &'static str) -> Result<(), Error> {
+        if self.eat(b) {
+            Ok(())
+        } else {
+            Err(self.err(reason))
+        }
+    }
+
+    fn skip_ws(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.idx += 1;
+        }
+    }
+
+    fn query(&mut self, relative: bool) -> Result<Query, Error> {
+        let mut segments = Vec::new();
+        loop {
+            let start = self.idx;
+            self.skip_ws();
+            if self.eat_str("..") {
+                let selectors = if self.peek() == Some(b'[') {
+                    stry!(self.bracketed())
+                } else {
+                    vec![stry!(self.shorthand())]
+                };
+                segments.push(Segment {
+                    descendant: true,
+                    selectors,
+                });
+            } else if self.eat(b'.') {
+                segments.push(Segment {
+                    descendant: false,
+                    selectors: vec![stry!(self.shorthand())],
+                });
+            } else if self.peek() == Some(b'[') {
+                segments.push(Segment {
+                    descendant: false,
+                    selectors: stry!(self.bracketed()),
+                });
+            } else {
+                self.idx = start;
+                return Ok(Query { relative, segments });
+            }
+        }
+    }
+
+    fn shorthand(&mut self) -> Result<Selector, Error> {
+        if self.eat(b'*') {
+            Ok(Selector::Wildcard)
+        } else {
+            self.name().map(Selector::Name)
+        }
+    }
+
+    fn name(&mut self) -> Result<String, Error> {
+        let start = self.idx;
+        match self.peek() {
+            Some(b) if is_name_first(b) => (),
+            _ => return Err(self.err("expected member name")),
+        }
+        while let Some(b) = self.peek() {
+            if is_name_first(b) || b.is_ascii_digit() {
+                self.idx += 1;
+            } else {
+                break;
+            }
+        }
+        // we only stop at ASCII characters so this is always valid UTF-8
+        Ok(String::from_utf8_lossy(&self.input[start..self.idx]).into_owned())
+    }
+
+    fn bracketed(&mut self) -> Result<Vec<Selector>, Error> {
+        stry!(self.expect(b'[', "expected `[`"));
+        let mut selectors = Vec::new();
+        loop {
+            self.skip_ws();
+            selectors.push(stry!(self.selector()));
+            self.skip_ws();
+            if self.eat(b']') {
+                return Ok(selectors);
+            }
+            stry!(self.expect(b',', "expected `,` or `]`"));
+        }
+    }
+
+    fn selector(&mut self) -> Result<Selector, Error> {
+        match self.peek() {
+            Some(b'\'') | Some(b'"') => self.string().map(Selector::Name),
+            Some(b'*') => {
+                self.idx += 1;
+                Ok(Selector::Wildcard)
+            }
+            Some(b'?') => {
+                self.idx += 1;
+                self.skip_ws();
+                self.or_expr().map(Selector::Filter)
+            }
+            Some(b'-') | Some(b'0'..=b'9') | Some(b':') => self.index_or_slice(),
+            _ => Err(self.err("expected selector")),
+        }
+    }
+
+    fn index_or_slice(&mut self) -> Result<Selector, Error> {
+        let start = stry!(self.opt_int());
+        self.skip_ws();
+        if !self.eat(b':') {
+            return start
+                .map(Selector::Index)
+                .ok_or_else(|| self.err("expected index"));
+        }
+        self.skip_ws();
+        let end = stry!(self.opt_int());
+        self.skip_ws();
+        let step = if self.eat(b':') {
+            self.skip_ws();
+            stry!(self.opt_int())
+        } else {
+            None
+        };
+        Ok(Selector::Slice(start, end, step))
+    }
+
+    fn opt_int(&mut self) -> Result<Option<i64>, Error> {
+        match self.peek() {
+            Some(b'-') | Some(b'0'..=b'9') => self.int().map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    fn int(&mut self) -> Result<i64, Error> {
+        let start = self.idx;
+        let negative = self.eat(b'-');
+        let digits = self.idx;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.idx += 1;
+        }
+        let s = &self.input[digits..self.idx];
+        if s.is_empty() || (s[0] == b'0' && (s.len() > 1 || negative)) {
+            self.idx = start;
+            return Err(self.err("invalid integer"));
+        }
+        let mut n: i64 = 0;
+        for d in s {
+            n = n * 10 + i64::from(d - b'0');
+            if n > MAX_INT {
+                self.idx = start;
+                return Err(self.err("integer out of range"));
+            }
+        }
+        Ok(if negative { -n } else { n })
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        let quote = self.input[self.idx];
+        self.idx += 1;
+        let mut res = Vec::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.err("unterminated string")),
+                Some(b) if b == quote => {
+                    self.idx += 1;
+                    // only whole characters were copied so this is valid UTF-8
+                    return Ok(String::from_utf8_lossy(&res).into_owned());
+                }
+                Some(b'\\') => {
+                    self.idx += 1;
+                    let c = match self.peek() {
+                        Some(b) if b == quote => quote as char,
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'/') => '/',
+                        Some(b'\\') => '\\',
+                        Some(b'u') => stry!(self.unicode_escape()),
+                        _ => return Err(self.err("invalid escape")),
+                    };
+                    self.idx += 1;
+                    let mut buf = [0; 4];
+                    res.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                Some(b) if b < 0x20 => return Err(self.err("control character in string")),
+                Some(b) => {
+                    res.push(b);
+                    self.idx += 1;
+                }
+            }
+        }
+    }
+
+    /// Parses the `XXXX` of a `\uXXXX` escape and a following low surrogate,
+    /// leaves `idx` on the last hex digit
+    fn unicode_escape(&mut self) -> Result<char, Error> {
+        let hi = stry!(self.hex4());
+        let c = if (0xD800..0xDC00).contains(&hi) {
+            if !self.input[self.idx + 1..].starts_with(b"\\u") {
+                return Err(self.err("unpaired surrogate"));
+            }
+            self.idx += 2;
+            let lo = stry!(self.hex4());
+            if !(0xDC00..0xE000).contains(&lo) {
+                return Err(self.err("unpaired surrogate"));
+            }
+            0x10000 + ((hi - 0xD800) << 10) + (lo - 0xDC00)
+        } else {
+            hi
+        };
+        std::char::from_u32(c).ok_or_else(|| self.err("unpaired surrogate"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, Error> {
+        // `from_str_radix` would also take a leading `+`
+        let digits = self
+            .input
+            .get(self.idx + 1..self.idx + 5)
+            .filter(|d| d.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|d| std::str::from_utf8(d).ok())
+            .and_then(|d| u32::from_str_radix(d, 16).ok());
+        match digits {
+            Some(d) => {
+                self.idx += 4;
+                Ok(d)
+            }
+            None => Err(self.err("invalid unicode escape")),
+        }
+    }
+
+    fn or_expr(&mut self) -> Result<Expr, Error> {
+        let mut es = vec![stry!(self.and_expr())];
+        loop {
+            self.skip_ws();
+            if !self.eat_str("||") {
+                break;
+            }
+            self.skip_ws();
+            es.push(stry!(self.and_expr()));
+        }
+        Ok(if es.len() == 1 {
+            es.remove(0)
+        } else {
+            Expr::Or(es)
+        })
+    }
+
+    fn and_expr(&mut self) -> Result<Expr, Error> {
+        let mut es = vec![stry!(self.basic_expr())];
+        loop {
+            let start = self.idx;
+            self.skip_ws();
+            if !self.eat_str("&&") {
+                self.idx = start;
+                break;
+            }
+            self.skip_ws();
+            es.push(stry!(self.basic_expr()));
+        }
+        Ok(if es.len() == 1 {
+            es.remove(0)
+        } else {
+            Expr::And(es)
+        })
+    }
+
+    fn basic_expr(&mut self) -> Result<Expr, Error> {
+        if self.eat(b'!') {
+            self.skip_ws();
+            let start = self.idx;
+            let e = if self.peek() == Some(b'(') {
+                stry!(self.paren_expr())
+            } else if let Some(e) = stry!(self.regex_function()) {
+                e
+            } else {
+                match stry!(self.comparable()) {
+                    Comparable::Query(q) => Expr::Test(q),
+                    _ => {
+                        self.idx = start;
+                        return Err(self.err("expected a query"));
+                    }
+                }
+            };
+            return Ok(Expr::Not(Box::new(e)));
+        }
+        if self.peek() == Some(b'(') {
+            return self.paren_expr();
+        }
+        if let Some(e) = stry!(self.regex_function()) {
+            return Ok(e);
+        }
+        let start = self.idx;
+        let lhs = stry!(self.comparable());
+        let end = self.idx;
+        self.skip_ws();
+        let op = if self.eat_str("==") {
+            CmpOp::Eq
+        } else if self.eat_str("!=") {
+            CmpOp::Ne
+        } else if self.eat_str("<=") {
+            CmpOp::Le
+        } else if self.eat_str(">=") {
+            CmpOp::Ge
+        } else if self.eat(b'<') {
+            CmpOp::Lt
+        } else if self.eat(b'>') {
+            CmpOp::Gt
+        } else {
+            self.idx = end;
+            return match lhs {
+                Comparable::Query(q) => Ok(Expr::Test(q)),
+                _ => {
+                    self.idx = start;
+                    Err(self.err("expected a query or comparison"))
+                }
+            };
+        };
+        self.skip_ws();
+        let rhs_start = self.idx;
+        let rhs = stry!(self.comparable());
+        if !lhs.is_value() {
+            self.idx = start;
+            Err(self.err("only singular queries can be compared"))
+        } else if !rhs.is_value() {
+            self.idx = rhs_start;
+            Err(self.err("only singular queries can be compared"))
+        } else {
+            Ok(Expr::Cmp(lhs, op, rhs))
+        }
+    }
+
+    /// Parses a call to `match` or `search`, their result is a logical value
+    /// so they can't be compared or passed to other functions
+    #[cfg(feature = "jsonpath-regex")]
+    fn regex_function(&mut self) -> Result<Option<Expr>, Error> {
+        let search = if self.input[self.idx..].starts_with(b"match(") {
+            false
+        } else if self.input[self.idx..].starts_with(b"search(") {
+            true
+        } else {
+            return Ok(None);
+        };
+        self.idx += if search { 7 } else { 6 };
+        self.skip_ws();
+        let value = stry!(self.function_value());
+        self.skip_ws();
+        stry!(self.expect(b',', "expected `,`"));
+        self.skip_ws();
+        let pattern = stry!(self.function_value());
+        self.skip_ws();
+        stry!(self.expect(b')', "expected `)`"));
+        let pattern = match pattern {
+            Comparable::Literal(Literal::String(p)) => Pattern::Compiled(iregexp(&p, search)),
+            p => Pattern::Dynamic(p),
+        };
+        Ok(Some(Expr::Match(Box::new(Match {
+            search,
+            value,
+            pattern,
+        }))))
+    }
+
+    /// `match` and `search` need the `jsonpath-regex` feature
+    #[cfg(not(feature = "jsonpath-regex"))]
+    fn regex_function(&mut self) -> Result<Option<Expr>, Error> {
+        let rest = &self.input[self.idx..];
+        if rest.starts_with(b"match(") || rest.starts_with(b"search(") {
+            Err(self.err("`match` and `search` need the `jsonpath-regex` feature"))
+        } else {
+            Ok(None)
+        }
+    }
+
+    #[cfg(feature = "jsonpath-regex")]
+    fn function_value(&mut self) -> Result<Comparable, Error> {
+        let start = self.idx;
+        let arg = stry!(self.comparable());
+        if arg.is_value() {
+            Ok(arg)
+        } else {
+            self.idx = start;
+            Err(self.err("expected a value"))
+        }
+    }
+
+    fn paren_expr(&mut self) -> Result<Expr, Error> {
+        stry!(self.expect(b'(', "expected `(`"));
+        self.skip_ws();
+        let e = stry!(self.or_expr());
+        self.skip_ws();
+        stry!(self.expect(b')', "expected `)`"));
+        Ok(e)
+    }
+
+    fn comparable(&mut self) -> Result<Comparable, Error> {
+        match self.peek() {
+            Some(b'@') => {
+                self.idx += 1;
+                self.query(true).map(Comparable::Query)
+            }
+            Some(b'$') => {
+                self.idx += 1;
+                self.query(false).map(Comparable::Query)
+            }
+            Some(b'\'') | Some(b'"') => self
+                .string()
+                .map(|s| Comparable::Literal(Literal::String(s))),
+            Some(b'-') | Some(b'0'..=b'9') => self
+                .number()
+                .map(|n| Comparable::Literal(Literal::Number(n))),
+            Some(b'a'..=b'z') => self.keyword(),
+            _ => Err(self.err("expected a query, literal or function")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Num, Error> {
+        let start = self.idx;
+        self.eat(b'-');
+        let digits = self.idx;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.idx += 1;
+        }
+        let int_len = self.idx - digits;
+        if int_len == 0 || (int_len > 1 && self.input[digits] == b'0') {
+            self.idx = start;
+            return Err(self.err("invalid number"));
+        }
+        let mut float = false;
+        if self.eat(b'.') {
+            float = true;
+            stry!(self.digits());
+        }
+        if self.eat(b'e') || self.eat(b'E') {
+            float = true;
+            if !self.eat(b'+') {
+                self.eat(b'-');
+            }
+            stry!(self.digits());
+        }
+        // the number only consists of ASCII characters
+        let s = String::from_utf8_lossy(&self.input[start..self.idx]);
+        let n = if float {
+            s.parse().ok().map(Num::Float)
+        } else {
+            s.parse().ok().map(Num::Int)
+        };
+        n.ok_or_else(|| self.err("invalid number"))
+    }
+
+    fn digits(&mut self) -> Result<(), Error> {
+        let start = self.idx;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.idx += 1;
+        }
+        if self.idx == start {
+            Err(self.err("expected digit"))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn keyword(&mut self) -> Result<Comparable, Error> {
+        let start = self.idx;
+        while let Some(b'a'..=b'z') | Some(b'0'..=b'9') | Some(b'_') = self.peek() {
+            self.idx += 1;
+        }
+        let literal = match &self.input[start..self.idx] {
+            b"true" => Some(Literal::Bool(true)),
+            b"false" => Some(Literal::Bool(false)),
+            b"null" => Some(Literal::Null),
+            _ => None,
+        };
+        if let Some(l) = literal {
+            return Ok(Comparable::Literal(l));
+        }
+        let name = &self.input[start..self.idx];
+        if !self.eat(b'(') {
+            self.idx = start;
+            return Err(self.err("expected a query, literal or function"));
+        }
+        self.skip_ws();
+        let arg_start = self.idx;
+        let f = match name {
+            b"length" => {
+                let arg = stry!(self.comparable());
+                if arg.is_value() {
+                    Comparable::Length(Box::new(arg))
+                } else {
+                    self.idx = arg_start;
+                    return Err(self.err("`length` expects a value"));
+                }
+            }
+            b"count" | b"value" => {
+                let q = match stry!(self.comparable()) {
+                    Comparable::Query(q) => q,
+                    _ => {
+                        self.idx = arg_start;
+                        return Err(self.err("expected a query"));
+                    }
+                };
+                if name == b"count" {
+                    Comparable::Count(q)
+                } else {
+                    Comparable::Value(q)
+                }
+            }
+            b"match" | b"search" => {
+                self.idx = start;
+                return Err(self.err("`match` and `search` can only be tested"));
+            }
+            _ => {
+                self.idx = start;
+                return Err(self.err("unknown function"));
+            }
+        };
+        self.skip_ws();
+        stry!(self.expect(b')', "expected `)`"));
+        Ok(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{query, JsonPath};
+    use crate::{BorrowedValue, OwnedValue};
+
+    fn store() -> OwnedValue {
+        json!({ "store": {
+            "book": [
+                { "category": "reference",
+                  "author": "Nigel Rees",
+                  "title": "Sayings of the Century",
+                  "price": 8.95
+                },
+                { "category": "fiction",
+                  "author": "Evelyn Waugh",
+                  "title": "Sword of Honour",
+                  "price": 12.99
+                },
+                { "category": "fiction",
+                  "author": "Herman Melville",
+                  "title": "Moby Dick",
+                  "isbn": "0-553-21311-3",
+                  "price": 8.99
+                },
+                { "category": "fiction",
+                  "author": "J. R. R. Tolkien",
+                  "title": "The Lord of the Rings",
+                  "isbn": "0-395-19395-8",
+                  "price": 22.99
+                }
+            ],
+            "bicycle": {
+                "color": "red",
+                "price": 399
+            }
+        }})
+    }
+
+    fn strs(nodes: Vec<&OwnedValue>) -> Vec<String> {
+        nodes.into_iter().map(|n| n.encode()).collect()
+    }
+
+    #[test]
+    fn rfc9535_examples() {
+        let v = store();
+        let authors = r#"["\"Nigel Rees\"", "\"Evelyn Waugh\"", "\"Herman Melville\"", "\"J. R. R. Tolkien\""]"#;
+        let authors: Vec<String> = serde_json::from_str(authors).expect("json");
+        let q = |p: &str| strs(query(p, &v).expect(p));
+
+        assert_eq!(q("$.store.book[*].author"), authors);
+        assert_eq!(q("$..author"), authors);
+        assert_eq!(q("$.store.*").len(), 2);
+        assert_eq!(q("$.store..price").len(), 5);
+        assert_eq!(q("$..book[2].title"), vec![r#""Moby Dick""#]);
+        assert_eq!(q("$..book[-1].title"), vec![r#""The Lord of the Rings""#]);
+        assert_eq!(
+            q("$..book[0,1].title"),
+            vec![r#""Sayings of the Century""#, r#""Sword of Honour""#]
+        );
+        assert_eq!(
+            q("$..book[:2].title"),
+            vec![r#""Sayings of the Century""#, r#""Sword of Honour""#]
+        );
+        assert_eq!(
+            q("$..book[?@.isbn].title"),
+            vec![r#""Moby Dick""#, r#""The Lord of the Rings""#]
+        );
+        assert_eq!(
+            q("$..book[?@.price<10].title"),
+            vec![r#""Sayings of the Century""#, r#""Moby Dick""#]
+        );
+        assert_eq!(
+            q("$.store.book[?(@.price < 10)].title"),
+            vec![r#""Sayings of the Century""#, r#""Moby Dick""#]
+        );
+        assert_eq!(q("$..*").len(), 27);
+        assert_eq!(q("$"), vec![v.encode()]);
+    }
+
+    #[test]
+    fn borrowed() {
+        let v: BorrowedValue = store().into();
+        let path = JsonPath::compile("$.store.bicycle['color', \"price\"]").expect("path");
+        assert_eq!(
+            path.select(&v),
+            vec![&BorrowedValue::from("red"), &BorrowedValue::from(399)]
+        );
+        let path: JsonPath = "$..book[?@.author == 'Herman Melville'].price"
+            .parse()
+            .expect("path");
+        assert_eq!(path.select(&v), vec![&BorrowedValue::from(8.99)]);
+    }
+
+    #[test]
+    fn slices() {
+        let v = json!([0, 1, 2, 3, 4, 5, 6]);
+        let q = |p: &str| strs(query(p, &v).expect(p)).join(",");
+        assert_eq!(q("$[1:3]"), "1,2");
+        assert_eq!(q("$[5:]"), "5,6");
+        assert_eq!(q("$[1:5:2]"), "1,3");
+        assert_eq!(q("$[5:1:-2]"), "5,3");
+        assert_eq!(q("$[::-1]"), "6,5,4,3,2,1,0");
+        assert_eq!(q("$[-2:]"), "5,6");
+        assert_eq!(q("$[-100:100:3]"), "0,3,6");
+        assert_eq!(q("$[1:3:0]"), "");
+        assert_eq!(q("$[7]"), "");
+        assert_eq!(q("$[-7]"), "0");
+        assert_eq!(q("$[0, 0, 1:3]"), "0,0,1,2");
+    }
+
+    #[test]
+    fn filters() {
+        let v = json!({
+            "a": [3, 5, 1, 2, 4, 6, {"b": "j"}, {"b": "k"}, {"b": {}}, {"b": "kilo"}],
+            "o": {"p": 1, "q": 2, "r": 3, "s": 5, "t": {"u": 6}},
+            "e": [{"x": [1, 2]}, {"x": [1, 2.0]}, {"x": [2]}, {"x": {"y": null}}]
+        });
+        let q = |p: &str| strs(query(p, &v).expect(p)).join(",");
+        assert_eq!(q("$.a[?@ > 3.5]"), "5,4,6");
+        assert_eq!(
+            q("$.a[?@.b]"),
+            r#"{"b":"j"},{"b":"k"},{"b":{}},{"b":"kilo"}"#
+        );
+        assert_eq!(q("$.a[?@.b == 'kilo']"), r#"{"b":"kilo"}"#);
+        assert_eq!(q("$.a[?@.b < 'k']"), r#"{"b":"j"}"#);
+        assert_eq!(q("$.a[?@ == 1 || @ == 6]"), "1,6");
+        assert_eq!(q("$.a[?@ > 1 && @ < 4]"), "3,2");
+        assert_eq!(q("$.a[?!(@ > 1 && @ < 4) && @ <= 5]"), "5,1,4");
+        assert_eq!(q("$.a[?!@.b]"), "3,5,1,2,4,6");
+        assert_eq!(q("$.a[?@.b == $.a[8].b]"), r#"{"b":{}}"#);
+        // missing values only compare equal to each other
+        assert_eq!(q("$.a[?@.b == $.o.t.v]"), "3,5,1,2,4,6");
+        assert_eq!(q("$.a[?@.c == $.o.t.v]"), q("$.a.*"));
+        assert_eq!(q("$.a[?@.c != 1]"), q("$.a.*"));
+        assert_eq!(q("$.a[?@.c <= $.o.t.v]"), q("$.a.*"));
+        assert_eq!(q("$.a[?@.c < $.o.t.v]"), "");
+        assert_eq!(q("$.o[?@ >= $.o.q]"), "2,3,5");
+        assert_eq!(q("$.o[?@.u]"), r#"{"u":6}"#);
+        assert_eq!(q("$.e[?@.x == $.e[0].x].x"), "[1,2],[1,2.0]");
+        assert_eq!(q("$.e[?@.x == null]"), "");
+        assert_eq!(q("$.e[?@.x.y == null].x"), r#"{"y":null}"#);
+        assert_eq!(q("$.e[?length(@.x) == 2].x"), "[1,2],[1,2.0]");
+        assert_eq!(q("$.e[?length(@.x) == 1].x"), r#"[2],{"y":null}"#);
+        assert_eq!(q("$.a[?length(@.b) == 4]"), r#"{"b":"kilo"}"#);
+        assert_eq!(q("$[?count(@.*) == 5]").len(), q("$.o").len());
+        assert_eq!(q("$.e[?value(@..y) == null]"), r#"{"x":{"y":null}}"#);
+        assert_eq!(q("$[?@.*[?@ == 6]]"), q("$.o"));
+    }
+
+    #[test]
+    #[cfg(feature = "jsonpath-regex")]
+    fn regex_functions() {
+        let v = json!([
+            {"s": "1974-05-11", "p": "1974-.*"},
+            {"s": "1974-05-1x", "p": "[a-z]"},
+            {"s": "Bob", "p": 1},
+            {"s": "a\nb", "p": "a.b"},
+            {"s": "a^b$", "p": "\\p{Lu}"},
+            {"s": 1974}
+        ]);
+        let q = |p: &str| strs(query(p, &v).expect(p)).join(",");
+        let s = |p: &str| q(&format!("{}.s", p));
+        assert_eq!(
+            s("$[?match(@.s, '1974-05-..')]"),
+            r#""1974-05-11","1974-05-1x""#
+        );
+        assert_eq!(s("$[?match(@.s, '1974-05-\\\\d\\\\d')]"), "");
+        assert_eq!(
+            s("$[?match(@.s, '[0-9]{4}(-[0-9]{2}){2}')]"),
+            r#""1974-05-11""#
+        );
+        assert_eq!(s("$[?match(@.s, '05')]"), "");
+        assert_eq!(s("$[?search(@.s, '05')]"), r#""1974-05-11","1974-05-1x""#);
+        assert_eq!(s("$[?search(@.s, '[BR]ob')]"), r#""Bob""#);
+        // values that aren't strings never match
+        assert_eq!(s("$[?!search(@.s, '[^0-9-]')]"), r#""1974-05-11",1974"#);
+        // `.` doesn't match line breaks, `^` and `$` are plain characters
+        assert_eq!(s("$[?match(@.s, 'a.b')]"), "");
+        assert_eq!(s("$[?match(@.s, 'a^b$')]"), r#""a^b$""#);
+        assert_eq!(s("$[?search(@.s, '\\\\p{Lu}')]"), r#""Bob""#);
+        assert_eq!(
+            s("$[?search(@.s, '[\\\\p{Lu}x]')]"),
+            r#""1974-05-1x","Bob""#
+        );
+        // the pattern can come from the document
+        assert_eq!(s("$[?match(@.s, @.p)]"), r#""1974-05-11""#);
+        assert_eq!(s("$[?search(@.s, @.p)]"), r#""1974-05-11","1974-05-1x""#);
+        // invalid patterns match nothing
+        for p in &[
+            "a**",
+            "(a",
+            "a)",
+            "[]",
+            "[a-]b-c]",
+            "\\\\d",
+            "(?:a)",
+            "\\\\p{Xx}",
+            "a{,2}",
+            "[b-a]",
+        ] {
+            assert_eq!(q(&format!("$[?search(@.s, '{}')]", p)), "", "{}", p);
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "jsonpath-regex"))]
+    fn regex_functions_need_feature() {
+        let e = JsonPath::compile("$[?match(@.a, 'a')]").expect_err("compiled");
+        assert_eq!(e.index(), 3);
+        assert_eq!(
+            e.reason(),
+            "`match` and `search` need the `jsonpath-regex` feature"
+        );
+    }
+
+    #[test]
+    fn names() {
+        let v = json!({"a b": 1, "ü": 2, "'\"": 3, "\u{1F600}": 4, "_x1": 5});
+        let q = |p: &str| strs(query(p, &v).expect(p)).join(",");
+        assert_eq!(q("$['a b']"), "1");
+        assert_eq!(q("$.ü"), "2");
+        assert_eq!(q(r#"$['\'"']"#), "3");
+        assert_eq!(q(r#"$["'\""]"#), "3");
+        assert_eq!(q(r#"$["\ud83d\ude00"]"#), "4");
+        assert_eq!(q("$._x1"), "5");
+        assert_eq!(q("$ .a"), "");
+    }
+
+    #[test]
+    fn errors() {
+        let invalid = [
+            "",
+            "store",
+            "$.",
+            "$..",
+            "$[",
+            "$[]",
+            "$[01]",
+            "$[-0]",
+            "$[1,]",
+            "$['a]",
+            "$['\\a']",
+            "$[\"\\ud83d\"]",
+            "$[\"\\u+041\"]",
+            "$[9007199254740992]",
+            "$ ",
+            "$.1a",
+            "$[?@.a == $..b]",
+            "$[?@.* == 1]",
+            "$[?1]",
+            "$[?length(@.a)]",
+            "$[?!@.a == 1]",
+            "$[?match(@.a, 'a') == true]",
+            "$[?match(@.a)]",
+            "$[?search(@.a 'a')]",
+            "$[?match(@.*, 'a')]",
+            "$[?length(match(@.a, 'a')) == 1]",
+            "$[?@.a == search(@.a, 'a')]",
+            "$[?foo(@.a)]",
+            "$[?@.a == 01]",
+            "$[?(@.a == 1]",
+            "$[?count(1) == 1]",
+            "$[?@.a == {}]",
+        ];
+        for p in invalid.iter() {
+            assert!(JsonPath::compile(p).is_err(), "{:?} compiled", p);
+        }
+        let e = JsonPath::compile("$.a[?@.b == $..c]").expect_err("compiled");
+        assert_eq!(e.index(), 12);
+        assert_eq!(e.reason(), "only singular queries can be compared");
+    }
+}
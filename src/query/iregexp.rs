@@ -0,0 +1,201 @@
+//! Translation of the I-Regexp (RFC 9485) patterns taken by the JSONPath
+//! `match` and `search` functions to the syntax of the `regex` crate.
+
+use regex::Regex;
+
+/// A compiled I-Regexp
+#[derive(Debug, Clone)]
+pub(super) struct Regexp(Regex);
+
+impl PartialEq for Regexp {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl Regexp {
+    pub(super) fn is_match(&self, s: &str) -> bool {
+        self.0.is_match(s)
+    }
+}
+
+/// Compiles an I-Regexp, `search` patterns may match any substring while
+/// `match` patterns have to match the whole string.
+pub(super) fn iregexp(pattern: &str, search: bool) -> Option<Regexp> {
+    let re = translate(pattern)?;
+    let re = if search {
+        re
+    } else {
+        format!(r"\A(?:{})\z", re)
+    };
+    Regex::new(&re).ok().map(Regexp)
+}
+
+/// Translates an I-Regexp into the syntax of the `regex` crate, `None` if
+/// `pattern` isn't a valid I-Regexp
+fn translate(pattern: &str) -> Option<String> {
+    let mut out = String::with_capacity(pattern.len() + 8);
+    let mut chars = pattern.chars().peekable();
+    let mut depth = 0_usize;
+    // only atoms can be quantified, and only once
+    let mut quantifiable = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '(' => {
+                depth += 1;
+                out.push_str("(?:");
+                quantifiable = false;
+                continue;
+            }
+            ')' => {
+                depth = depth.checked_sub(1)?;
+                out.push(')');
+            }
+            '|' => {
+                out.push('|');
+                quantifiable = false;
+                continue;
+            }
+            '*' | '+' | '?' | '{' => {
+                if !quantifiable {
+                    return None;
+                }
+                out.push(c);
+                if c == '{' {
+                    range_quantifier(&mut chars, &mut out)?;
+                }
+                quantifiable = false;
+                continue;
+            }
+            // `.` doesn't match line breaks in I-Regexp
+            '.' => out.push_str(r"[^\n\r]"),
+            '\\' => {
+                escape(&mut chars, &mut out, true)?;
+            }
+            '[' => char_class(&mut chars, &mut out)?,
+            ']' | '}' => return None,
+            c => out.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+        quantifiable = true;
+    }
+    if depth == 0 {
+        Some(out)
+    } else {
+        None
+    }
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+/// Copies the `n}`, `n,}` or `n,m}` of a range quantifier
+fn range_quantifier(chars: &mut Chars, out: &mut String) -> Option<()> {
+    let digits = |chars: &mut Chars, out: &mut String| {
+        let mut n = 0;
+        while let Some(c @ '0'..='9') = chars.peek().copied() {
+            chars.next();
+            out.push(c);
+            n += 1;
+        }
+        n
+    };
+    if digits(chars, out) == 0 {
+        return None;
+    }
+    if chars.peek() == Some(&',') {
+        chars.next();
+        out.push(',');
+        digits(chars, out);
+    }
+    if chars.next()? == '}' {
+        out.push('}');
+        Some(())
+    } else {
+        None
+    }
+}
+
+/// Copies the escape following a `\`, category escapes are only allowed if
+/// `category` is set. Returns `true` for escapes of a single character.
+fn escape(chars: &mut Chars, out: &mut String, category: bool) -> Option<bool> {
+    const CATEGORIES: [&str; 36] = [
+        "L", "Ll", "Lm", "Lo", "Lt", "Lu", "M", "Mc", "Me", "Mn", "N", "Nd", "Nl", "No", "P", "Pc",
+        "Pd", "Pe", "Pf", "Pi", "Po", "Ps", "Z", "Zl", "Zp", "Zs", "S", "Sc", "Sk", "Sm", "So",
+        "C", "Cc", "Cf", "Cn", "Co",
+    ];
+    match chars.next()? {
+        'n' => out.push_str(r"\n"),
+        'r' => out.push_str(r"\r"),
+        't' => out.push_str(r"\t"),
+        c @ 'p' | c @ 'P' if category => {
+            if chars.next()? != '{' {
+                return None;
+            }
+            let mut name = String::new();
+            loop {
+                match chars.next()? {
+                    '}' => break,
+                    c => name.push(c),
+                }
+            }
+            if !CATEGORIES.contains(&name.as_str()) {
+                return None;
+            }
+            out.push('\\');
+            out.push(c);
+            out.push('{');
+            out.push_str(&name);
+            out.push('}');
+            return Some(false);
+        }
+        c if "()*+-.?[\\]^{|}".contains(c) => {
+            out.push_str(&regex::escape(c.encode_utf8(&mut [0; 4])))
+        }
+        _ => return None,
+    }
+    Some(true)
+}
+
+/// Copies a character class expression following its `[`
+fn char_class(chars: &mut Chars, out: &mut String) -> Option<()> {
+    out.push('[');
+    if chars.peek() == Some(&'^') {
+        chars.next();
+        out.push('^');
+    }
+    let mut empty = true;
+    // whether the last thing copied can start a range
+    let mut single = false;
+    loop {
+        let c = chars.next()?;
+        match c {
+            ']' if !empty => {
+                out.push(']');
+                return Some(());
+            }
+            // `-` is only literal at the start and the end of the class
+            '-' if empty || chars.peek() == Some(&']') => {
+                out.push_str(r"\-");
+                single = false;
+            }
+            '-' if single => {
+                out.push('-');
+                let c = chars.next()?;
+                match c {
+                    '\\' => {
+                        escape(chars, out, false)?;
+                    }
+                    '[' | ']' | '-' => return None,
+                    c => out.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+                }
+                single = false;
+            }
+            '[' | ']' | '-' => return None,
+            '\\' => single = escape(chars, out, true)?,
+            c => {
+                out.push_str(&regex::escape(c.encode_utf8(&mut [0; 4])));
+                single = true;
+            }
+        }
+        empty = false;
+    }
+}
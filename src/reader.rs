@@ -0,0 +1,555 @@
+use crate::{Error, ErrorType, Limit, Limits, OwnedValue, Parser, Result};
+#[cfg(feature = "serde_impl")]
+use serde_ext::de::DeserializeOwned;
+use std::io::{self, Read};
+use std::mem;
+
+/// Number of bytes requested from the reader at a time
+const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// One of the `ReaderStream::next_*` functions
+type NextFn<R, T> = fn(&mut ReaderStream<R>) -> Option<(usize, Result<T>)>;
+
+/// Reads the only document in `reader` into an `OwnedValue`. The input is
+/// pulled from the reader in chunks instead of having to be buffered up front,
+/// whitespace may follow the document but anything else is an error.
+///
+/// ```
+/// use simd_json::ValueTrait;
+///
+/// let v = simd_json::to_owned_value_from_reader(&b"{\"id\": 1}\n"[..]).unwrap();
+/// assert_eq!(v["id"], 1);
+/// assert!(simd_json::to_owned_value_from_reader(&b"[1] [2]"[..]).is_err());
+/// ```
+pub fn to_owned_value_from_reader<R>(reader: R) -> Result<OwnedValue>
+where
+    R: Read,
+{
+    ReaderStream::new(reader).single(ReaderStream::next_owned)
+}
+
+/// A stream of JSON documents read from a `std::io::Read`.
+///
+/// The input is pulled from the reader in chunks, only the document that is
+/// currently parsed is kept in memory. Documents can be concatenated
+/// (`{"a": 1}{"a": 2}`) or separated by whitespace, which covers newline
+/// delimited JSON as well. Each document is returned with the byte offset it
+/// starts at in the input. An invalid document only results in an error for
+/// that document, the stream continues with the next one. Once the reader
+/// fails the error is returned and the stream ends.
+///
+/// ```
+/// use simd_json::{ReaderStream, ValueTrait};
+///
+/// let input = &b"{\"id\": 1}\n{\"id\": 2} [3]"[..];
+/// let mut stream = ReaderStream::new(input);
+///
+/// let (offset, v) = stream.next_owned().unwrap();
+/// assert_eq!(offset, 0);
+/// assert_eq!(v.unwrap()["id"], 1);
+/// let (offset, v) = stream.next_owned().unwrap();
+/// assert_eq!(offset, 10);
+/// assert_eq!(v.unwrap()["id"], 2);
+/// let (offset, v) = stream.next_owned().unwrap();
+/// assert_eq!(offset, 20);
+/// assert_eq!(v.unwrap().get_idx(0), Some(&3.into()));
+/// assert!(stream.next_owned().is_none());
+/// ```
+pub struct ReaderStream<R> {
+    reader: R,
+    /// bytes read but not yet consumed, `buf[0]` is at `offset` in the input
+    buf: Vec<u8>,
+    offset: usize,
+    /// start of the current document in `buf`
+    start: usize,
+    /// position of the scanner in `buf`
+    pos: usize,
+    scan: Scan,
+    /// the current document exceeded `max_document_size` and is dropped
+    skipping: bool,
+    eof: bool,
+    line_delimited: bool,
+    chunk_size: usize,
+    max_document_size: Option<usize>,
+    limits: Limits,
+    parser: Parser,
+}
+
+/// State of the scanner looking for the end of the current document
+#[derive(Debug, Default)]
+struct Scan {
+    in_doc: bool,
+    /// the document is a number or literal, it ends at the next whitespace
+    /// or structural character
+    scalar: bool,
+    in_string: bool,
+    escaped: bool,
+    depth: usize,
+}
+
+impl<R> ReaderStream<R>
+where
+    R: Read,
+{
+    /// Creates a new stream reading documents from `reader`.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: Vec::new(),
+            offset: 0,
+            start: 0,
+            pos: 0,
+            scan: Scan::default(),
+            skipping: false,
+            eof: false,
+            line_delimited: false,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            max_document_size: None,
+            limits: Limits::default(),
+            parser: Parser::new(),
+        }
+    }
+
+    /// Treats each line as a document. An invalid line, for example one
+    /// with an unbalanced bracket, then only affects that line.
+    pub fn line_delimited(mut self) -> Self {
+        self.line_delimited = true;
+        self
+    }
+
+    /// Sets the number of bytes requested from the reader at a time,
+    /// defaults to 64KiB.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Limits the size of a single document. Larger documents result in a
+    /// `LimitExceeded` error and are skipped without buffering them, this
+    /// bounds the memory used by the stream.
+    pub fn max_document_size(mut self, max: usize) -> Self {
+        self.max_document_size = Some(max);
+        self.parser = Parser::with_max_capacity(max).with_limits(self.limits);
+        self
+    }
+
+    /// Sets the limits each document is checked against, see `Limits`. A
+    /// `max_document_size` is applied while reading as well.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self.parser = mem::take(&mut self.parser).with_limits(limits);
+        if limits.max_document_size < usize::max_value() {
+            self = self.max_document_size(limits.max_document_size);
+        }
+        self
+    }
+
+    /// Returns the byte offset and value of the next document as an
+    /// `OwnedValue`, `None` once the reader is exhausted.
+    pub fn next_owned(&mut self) -> Option<(usize, Result<OwnedValue>)> {
+        let (offset, doc) = self.next_document()?;
+        let parser = &mut self.parser;
+        let buf = &mut self.buf;
+        Some((
+            offset,
+            doc.and_then(|(s, e)| parser.parse_owned(&mut buf[s..e])),
+        ))
+    }
+
+    /// Returns the byte offset of the next document and deserializes it
+    /// using serde, `None` once the reader is exhausted.
+    #[cfg(feature = "serde_impl")]
+    pub fn next_deserialize<T>(&mut self) -> Option<(usize, Result<T>)>
+    where
+        T: DeserializeOwned,
+    {
+        let (offset, doc) = self.next_document()?;
+        let parser = &mut self.parser;
+        let buf = &mut self.buf;
+        Some((
+            offset,
+            doc.and_then(|(s, e)| parser.deserialize(&mut buf[s..e])),
+        ))
+    }
+
+    /// Returns the document read by `next`, it has to be the only one in the
+    /// input.
+    pub(crate) fn single<T>(mut self, next: NextFn<R, T>) -> Result<T> {
+        let v = match next(&mut self) {
+            Some((_, v)) => stry!(v),
+            None => return Err(Error::generic(ErrorType::EOF)),
+        };
+        match self.next_document() {
+            None => Ok(v),
+            Some((_, Err(e))) => Err(e),
+            Some((_, Ok(_))) => Err(Error::generic(ErrorType::TrailingCharacters)),
+        }
+    }
+
+    /// Reads until the end of the next document is found and returns its
+    /// offset and the range it occupies in `buf`.
+    fn next_document(&mut self) -> Option<(usize, Result<(usize, usize)>)> {
+        loop {
+            let end = if self.line_delimited {
+                self.scan_line()
+            } else {
+                self.scan_document()
+            };
+            if let Some(end) = end {
+                let start = self.start;
+                self.start = self.pos;
+                self.scan = Scan::default();
+                if self.skipping {
+                    self.skipping = false;
+                    continue;
+                }
+                return Some((self.offset + start, Ok((start, end))));
+            }
+
+            if let Some(max) = self.max_document_size {
+                if self.scan.in_doc && !self.skipping && self.pos - self.start > max {
+                    self.skipping = true;
+                    let offset = self.offset + self.start;
+                    let e = ErrorType::LimitExceeded(Limit::DocumentSize);
+                    return Some((offset, Err(Error::generic(e))));
+                }
+            }
+
+            if self.eof {
+                if self.scan.in_doc && !self.skipping {
+                    // The input ended before the document did
+                    let start = self.start;
+                    self.start = self.pos;
+                    self.scan = Scan::default();
+                    return Some((self.offset + start, Ok((start, self.pos))));
+                }
+                return None;
+            }
+
+            if let Err(e) = self.fill() {
+                // We can't tell where the input continues, so we stop here
+                self.eof = true;
+                self.scan = Scan::default();
+                self.start = self.pos;
+                let offset = self.offset + self.pos;
+                return Some((offset, Err(e.into())));
+            }
+        }
+    }
+
+    /// Drops consumed bytes and reads the next chunk from the reader.
+    fn fill(&mut self) -> io::Result<()> {
+        if self.skipping {
+            self.start = self.pos;
+        }
+        if self.start > 0 {
+            self.buf.drain(..self.start);
+            self.offset += self.start;
+            self.pos -= self.start;
+            self.start = 0;
+        }
+        let len = self.buf.len();
+        self.buf.resize(len + self.chunk_size, 0);
+        loop {
+            match self.reader.read(&mut self.buf[len..]) {
+                Ok(n) => {
+                    self.buf.truncate(len + n);
+                    self.eof = n == 0;
+                    return Ok(());
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.buf.truncate(len);
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// Scans the buffered input for the end of the current document.
+    fn scan_document(&mut self) -> Option<usize> {
+        let s = &mut self.scan;
+        while let Some(c) = self.buf.get(self.pos).copied() {
+            if !s.in_doc {
+                self.pos += 1;
+                if is_ws(c) {
+                    self.start = self.pos;
+                    continue;
+                }
+                s.in_doc = true;
+                match c {
+                    b'{' | b'[' => s.depth = 1,
+                    b'"' => s.in_string = true,
+                    // A stray structural character is a document on its own
+                    b'}' | b']' | b',' | b':' => return Some(self.pos),
+                    _ => s.scalar = true,
+                }
+            } else if s.in_string {
+                if s.escaped {
+                    s.escaped = false;
+                } else if c == b'\\' {
+                    s.escaped = true;
+                } else if c == b'"' {
+                    s.in_string = false;
+                    if s.depth == 0 {
+                        self.pos += 1;
+                        return Some(self.pos);
+                    }
+                } else if c == b'\n' {
+                    // Strings can't contain raw newlines so the document is
+                    // broken, ending it here keeps it from swallowing the
+                    // documents that follow.
+                    return Some(self.pos);
+                }
+                self.pos += 1;
+            } else if s.scalar {
+                if is_ws(c) || is_structural(c) {
+                    return Some(self.pos);
+                }
+                self.pos += 1;
+            } else {
+                self.pos += 1;
+                match c {
+                    b'"' => s.in_string = true,
+                    b'{' | b'[' => s.depth += 1,
+                    b'}' | b']' => {
+                        s.depth -= 1;
+                        if s.depth == 0 {
+                            return Some(self.pos);
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+        None
+    }
+
+    /// Scans the buffered input for the end of the current line, skipping
+    /// lines that only contain whitespace.
+    fn scan_line(&mut self) -> Option<usize> {
+        let s = &mut self.scan;
+        while let Some(c) = self.buf.get(self.pos).copied() {
+            self.pos += 1;
+            if c == b'\n' {
+                if s.in_doc {
+                    return Some(self.pos - 1);
+                }
+                self.start = self.pos;
+            } else if !is_ws(c) {
+                s.in_doc = true;
+            }
+        }
+        None
+    }
+}
+
+impl<R> Iterator for ReaderStream<R>
+where
+    R: Read,
+{
+    type Item = (usize, Result<OwnedValue>);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_owned()
+    }
+}
+
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+fn is_ws(c: u8) -> bool {
+    c == b' ' || c == b'\t' || c == b'\r' || c == b'\n'
+}
+
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+fn is_structural(c: u8) -> bool {
+    c == b'{' || c == b'}' || c == b'[' || c == b']' || c == b',' || c == b':' || c == b'"'
+}
+
+#[cfg(test)]
+mod test {
+    use super::{to_owned_value_from_reader, ReaderStream};
+    use crate::{to_owned_value, Error, ErrorType, Limit, OwnedValue, ValueTrait};
+    use std::io::{self, Read};
+
+    fn collect(input: &str, chunk_size: usize) -> Vec<(usize, Option<OwnedValue>)> {
+        ReaderStream::new(input.as_bytes())
+            .chunk_size(chunk_size)
+            .map(|(offset, v)| (offset, v.ok()))
+            .collect()
+    }
+
+    fn value(s: &str) -> Option<OwnedValue> {
+        let mut d = s.as_bytes().to_vec();
+        Some(to_owned_value(&mut d).expect("invalid test data"))
+    }
+
+    #[test]
+    fn concatenated() {
+        let input = "{\"a\": \"}\\\"\"}[1,[2]]\n\n  \"str\"42 true\tnull{}";
+        let expected = vec![
+            (0, value("{\"a\": \"}\\\"\"}")),
+            (12, value("[1,[2]]")),
+            (23, value("\"str\"")),
+            (28, value("42")),
+            (31, value("true")),
+            (36, value("null")),
+            (40, value("{}")),
+        ];
+        // the chunk size must not change the result
+        for chunk_size in &[1, 2, 3, 7, 64] {
+            assert_eq!(collect(input, *chunk_size), expected);
+        }
+    }
+
+    #[test]
+    fn errors_dont_abort() {
+        let input = "{\"a\": 1}\n{\"a\": }\n] [1 2]\n{\"a\": \"b\n[2]";
+        for chunk_size in &[1, 5, 64] {
+            assert_eq!(
+                collect(input, *chunk_size),
+                vec![
+                    (0, value("{\"a\": 1}")),
+                    (9, None),
+                    (17, None),
+                    (19, None),
+                    (25, None),
+                    (34, value("[2]")),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn unterminated() {
+        assert_eq!(collect("[1] [2, 3", 2), vec![(0, value("[1]")), (4, None)]);
+    }
+
+    #[test]
+    fn line_delimited() {
+        let input = "{\"a\": 1}\n[1, 2\n  \r\n{} {}\n{\"a\": 2}";
+        let res: Vec<_> = ReaderStream::new(input.as_bytes())
+            .line_delimited()
+            .chunk_size(3)
+            .map(|(offset, v)| (offset, v.ok()))
+            .collect();
+        assert_eq!(
+            res,
+            vec![
+                (0, value("{\"a\": 1}")),
+                (9, None),
+                (19, None),
+                (25, value("{\"a\": 2}")),
+            ]
+        );
+    }
+
+    #[test]
+    fn max_document_size() {
+        let big = format!("[{}1]", "1,".repeat(1000));
+        let input = format!("[1]\n{}\n[2]", big);
+        let mut stream = ReaderStream::new(input.as_bytes())
+            .chunk_size(16)
+            .max_document_size(64);
+        let (_, v) = stream.next_owned().expect("first document");
+        assert_eq!(v.expect("valid document").get_idx(0), Some(&1.into()));
+        let (offset, v) = stream.next_owned().expect("second document");
+        assert_eq!(offset, 4);
+        assert_eq!(
+            v,
+            Err(Error::generic(ErrorType::LimitExceeded(
+                Limit::DocumentSize
+            )))
+        );
+        let (offset, v) = stream.next_owned().expect("third document");
+        assert_eq!(offset, big.len() + 5);
+        assert_eq!(v.expect("valid document").get_idx(0), Some(&2.into()));
+        assert!(stream.next_owned().is_none());
+        assert!(stream.buf.capacity() < 256);
+    }
+
+    struct Failing<'a> {
+        data: &'a [u8],
+    }
+
+    impl<'a> Read for Failing<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.data.is_empty() {
+                Err(io::Error::new(io::ErrorKind::Other, "badger"))
+            } else {
+                self.data.read(buf)
+            }
+        }
+    }
+
+    #[test]
+    fn io_error() {
+        let stream = ReaderStream::new(Failing { data: b"[1] [2" });
+        let res: Vec<_> = stream.collect();
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[0].1, Ok(OwnedValue::Array(vec![1.into()])));
+        assert_eq!(
+            res[1],
+            (
+                6,
+                Err(Error::generic(ErrorType::Io(
+                    io::ErrorKind::Other,
+                    "badger".to_string()
+                )))
+            )
+        );
+    }
+
+    #[test]
+    fn from_reader() {
+        let input = "\n{\"a\": [1, \"b\"]} \n";
+        for chunk_size in &[1, 3, 64] {
+            let stream = ReaderStream::new(input.as_bytes()).chunk_size(*chunk_size);
+            let v = stream.single(ReaderStream::next_owned);
+            assert_eq!(v.ok(), value("{\"a\": [1, \"b\"]}"));
+        }
+        let err = |input: &[u8]| {
+            to_owned_value_from_reader(input)
+                .map_err(|e| e.error_type().clone())
+                .err()
+        };
+        assert_eq!(err(b" \n"), Some(ErrorType::EOF));
+        assert_eq!(err(b"[1] [2]"), Some(ErrorType::TrailingCharacters));
+        assert_eq!(err(b"[1]]"), Some(ErrorType::TrailingCharacters));
+        assert!(err(b"[1, 2").is_some());
+        let e = to_owned_value_from_reader(Failing { data: b"[1] " }).expect_err("failed");
+        assert_eq!(
+            e.error_type(),
+            &ErrorType::Io(io::ErrorKind::Other, "badger".to_string())
+        );
+    }
+
+    #[cfg(feature = "serde_impl")]
+    #[test]
+    fn deserialize() {
+        use serde::Deserialize;
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Line {
+            level: String,
+            code: u32,
+        }
+        let input =
+            &b"{\"level\": \"info\", \"code\": 1}\n{\"level\": \"warn\\n\", \"code\": 2}\n"[..];
+        let mut stream = ReaderStream::new(input).line_delimited().chunk_size(8);
+        let mut lines = Vec::new();
+        while let Some((_, line)) = stream.next_deserialize::<Line>() {
+            lines.push(line.expect("valid line"));
+        }
+        assert_eq!(
+            lines,
+            vec![
+                Line {
+                    level: "info".to_string(),
+                    code: 1
+                },
+                Line {
+                    level: "warn\n".to_string(),
+                    code: 2
+                }
+            ]
+        );
+    }
+}
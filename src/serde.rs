@@ -7,14 +7,25 @@
 /// directly to structs this is th4 place to go.
 ///
 mod de;
+mod key;
+#[cfg(feature = "arbitrary_precision")]
+mod number;
+mod se;
 mod value;
+#[cfg(feature = "arbitrary_precision")]
+pub use self::number::Number;
+pub use self::se::{
+    to_string, to_string_pretty, to_vec, to_vec_pretty, to_writer, to_writer_pretty,
+};
 pub use self::value::*;
-use crate::numberparse::Number;
+use crate::numberparse::Number as ParsedNumber;
 use crate::{stry, Deserializer, Error, ErrorType, Result};
 use crate::{BorrowedValue, OwnedValue};
+use serde_ext::de::DeserializeOwned;
 use serde_ext::Deserialize;
 use std::convert::{TryFrom, TryInto};
 use std::fmt;
+use std::io::Read;
 
 type ConvertResult<T> = std::result::Result<T, SerdeConversionError>;
 
@@ -23,7 +34,7 @@ type ConvertResult<T> = std::result::Result<T, SerdeConversionError>;
 pub enum SerdeConversionError {
     /// Serde can not reflect NAN or Infinity
     NanOrInfinity,
-    /// A integer was to large, simd-json uses i64 for all integers
+    /// A integer was to large to be represented as either a i64 or a u64
     IntegerTooLarge,
     /// Something horrible went wrong, please open a ticket at <https://simd-json.rs>
     Oops,
@@ -33,7 +44,7 @@ impl std::fmt::Display for SerdeConversionError {
         use SerdeConversionError::*;
         match self {
             NanOrInfinity => write!(f, "JSON can not represent NAN or Infinity values"),
-            IntegerTooLarge => write!(f, "Integer value is too large to fit in a i64"),
+            IntegerTooLarge => write!(f, "Integer value is too large to fit in a i64 or u64"),
             Oops => write!(
                 f,
                 "Unreachable code is reachable, oops - please open a bug with simdjson-rs"
@@ -53,7 +64,19 @@ where
 {
     let mut deserializer = stry!(Deserializer::from_slice(s));
 
-    T::deserialize(&mut deserializer)
+    T::deserialize(&mut deserializer).map_err(|e| deserializer.locate(e))
+}
+/// parses a byte slice using a serde deserializer without modifying it.
+/// Strings can only be borrowed if they don't contain escapes, use
+/// `Cow<str>` or owned types for fields that might.
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+pub fn from_slice_ref<'a, T>(s: &'a [u8]) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    let mut deserializer = stry!(Deserializer::from_slice_ref(s));
+
+    T::deserialize(&mut deserializer).map_err(|e| deserializer.locate(e))
 }
 /// parses a str  using a serde deserializer.
 /// note that the slice will be rewritten in the process and
@@ -65,10 +88,29 @@ where
 {
     let mut deserializer = stry!(Deserializer::from_slice(unsafe { s.as_bytes_mut() }));
 
-    T::deserialize(&mut deserializer)
+    T::deserialize(&mut deserializer).map_err(|e| deserializer.locate(e))
+}
+/// parses the only document in a reader using a serde deserializer, see
+/// `to_owned_value_from_reader`.
+///
+/// ```
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Line {
+///     code: u32,
+/// }
+///
+/// let line: Line = simd_json::serde::from_reader(&b"{\"code\": 200}"[..]).unwrap();
+/// assert_eq!(line.code, 200);
+/// ```
+pub fn from_reader<R, T>(reader: R) -> Result<T>
+where
+    R: Read,
+    T: DeserializeOwned,
+{
+    crate::ReaderStream::new(reader).single(crate::ReaderStream::next_deserialize)
 }
-
-impl std::error::Error for Error {}
 
 impl serde::de::Error for Error {
     fn custom<T: fmt::Display>(msg: T) -> Self {
@@ -90,7 +132,7 @@ impl<'de> Deserializer<'de> {
             self.idx += 1;
             if let Some(idx) = self.structural_indexes.get(self.idx) {
                 self.iidx = *idx as usize;
-                let r = *self.input.get_unchecked(self.iidx);
+                let r = *self.input.as_slice().get_unchecked(self.iidx);
                 Ok(r)
             } else {
                 Err(self.error(ErrorType::Syntax))
@@ -101,7 +143,7 @@ impl<'de> Deserializer<'de> {
     #[cfg_attr(not(feature = "no-inline"), inline(always))]
     fn peek(&self) -> Result<u8> {
         if let Some(idx) = self.structural_indexes.get(self.idx + 1) {
-            unsafe { Ok(*self.input.get_unchecked(*idx as usize)) }
+            unsafe { Ok(*self.input.as_slice().get_unchecked(*idx as usize)) }
         } else {
             Err(self.error(ErrorType::UnexpectedEnd))
         }
@@ -111,11 +153,11 @@ impl<'de> Deserializer<'de> {
     fn parse_signed(&mut self) -> Result<i64> {
         match self.next_() {
             b'-' => match stry!(self.parse_number(true)) {
-                Number::I64(n) => Ok(n),
+                ParsedNumber::I64(n) => Ok(n),
                 _ => Err(self.error(ErrorType::ExpectedSigned)),
             },
             b'0'..=b'9' => match stry!(self.parse_number(false)) {
-                Number::I64(n) => Ok(n),
+                ParsedNumber::I64(n) => Ok(n),
                 _ => Err(self.error(ErrorType::ExpectedSigned)),
             },
             _ => Err(self.error(ErrorType::ExpectedSigned)),
@@ -127,7 +169,8 @@ impl<'de> Deserializer<'de> {
         #[allow(clippy::cast_sign_loss)]
         match self.next_() {
             b'0'..=b'9' => match stry!(self.parse_number(false)) {
-                Number::I64(n) => Ok(n as u64),
+                ParsedNumber::I64(n) => Ok(n as u64),
+                ParsedNumber::U64(n) => Ok(n),
                 _ => Err(self.error(ErrorType::ExpectedUnsigned)),
             },
             _ => Err(self.error(ErrorType::ExpectedUnsigned)),
@@ -138,18 +181,31 @@ impl<'de> Deserializer<'de> {
         #[allow(clippy::cast_possible_wrap, clippy::cast_precision_loss)]
         match self.next_() {
             b'-' => match stry!(self.parse_number(true)) {
-                Number::F64(n) => Ok(n),
-                Number::I64(n) => Ok(n as f64),
+                ParsedNumber::F64(n) => Ok(n),
+                ParsedNumber::I64(n) => Ok(n as f64),
+                ParsedNumber::U64(n) => Ok(n as f64),
+                #[cfg(feature = "arbitrary_precision")]
+                ParsedNumber::Raw(n) => n.parse().map_err(|_| self.error(ErrorType::ExpectedFloat)),
             },
             b'0'..=b'9' => match stry!(self.parse_number(false)) {
-                Number::F64(n) => Ok(n),
-                Number::I64(n) => Ok(n as f64),
+                ParsedNumber::F64(n) => Ok(n),
+                ParsedNumber::I64(n) => Ok(n as f64),
+                ParsedNumber::U64(n) => Ok(n as f64),
+                #[cfg(feature = "arbitrary_precision")]
+                ParsedNumber::Raw(n) => n.parse().map_err(|_| self.error(ErrorType::ExpectedFloat)),
             },
             _ => Err(self.error(ErrorType::ExpectedFloat)),
         }
     }
 }
 
+/// Reads a `serde_json` number the way the parser reads number literals
+#[cfg(feature = "arbitrary_precision")]
+fn number_from_literal(n: &serde_json::Number) -> ConvertResult<OwnedValue> {
+    let mut literal = n.to_string().into_bytes();
+    crate::to_owned_value(&mut literal).map_err(|_| SerdeConversionError::Oops)
+}
+
 impl TryFrom<serde_json::Value> for OwnedValue {
     type Error = SerdeConversionError;
     fn try_from(item: serde_json::Value) -> ConvertResult<Self> {
@@ -157,15 +213,15 @@ impl TryFrom<serde_json::Value> for OwnedValue {
         Ok(match item {
             Value::Null => Self::Null,
             Value::Bool(b) => Self::Bool(b),
+            // Literals that don't fit without loss are kept as they are
+            #[cfg(feature = "arbitrary_precision")]
+            Value::Number(b) => number_from_literal(&b)?,
+            #[cfg(not(feature = "arbitrary_precision"))]
             Value::Number(b) => {
                 if let Some(n) = b.as_i64() {
                     Self::I64(n)
                 } else if let Some(n) = b.as_u64() {
-                    if n > i64::max_value() as u64 {
-                        return Err(SerdeConversionError::IntegerTooLarge);
-                    }
-                    #[allow(clippy::cast_possible_wrap)]
-                    Self::I64(n as i64)
+                    Self::U64(n)
                 } else if let Some(n) = b.as_f64() {
                     Self::F64(n)
                 } else {
@@ -195,6 +251,11 @@ impl TryInto<serde_json::Value> for OwnedValue {
             Self::Null => Value::Null,
             Self::Bool(b) => Value::Bool(b),
             Self::I64(n) => Value::Number(n.into()),
+            Self::U64(n) => Value::Number(n.into()),
+            #[cfg(feature = "arbitrary_precision")]
+            Self::Number(n) => {
+                Value::Number(serde_json::from_str(&n).map_err(|_| SerdeConversionError::Oops)?)
+            }
             Self::F64(n) => {
                 if let Some(n) = serde_json::Number::from_f64(n) {
                     Value::Number(n)
@@ -224,15 +285,15 @@ impl<'value> TryFrom<serde_json::Value> for BorrowedValue<'value> {
         Ok(match item {
             Value::Null => BorrowedValue::Null,
             Value::Bool(b) => BorrowedValue::Bool(b),
+            // Literals that don't fit without loss are kept as they are
+            #[cfg(feature = "arbitrary_precision")]
+            Value::Number(b) => number_from_literal(&b)?.into(),
+            #[cfg(not(feature = "arbitrary_precision"))]
             Value::Number(b) => {
                 if let Some(n) = b.as_i64() {
                     BorrowedValue::I64(n)
                 } else if let Some(n) = b.as_u64() {
-                    if n > i64::max_value() as u64 {
-                        return Err(SerdeConversionError::IntegerTooLarge);
-                    }
-                    #[allow(clippy::cast_possible_wrap)]
-                    BorrowedValue::I64(n as i64)
+                    BorrowedValue::U64(n)
                 } else if let Some(n) = b.as_f64() {
                     BorrowedValue::F64(n)
                 } else {
@@ -262,6 +323,11 @@ impl<'value> TryInto<serde_json::Value> for BorrowedValue<'value> {
             BorrowedValue::Null => Value::Null,
             BorrowedValue::Bool(b) => Value::Bool(b),
             BorrowedValue::I64(n) => Value::Number(n.into()),
+            BorrowedValue::U64(n) => Value::Number(n.into()),
+            #[cfg(feature = "arbitrary_precision")]
+            BorrowedValue::Number(n) => {
+                Value::Number(serde_json::from_str(&n).map_err(|_| SerdeConversionError::Oops)?)
+            }
             BorrowedValue::F64(n) => {
                 if let Some(n) = serde_json::Number::from_f64(n) {
                     Value::Number(n)
@@ -296,6 +362,7 @@ mod test {
             "int": 42,
             "float": 7.2,
             "neg-int": -23,
+            "u64": 18_446_744_073_709_551_615_u64,
             "string": "string",
             "bool": true,
             "null": null,
@@ -308,6 +375,7 @@ mod test {
             "int": 42,
             "float": 7.2,
             "neg-int": -23,
+            "u64": 18_446_744_073_709_551_615_u64,
             "string": "string",
             "bool": true,
             "null": null,
@@ -327,6 +395,7 @@ mod test {
             "int": 42,
             "float": 7.2,
             "neg-int": -23,
+            "u64": 18_446_744_073_709_551_615_u64,
             "string": "string",
             "bool": true,
             "null": null,
@@ -340,6 +409,7 @@ mod test {
             "int": 42,
             "float": 7.2,
             "neg-int": -23,
+            "u64": 18_446_744_073_709_551_615_u64,
             "string": "string",
             "bool": true,
             "null": null,
@@ -352,4 +422,28 @@ mod test {
         let v_c: BorrowedValue = s.try_into().unwrap();
         assert_eq!(v, v_c);
     }
+
+    #[test]
+    #[cfg(feature = "arbitrary_precision")]
+    fn convert_arbitrary_precision() {
+        for n in &["1e400", "18446744073709551616", "-0.1000000000000000000001"] {
+            let mut d = n.as_bytes().to_vec();
+            let v = crate::to_owned_value(&mut d).unwrap();
+            let s: SerdeValue = v.clone().try_into().unwrap();
+            assert_eq!(s, serde_json::from_str::<SerdeValue>(n).unwrap());
+            // and back without going through a float
+            let v_c: OwnedValue = s.clone().try_into().unwrap();
+            let s_c: SerdeValue = v_c.try_into().unwrap();
+            assert_eq!(s, s_c);
+
+            let mut d = n.as_bytes().to_vec();
+            let v = crate::to_borrowed_value(&mut d).unwrap();
+            let s: SerdeValue = v.clone().try_into().unwrap();
+            assert_eq!(s, serde_json::from_str::<SerdeValue>(n).unwrap());
+            // and back without going through a float
+            let v_c: BorrowedValue = s.clone().try_into().unwrap();
+            let s_c: SerdeValue = v_c.try_into().unwrap();
+            assert_eq!(s, s_c);
+        }
+    }
 }
@@ -3,6 +3,10 @@ use crate::*;
 use serde_ext::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
 use serde_ext::forward_to_deserialize_any;
 
+use crate::serde::key::MapKeyDeserializer;
+#[cfg(feature = "arbitrary_precision")]
+use crate::serde::number::NumberDeserializer;
+
 impl<'a, 'de> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     type Error = Error;
 
@@ -18,7 +22,10 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut Deserializer<'de> {
             b'"' => {
                 // We don't do the short string optimisation as serde requires
                 // additional checks
-                visitor.visit_borrowed_str(stry!(self.parse_str_()))
+                match stry!(self.parse_str_()) {
+                    ParsedStr::Borrowed(s) => visitor.visit_borrowed_str(s),
+                    ParsedStr::Copied(s) => visitor.visit_str(s),
+                }
             }
             b'n' => visitor.visit_unit(),
             b't' => visitor.visit_bool(true),
@@ -26,10 +33,16 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut Deserializer<'de> {
             b'-' => match stry!(self.parse_number(true)) {
                 Number::F64(n) => visitor.visit_f64(n),
                 Number::I64(n) => visitor.visit_i64(n),
+                Number::U64(n) => visitor.visit_u64(n),
+                #[cfg(feature = "arbitrary_precision")]
+                Number::Raw(n) => visitor.visit_map(NumberDeserializer::new(n)),
             },
             b'0'..=b'9' => match stry!(self.parse_number(false)) {
                 Number::F64(n) => visitor.visit_f64(n),
                 Number::I64(n) => visitor.visit_i64(n),
+                Number::U64(n) => visitor.visit_u64(n),
+                #[cfg(feature = "arbitrary_precision")]
+                Number::Raw(n) => visitor.visit_map(NumberDeserializer::new(n)),
             },
             b'[' => visitor.visit_seq(CommaSeparated::new(&mut self)),
             b'{' => visitor.visit_map(CommaSeparated::new(&mut self)),
@@ -73,12 +86,10 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         if stry!(self.next()) != b'"' {
             return Err(self.error(ErrorType::ExpectedString));
         }
-        if let Some(next) = self.structural_indexes.get(self.idx + 1) {
-            if *next as usize - self.iidx < 32 {
-                return visitor.visit_borrowed_str(stry!(self.parse_str_()));
-            }
+        match stry!(self.parse_str_()) {
+            ParsedStr::Borrowed(s) => visitor.visit_borrowed_str(s),
+            ParsedStr::Copied(s) => visitor.visit_str(s),
         }
-        visitor.visit_borrowed_str(stry!(self.parse_str_()))
     }
 
     #[cfg_attr(not(feature = "no-inline"), inline)]
@@ -89,12 +100,7 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         if stry!(self.next()) != b'"' {
             return Err(self.error(ErrorType::ExpectedString));
         }
-        if let Some(next) = self.structural_indexes.get(self.idx + 1) {
-            if *next as usize - self.iidx < 32 {
-                return visitor.visit_str(stry!(self.parse_str_()));
-            }
-        }
-        visitor.visit_str(stry!(self.parse_str_()))
+        visitor.visit_str(stry!(self.parse_str_()).as_str())
     }
 
     // The `parse_signed` function is generic over the integer type `T` so here
@@ -266,13 +272,13 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     fn deserialize_tuple_struct<V>(
         self,
         _name: &'static str,
-        _len: usize,
+        len: usize,
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        self.deserialize_tuple(len, visitor)
     }
 
     // Unit struct means a named value containing no data.
@@ -320,10 +326,51 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         self.deserialize_map(visitor)
     }
 
+    // Enums are represented the same way serde_json does it, unit variants
+    // are a string and all other variants an object with a single key.
+    #[cfg_attr(not(feature = "no-inline"), inline)]
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match stry!(self.peek()) {
+            b'"' => visitor.visit_enum(UnitVariantAccess { de: self }),
+            b'{' => {
+                self.skip();
+                let r = stry!(visitor.visit_enum(VariantAccess { de: &mut *self }));
+                if stry!(self.next()) == b'}' {
+                    Ok(r)
+                } else {
+                    Err(self.error(ErrorType::ExpectedMapEnd))
+                }
+            }
+            _ => {
+                self.skip();
+                Err(self.error(ErrorType::ExpectedEnum))
+            }
+        }
+    }
+
+    // Values nobody is interested in are skipped without building anything
+    // from them, only the grammar of their numbers and strings is checked.
+    #[cfg_attr(not(feature = "no-inline"), inline)]
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        stry!(self.skip_value());
+        visitor.visit_unit()
+    }
+
     forward_to_deserialize_any! {
             i128 u128 char
-            bytes byte_buf enum
-            identifier ignored_any
+            bytes byte_buf
+            identifier
     }
 }
 
@@ -394,7 +441,10 @@ impl<'de, 'a> MapAccess<'de> for CommaSeparated<'a, 'de> {
         } else {
             self.len -= 1;
             self.first = false;
-            seed.deserialize(&mut *self.de).map(Some)
+            // Keys are always strings, stage2 made sure of that
+            self.de.skip();
+            let key = stry!(self.de.parse_str_()).into_cow();
+            seed.deserialize(MapKeyDeserializer::new(key)).map(Some)
         }
     }
 
@@ -405,10 +455,11 @@ impl<'de, 'a> MapAccess<'de> for CommaSeparated<'a, 'de> {
     {
         // Skip the ':'
         self.de.skip();
-        // read the value
-        let r = seed.deserialize(&mut *self.de);
+        // read the value, on errors the deserializer stays on it so the
+        // error is located there
+        let r = stry!(seed.deserialize(&mut *self.de));
         self.de.skip();
-        r
+        Ok(r)
     }
 
     #[cfg_attr(not(feature = "no-inline"), inline)]
@@ -416,3 +467,370 @@ impl<'de, 'a> MapAccess<'de> for CommaSeparated<'a, 'de> {
         Some(self.len)
     }
 }
+
+// `EnumAccess` for variants with content, the deserializer is positioned
+// after the `{` of the object wrapping the variant.
+struct VariantAccess<'a, 'de: 'a> {
+    de: &'a mut Deserializer<'de>,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for VariantAccess<'a, 'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    #[cfg_attr(not(feature = "no-inline"), inline)]
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = stry!(seed.deserialize(&mut *self.de));
+        // Skip the ':'
+        self.de.skip();
+        Ok((variant, self))
+    }
+}
+
+impl<'de, 'a> de::VariantAccess<'de> for VariantAccess<'a, 'de> {
+    type Error = Error;
+
+    #[cfg_attr(not(feature = "no-inline"), inline)]
+    fn unit_variant(self) -> Result<()> {
+        de::Deserialize::deserialize(self.de)
+    }
+
+    #[cfg_attr(not(feature = "no-inline"), inline)]
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(self.de)
+    }
+
+    #[cfg_attr(not(feature = "no-inline"), inline)]
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_tuple(self.de, len, visitor)
+    }
+
+    #[cfg_attr(not(feature = "no-inline"), inline)]
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_struct(self.de, "", fields, visitor)
+    }
+}
+
+// `EnumAccess` for variants written as a plain string, those can only be
+// unit variants.
+struct UnitVariantAccess<'a, 'de: 'a> {
+    de: &'a mut Deserializer<'de>,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for UnitVariantAccess<'a, 'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    #[cfg_attr(not(feature = "no-inline"), inline)]
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = stry!(seed.deserialize(&mut *self.de));
+        Ok((variant, self))
+    }
+}
+
+impl<'de, 'a> de::VariantAccess<'de> for UnitVariantAccess<'a, 'de> {
+    type Error = Error;
+
+    #[cfg_attr(not(feature = "no-inline"), inline)]
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, _seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        Err(de::Error::invalid_type(
+            de::Unexpected::UnitVariant,
+            &"newtype variant",
+        ))
+    }
+
+    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Err(de::Error::invalid_type(
+            de::Unexpected::UnitVariant,
+            &"tuple variant",
+        ))
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], _visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Err(de::Error::invalid_type(
+            de::Unexpected::UnitVariant,
+            &"struct variant",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::serde::key::MapKeyDeserializer;
+    use crate::serde::{from_slice, to_vec};
+    use crate::ErrorType;
+    use halfbrown::HashMap;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+    use std::fmt::Debug;
+
+    fn roundtrip<T>(v: &T)
+    where
+        T: Serialize + for<'de> Deserialize<'de> + PartialEq + Debug,
+    {
+        let mut d = to_vec(v).expect("to_vec");
+        assert_eq!(d, serde_json::to_vec(v).expect("serde_json"));
+        let de: T = from_slice(&mut d).expect("from_slice");
+        assert_eq!(v, &de);
+    }
+
+    #[derive(Deserialize, Serialize, PartialEq, Debug)]
+    enum External {
+        Unit,
+        Newtype(String),
+        Tuple(i32, Vec<u8>),
+        Struct { x: i32, y: Option<bool> },
+        Empty {},
+    }
+
+    #[derive(Deserialize, Serialize, PartialEq, Debug)]
+    #[serde(tag = "type")]
+    enum Internal {
+        Unit,
+        Newtype(Inner),
+        Struct { x: i32, y: Option<bool> },
+    }
+
+    #[derive(Deserialize, Serialize, PartialEq, Debug)]
+    #[serde(tag = "t", content = "c")]
+    enum Adjacent {
+        Unit,
+        Newtype(String),
+        Tuple(i32, Vec<u8>),
+        Struct { x: i32, y: Option<bool> },
+    }
+
+    #[derive(Deserialize, Serialize, PartialEq, Debug)]
+    #[serde(untagged)]
+    enum Untagged {
+        Unit,
+        Newtype(String),
+        Tuple(i32, Vec<u8>),
+        Struct { x: i32, y: Option<bool> },
+    }
+
+    #[derive(Deserialize, Serialize, PartialEq, Debug)]
+    struct Inner {
+        snot: String,
+    }
+
+    #[derive(Deserialize, Serialize, PartialEq, Debug)]
+    struct Wrapper<T> {
+        before: u8,
+        events: Vec<T>,
+        after: u8,
+    }
+
+    fn wrap<T>(events: Vec<T>) -> Wrapper<T> {
+        Wrapper {
+            before: 1,
+            events,
+            after: 2,
+        }
+    }
+
+    #[test]
+    fn externally_tagged() {
+        roundtrip(&wrap(vec![
+            External::Unit,
+            External::Newtype("badger".into()),
+            External::Tuple(-1, vec![1, 2]),
+            External::Struct { x: 7, y: None },
+            External::Empty {},
+            External::Unit,
+        ]));
+        let mut d = br#"{"Unit": null}"#.to_vec();
+        assert_eq!(
+            from_slice::<External>(&mut d).expect("unit"),
+            External::Unit
+        );
+    }
+
+    #[test]
+    fn internally_tagged() {
+        roundtrip(&wrap(vec![
+            Internal::Unit,
+            Internal::Newtype(Inner {
+                snot: "badger".into(),
+            }),
+            Internal::Struct {
+                x: 7,
+                y: Some(true),
+            },
+        ]));
+    }
+
+    #[test]
+    fn adjacently_tagged() {
+        roundtrip(&wrap(vec![
+            Adjacent::Unit,
+            Adjacent::Newtype("badger".into()),
+            Adjacent::Tuple(-1, vec![1, 2]),
+            Adjacent::Struct { x: 7, y: None },
+        ]));
+    }
+
+    #[test]
+    fn untagged() {
+        roundtrip(&wrap(vec![
+            Untagged::Unit,
+            Untagged::Newtype("badger".into()),
+            Untagged::Tuple(-1, vec![1, 2]),
+            Untagged::Struct { x: 7, y: None },
+        ]));
+    }
+
+    #[derive(Deserialize, Serialize, PartialEq, Debug)]
+    struct Point(i32, i32);
+
+    #[test]
+    fn tuple_struct() {
+        roundtrip(&wrap(vec![Point(1, 2), Point(3, 4)]));
+        roundtrip(&wrap(vec![External::Newtype("snot".into())]));
+    }
+
+    #[derive(Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+    enum Key {
+        Snot,
+        Badger,
+    }
+
+    #[derive(Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+    struct Id(u16);
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Keys {
+        ints: HashMap<u64, u8>,
+        signed: BTreeMap<i8, u8>,
+        bools: HashMap<bool, u8>,
+        chars: BTreeMap<char, u8>,
+        enums: BTreeMap<Key, u8>,
+        newtypes: HashMap<Id, u8>,
+    }
+
+    #[test]
+    fn map_keys() {
+        let d = br#"{
+            "ints": {"42": 1, "18446744073709551615": 2},
+            "signed": {"-1": 3},
+            "bools": {"true": 4, "false": 5},
+            "chars": {"x": 6},
+            "enums": {"Snot": 7, "Badger": 8},
+            "newtypes": {"23": 9}
+        }"#;
+        let keys: Keys = from_slice(&mut d.to_vec()).expect("from_slice");
+        assert_eq!(keys.ints[&u64::max_value()], 2);
+        assert_eq!(keys.signed[&-1], 3);
+        assert_eq!(keys.bools[&false], 5);
+        assert_eq!(keys.chars[&'x'], 6);
+        assert_eq!(keys.enums[&Key::Badger], 8);
+        assert_eq!(keys.newtypes[&Id(23)], 9);
+
+        let mut b = d.to_vec();
+        let v = crate::to_borrowed_value(&mut b).expect("to_borrowed_value");
+        assert_eq!(Keys::deserialize(v).expect("borrowed"), keys);
+        let v = crate::to_owned_value(&mut d.to_vec()).expect("to_owned_value");
+        assert_eq!(Keys::deserialize(v).expect("owned"), keys);
+
+        let mut d = br#"{"snot": 1}"#.to_vec();
+        assert!(from_slice::<HashMap<u8, u8>>(&mut d).is_err());
+        // keys have to follow the JSON number grammar
+        for k in &["+1", " 1", "1 ", "01", "1.", "0x1"] {
+            let mut d = format!(r#"{{"{}": 2}}"#, k).into_bytes();
+            assert!(from_slice::<HashMap<i32, i32>>(&mut d).is_err(), "{}", k);
+        }
+        for k in &["inf", "-inf", "NaN", "+1.5", ".5"] {
+            let key = MapKeyDeserializer::new((*k).into());
+            assert!(f64::deserialize(key).is_err(), "{}", k);
+        }
+        let key = MapKeyDeserializer::new("-1.5e3".into());
+        assert_eq!(f64::deserialize(key).expect("f64"), -1500.0);
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Few<'a> {
+        id: u64,
+        name: &'a str,
+    }
+
+    #[test]
+    fn ignored_fields() {
+        let mut d = br#"{
+            "meta": {"tags": ["a", "b\\n"], "nested": [[{"x": null}], []]},
+            "id": 42,
+            "blob": "\\u0041\\n",
+            "name": "snot",
+            "more": [1, 2.5, true, {}]
+        }"#
+        .to_vec();
+        let few: Few = from_slice(&mut d).expect("from_slice");
+        assert_eq!(
+            few,
+            Few {
+                id: 42,
+                name: "snot"
+            }
+        );
+        let mut d =
+            br#"[{"id": 1, "name": "a", "x": [{}]}, {"x": {}, "name": "b", "id": 2}]"#.to_vec();
+        let few: Vec<Few> = from_slice(&mut d).expect("from_slice");
+        assert_eq!(few[1], Few { id: 2, name: "b" });
+        let mut d = br#"[1, [2, 3], {"a": 4}, 5]"#.to_vec();
+        let v: (u8, serde::de::IgnoredAny, serde::de::IgnoredAny, u8) =
+            from_slice(&mut d).expect("from_slice");
+        assert_eq!((v.0, v.3), (1, 5));
+
+        // ignored values are rejected like any other invalid value
+        for d in &[
+            r#"{"id": 1, "name": "a", "x": 01}"#,
+            r#"{"id": 1, "name": "a", "x": -}"#,
+            r#"{"id": 1, "name": "a", "x": "\q"}"#,
+            r#"{"id": 1, "name": "a", "x": [1, {"y": 2.}]}"#,
+        ] {
+            let mut d = d.as_bytes().to_vec();
+            assert!(from_slice::<Few>(&mut d).is_err());
+        }
+    }
+
+    #[test]
+    fn bad_enums() {
+        let mut d = br#"{"Unit": null, "Newtype": "snot"}"#.to_vec();
+        let e = from_slice::<External>(&mut d).expect_err("two variants");
+        assert_eq!(e.error_type(), &ErrorType::ExpectedMapEnd);
+        let mut d = br#"["Unit"]"#.to_vec();
+        let e = from_slice::<External>(&mut d).expect_err("array");
+        assert_eq!(e.error_type(), &ErrorType::ExpectedEnum);
+        let mut d = br#""Newtype""#.to_vec();
+        assert!(from_slice::<External>(&mut d).is_err());
+        let mut d = br#"{"Badger": 1}"#.to_vec();
+        assert!(from_slice::<External>(&mut d).is_err());
+    }
+}
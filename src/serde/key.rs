@@ -0,0 +1,208 @@
+use crate::numberparse::number_len;
+use crate::Error;
+use serde_ext::de::{self, Unexpected, Visitor};
+use serde_ext::forward_to_deserialize_any;
+use std::borrow::Cow;
+
+/// Deserializes object keys, JSON keys are always strings so integers,
+/// floats and booleans are parsed from their quoted representation.
+pub(crate) struct MapKeyDeserializer<'de> {
+    key: Cow<'de, str>,
+}
+
+impl<'de> MapKeyDeserializer<'de> {
+    pub(crate) fn new(key: Cow<'de, str>) -> Self {
+        MapKeyDeserializer { key }
+    }
+}
+
+// Numbers are only parsed from keys that follow the JSON grammar,
+// `str::parse` would also take `+1`, `inf` or `NaN`.
+fn is_number(key: &str) -> bool {
+    number_len(key.as_bytes()) == Some(key.len())
+}
+
+macro_rules! deserialize_parsed_key {
+    ($method:ident => $visit:ident) => {
+        deserialize_parsed_key!($method => $visit, |_| true);
+    };
+    ($method:ident => $visit:ident, $valid:expr) => {
+        fn $method<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            let parsed = if $valid(&self.key) {
+                self.key.parse().ok()
+            } else {
+                None
+            };
+            match (parsed, self.key) {
+                (Some(v), _) => visitor.$visit(v),
+                (None, Cow::Borrowed(s)) => visitor.visit_borrowed_str(s),
+                (None, Cow::Owned(s)) => visitor.visit_string(s),
+            }
+        }
+    };
+}
+
+impl<'de> de::Deserializer<'de> for MapKeyDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        BorrowedCowStrDeserializer::new(self.key).deserialize_any(visitor)
+    }
+
+    deserialize_parsed_key!(deserialize_bool => visit_bool);
+    deserialize_parsed_key!(deserialize_i8 => visit_i8, is_number);
+    deserialize_parsed_key!(deserialize_i16 => visit_i16, is_number);
+    deserialize_parsed_key!(deserialize_i32 => visit_i32, is_number);
+    deserialize_parsed_key!(deserialize_i64 => visit_i64, is_number);
+    deserialize_parsed_key!(deserialize_i128 => visit_i128, is_number);
+    deserialize_parsed_key!(deserialize_u8 => visit_u8, is_number);
+    deserialize_parsed_key!(deserialize_u16 => visit_u16, is_number);
+    deserialize_parsed_key!(deserialize_u32 => visit_u32, is_number);
+    deserialize_parsed_key!(deserialize_u64 => visit_u64, is_number);
+    deserialize_parsed_key!(deserialize_u128 => visit_u128, is_number);
+    deserialize_parsed_key!(deserialize_f32 => visit_f32, is_number);
+    deserialize_parsed_key!(deserialize_f64 => visit_f64, is_number);
+
+    #[inline]
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        // Map keys cannot be null.
+        visitor.visit_some(self)
+    }
+
+    #[inline]
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        BorrowedCowStrDeserializer::new(self.key).deserialize_enum(name, variants, visitor)
+    }
+
+    forward_to_deserialize_any! {
+        char str string bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct BorrowedCowStrDeserializer<'de> {
+    value: Cow<'de, str>,
+}
+
+impl<'de> BorrowedCowStrDeserializer<'de> {
+    fn new(value: Cow<'de, str>) -> Self {
+        BorrowedCowStrDeserializer { value }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for BorrowedCowStrDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value {
+            Cow::Borrowed(string) => visitor.visit_borrowed_str(string),
+            Cow::Owned(string) => visitor.visit_string(string),
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_enum(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> de::EnumAccess<'de> for BorrowedCowStrDeserializer<'de> {
+    type Error = Error;
+    type Variant = UnitOnly;
+
+    fn variant_seed<T>(self, seed: T) -> Result<(T::Value, Self::Variant), Self::Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        let value = seed.deserialize(self)?;
+        Ok((value, UnitOnly))
+    }
+}
+
+struct UnitOnly;
+
+impl<'de> de::VariantAccess<'de> for UnitOnly {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, _seed: T) -> Result<T::Value, Self::Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        Err(de::Error::invalid_type(
+            Unexpected::UnitVariant,
+            &"newtype variant",
+        ))
+    }
+
+    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(de::Error::invalid_type(
+            Unexpected::UnitVariant,
+            &"tuple variant",
+        ))
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(de::Error::invalid_type(
+            Unexpected::UnitVariant,
+            &"struct variant",
+        ))
+    }
+}
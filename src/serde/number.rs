@@ -0,0 +1,230 @@
+use crate::Error;
+use serde_ext::de::value::BorrowedStrDeserializer;
+use serde_ext::de::{self, Deserialize, DeserializeSeed, IntoDeserializer, MapAccess, Visitor};
+use serde_ext::ser::{Serialize, SerializeStruct, Serializer};
+use std::fmt;
+
+/// Name of the struct arbitrary precision numbers are passed through serde
+/// as, our serializers and deserializers look out for it to keep the literal
+/// of the number intact.
+pub(crate) const TOKEN: &str = "$simd_json::private::Number";
+
+/// A JSON number kept as the literal it was parsed from. Deserializing into
+/// a `Number` preserves numbers that don't fit into a `i64`, `u64` or `f64`
+/// without loss of precision.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Number {
+    n: String,
+}
+
+impl Number {
+    /// Returns the literal of the number
+    pub fn as_str(&self) -> &str {
+        &self.n
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.n)
+    }
+}
+
+impl From<Number> for String {
+    fn from(n: Number) -> Self {
+        n.n
+    }
+}
+
+/// Serializes a number literal as the private number struct
+pub(crate) fn serialize_number<S>(n: &str, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let mut s = serializer.serialize_struct(TOKEN, 1)?;
+    s.serialize_field(TOKEN, n)?;
+    s.end()
+}
+
+impl Serialize for Number {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serialize_number(&self.n, serializer)
+    }
+}
+
+struct NumberVisitor;
+
+impl<'de> Visitor<'de> for NumberVisitor {
+    type Value = Number;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON number")
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<Number, E> {
+        Ok(Number {
+            n: itoa::Buffer::new().format(value).to_owned(),
+        })
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Number, E> {
+        Ok(Number {
+            n: itoa::Buffer::new().format(value).to_owned(),
+        })
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<Number, E>
+    where
+        E: de::Error,
+    {
+        if value.is_finite() {
+            Ok(Number {
+                n: ryu::Buffer::new().format_finite(value).to_owned(),
+            })
+        } else {
+            Err(de::Error::custom(
+                "JSON can not represent NAN or Infinity values",
+            ))
+        }
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Number, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        match map.next_key::<String>()? {
+            Some(ref k) if k == TOKEN => Ok(Number {
+                n: map.next_value()?,
+            }),
+            _ => Err(de::Error::invalid_type(de::Unexpected::Map, &self)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Number {
+    fn deserialize<D>(deserializer: D) -> Result<Number, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(NumberVisitor)
+    }
+}
+
+/// Hands a number literal to a visitor as the private number struct
+pub(crate) struct NumberDeserializer {
+    n: Option<String>,
+}
+
+impl NumberDeserializer {
+    pub(crate) fn new(n: String) -> Self {
+        Self { n: Some(n) }
+    }
+}
+
+impl<'de> MapAccess<'de> for NumberDeserializer {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        if self.n.is_some() {
+            seed.deserialize(BorrowedStrDeserializer::new(TOKEN))
+                .map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        // Panic because this indicates a bug in the program rather than an
+        // expected failure.
+        let n = self
+            .n
+            .take()
+            .expect("next_value_seed called before next_key_seed");
+        seed.deserialize(n.into_deserializer())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Number;
+    use crate::{to_borrowed_value, to_owned_value, BorrowedValue, OwnedValue};
+    use serde::Deserialize;
+
+    const BIG: &str =
+        r#"{"int":123456789012345678901234567890,"float":0.12345678901234567890123,"small":1.5}"#;
+
+    #[derive(Deserialize, Debug)]
+    struct Prices {
+        int: Number,
+        float: Number,
+        small: Number,
+    }
+
+    #[test]
+    fn deserialize_number() {
+        let mut d = String::from(BIG);
+        let d = unsafe { d.as_bytes_mut() };
+        let p: Prices = crate::serde::from_slice(d).expect("failed to deserialize");
+        assert_eq!(p.int.as_str(), "123456789012345678901234567890");
+        assert_eq!(p.float.as_str(), "0.12345678901234567890123");
+        assert_eq!(p.small.as_str(), "1.5");
+    }
+
+    #[test]
+    fn value_roundtrip() {
+        let mut d = String::from(BIG);
+        let d = unsafe { d.as_bytes_mut() };
+        let v: BorrowedValue = to_borrowed_value(d).expect("failed to parse");
+        assert_eq!(
+            v["int"],
+            BorrowedValue::Number("123456789012345678901234567890".into())
+        );
+        assert_eq!(v["small"], 1.5);
+
+        let o: OwnedValue = crate::serde::to_owned_value(&v).expect("failed to convert");
+        assert_eq!(o, v);
+        let p: Prices = crate::serde::from_owned_value(o).expect("failed to deserialize");
+        assert_eq!(p.float.as_str(), "0.12345678901234567890123");
+        let o: OwnedValue = crate::serde::to_owned_value(&p.int).expect("failed to convert");
+        assert_eq!(
+            o,
+            OwnedValue::Number("123456789012345678901234567890".to_string())
+        );
+        let b: BorrowedValue =
+            crate::serde::from_borrowed_value(v.clone()).expect("failed to deserialize");
+        assert_eq!(b, v);
+    }
+
+    #[test]
+    fn serializer_roundtrip() {
+        let mut d = String::from(BIG);
+        let d = unsafe { d.as_bytes_mut() };
+        let p: Prices = crate::serde::from_slice(d).expect("failed to deserialize");
+        let s = crate::serde::to_string(&p.int).expect("failed to serialize");
+        assert_eq!(s, "123456789012345678901234567890");
+        let v = to_owned_value(d).expect("failed to parse");
+        let mut s = crate::serde::to_vec(&v).expect("failed to serialize");
+        assert_eq!(to_owned_value(&mut s).expect("failed to reparse"), v);
+    }
+
+    #[test]
+    fn generator_roundtrip() {
+        let mut d = String::from(BIG);
+        let d = unsafe { d.as_bytes_mut() };
+        let v = to_owned_value(d).expect("failed to parse");
+        let mut e = v.encode();
+        let e = unsafe { e.as_bytes_mut() };
+        assert_eq!(to_owned_value(e).expect("failed to reparse"), v);
+        assert!(v.encode().contains("123456789012345678901234567890"));
+        assert!(v.encode().contains("0.12345678901234567890123"));
+    }
+}
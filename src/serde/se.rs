@@ -0,0 +1,893 @@
+#[cfg(feature = "arbitrary_precision")]
+use crate::serde::number::TOKEN;
+use crate::value::generator::{
+    BaseGenerator, DumpGenerator, PrettyGenerator, PrettyWriterGenerator, WriterGenerator,
+};
+use crate::{stry, Error, ErrorType, OwnedValue, Result};
+use serde_ext::ser::{self, Serialize};
+use std::io::{self, Write};
+
+type Impossible = ser::Impossible<(), Error>;
+
+macro_rules! iotry {
+    ($e:expr) => {
+        stry!($e.map_err(Error::from))
+    };
+}
+
+/// Serializes a value as JSON into a `Vec<u8>`
+pub fn to_vec<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: Serialize + ?Sized,
+{
+    let mut s = Serializer(DumpGenerator::<OwnedValue>::new());
+    stry!(value.serialize(&mut s));
+    Ok(s.0.consume().into_bytes())
+}
+
+/// Serializes a value as pretty printed JSON into a `Vec<u8>`
+pub fn to_vec_pretty<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: Serialize + ?Sized,
+{
+    let mut s = Serializer(PrettyGenerator::<OwnedValue>::new(2));
+    stry!(value.serialize(&mut s));
+    Ok(s.0.consume().into_bytes())
+}
+
+/// Serializes a value as JSON into a `String`
+pub fn to_string<T>(value: &T) -> Result<String>
+where
+    T: Serialize + ?Sized,
+{
+    let mut s = Serializer(DumpGenerator::<OwnedValue>::new());
+    stry!(value.serialize(&mut s));
+    Ok(s.0.consume())
+}
+
+/// Serializes a value as pretty printed JSON into a `String`
+pub fn to_string_pretty<T>(value: &T) -> Result<String>
+where
+    T: Serialize + ?Sized,
+{
+    let mut s = Serializer(PrettyGenerator::<OwnedValue>::new(2));
+    stry!(value.serialize(&mut s));
+    Ok(s.0.consume())
+}
+
+/// Serializes a value as JSON into a writer
+pub fn to_writer<W, T>(mut writer: W, value: &T) -> Result<()>
+where
+    W: Write,
+    T: Serialize + ?Sized,
+{
+    let mut s = Serializer(WriterGenerator::<W, OwnedValue>::new(&mut writer));
+    value.serialize(&mut s)
+}
+
+/// Serializes a value as pretty printed JSON into a writer
+pub fn to_writer_pretty<W, T>(mut writer: W, value: &T) -> Result<()>
+where
+    W: Write,
+    T: Serialize + ?Sized,
+{
+    let mut s = Serializer(PrettyWriterGenerator::<W, OwnedValue>::new(&mut writer, 2));
+    value.serialize(&mut s)
+}
+
+/// Writes JSON text straight into a generator, the generator decides
+/// between compact and pretty output.
+struct Serializer<B: BaseGenerator>(B);
+
+impl<B> Serializer<B>
+where
+    B: BaseGenerator,
+{
+    /// Opens the object wrapping externally tagged enum variants
+    fn begin_variant(&mut self, variant: &str) -> io::Result<()> {
+        stry!(self.0.write_char(b'{'));
+        self.0.indent();
+        stry!(self.0.new_line());
+        stry!(self.0.write_string(variant));
+        self.0.write_min(b": ", b':')
+    }
+
+    fn end_variant(&mut self) -> io::Result<()> {
+        self.0.dedent();
+        stry!(self.0.new_line());
+        self.0.write_char(b'}')
+    }
+
+    fn begin_compound(&mut self, close: u8, variant: bool) -> Compound<'_, B> {
+        Compound {
+            s: self,
+            close,
+            first: true,
+            variant,
+        }
+    }
+}
+
+impl<'w, B> ser::Serializer for &'w mut Serializer<B>
+where
+    B: BaseGenerator,
+{
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Compound<'w, B>;
+    type SerializeTuple = Compound<'w, B>;
+    type SerializeTupleStruct = Compound<'w, B>;
+    type SerializeTupleVariant = Compound<'w, B>;
+    type SerializeMap = Compound<'w, B>;
+    type SerializeStruct = Struct<'w, B>;
+    type SerializeStructVariant = Compound<'w, B>;
+
+    #[inline]
+    fn serialize_bool(self, value: bool) -> Result<()> {
+        if value {
+            self.0.write(b"true").map_err(Error::from)
+        } else {
+            self.0.write(b"false").map_err(Error::from)
+        }
+    }
+
+    #[inline]
+    fn serialize_i8(self, value: i8) -> Result<()> {
+        self.serialize_i64(i64::from(value))
+    }
+
+    #[inline]
+    fn serialize_i16(self, value: i16) -> Result<()> {
+        self.serialize_i64(i64::from(value))
+    }
+
+    #[inline]
+    fn serialize_i32(self, value: i32) -> Result<()> {
+        self.serialize_i64(i64::from(value))
+    }
+
+    #[inline]
+    fn serialize_i64(self, value: i64) -> Result<()> {
+        self.0.write_int(value).map_err(Error::from)
+    }
+
+    fn serialize_i128(self, value: i128) -> Result<()> {
+        write!(self.0.get_writer(), "{}", value).map_err(Error::from)
+    }
+
+    #[inline]
+    fn serialize_u8(self, value: u8) -> Result<()> {
+        self.serialize_u64(u64::from(value))
+    }
+
+    #[inline]
+    fn serialize_u16(self, value: u16) -> Result<()> {
+        self.serialize_u64(u64::from(value))
+    }
+
+    #[inline]
+    fn serialize_u32(self, value: u32) -> Result<()> {
+        self.serialize_u64(u64::from(value))
+    }
+
+    #[inline]
+    fn serialize_u64(self, value: u64) -> Result<()> {
+        self.0.write_uint(value).map_err(Error::from)
+    }
+
+    fn serialize_u128(self, value: u128) -> Result<()> {
+        write!(self.0.get_writer(), "{}", value).map_err(Error::from)
+    }
+
+    #[inline]
+    fn serialize_f32(self, value: f32) -> Result<()> {
+        // JSON can't represent NAN or Infinity, we write them as `null`
+        if value.is_finite() {
+            let mut buffer = ryu::Buffer::new();
+            self.0
+                .write(buffer.format_finite(value).as_bytes())
+                .map_err(Error::from)
+        } else {
+            self.serialize_unit()
+        }
+    }
+
+    #[inline]
+    fn serialize_f64(self, value: f64) -> Result<()> {
+        if value.is_finite() {
+            self.0.write_float(value).map_err(Error::from)
+        } else {
+            self.serialize_unit()
+        }
+    }
+
+    #[inline]
+    fn serialize_char(self, value: char) -> Result<()> {
+        let mut buf = [0; 4];
+        self.serialize_str(value.encode_utf8(&mut buf))
+    }
+
+    #[inline]
+    fn serialize_str(self, value: &str) -> Result<()> {
+        self.0.write_string(value).map_err(Error::from)
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<()> {
+        use serde_ext::ser::SerializeSeq;
+        let mut seq = stry!(self.serialize_seq(Some(value.len())));
+        for b in value {
+            stry!(seq.serialize_element(b));
+        }
+        seq.end()
+    }
+
+    #[inline]
+    fn serialize_unit(self) -> Result<()> {
+        self.0.write(b"null").map_err(Error::from)
+    }
+
+    #[inline]
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        self.serialize_unit()
+    }
+
+    #[inline]
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.serialize_str(variant)
+    }
+
+    #[inline]
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        iotry!(self.begin_variant(variant));
+        stry!(value.serialize(&mut *self));
+        self.end_variant().map_err(Error::from)
+    }
+
+    #[inline]
+    fn serialize_none(self) -> Result<()> {
+        self.serialize_unit()
+    }
+
+    #[inline]
+    fn serialize_some<T>(self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        iotry!(self.0.write_char(b'['));
+        Ok(self.begin_compound(b']', false))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        iotry!(self.begin_variant(variant));
+        iotry!(self.0.write_char(b'['));
+        Ok(self.begin_compound(b']', true))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        iotry!(self.0.write_char(b'{'));
+        Ok(self.begin_compound(b'}', false))
+    }
+
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        match name {
+            #[cfg(feature = "arbitrary_precision")]
+            TOKEN => Ok(Struct::Number(self)),
+            _ => self.serialize_map(Some(len)).map(Struct::Map),
+        }
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        iotry!(self.begin_variant(variant));
+        iotry!(self.0.write_char(b'{'));
+        Ok(self.begin_compound(b'}', true))
+    }
+}
+
+/// An array or object that is being written
+struct Compound<'w, B: BaseGenerator> {
+    s: &'w mut Serializer<B>,
+    close: u8,
+    first: bool,
+    variant: bool,
+}
+
+impl<'w, B> Compound<'w, B>
+where
+    B: BaseGenerator,
+{
+    /// Writes what goes in front of an element
+    fn next_element(&mut self) -> io::Result<()> {
+        if self.first {
+            self.first = false;
+            self.s.0.indent();
+        } else {
+            stry!(self.s.0.write_char(b','));
+        }
+        self.s.0.new_line()
+    }
+
+    fn close(self) -> Result<()> {
+        let g = &mut self.s.0;
+        if !self.first {
+            g.dedent();
+            iotry!(g.new_line());
+        }
+        iotry!(g.write_char(self.close));
+        if self.variant {
+            iotry!(self.s.end_variant());
+        }
+        Ok(())
+    }
+}
+
+impl<'w, B> ser::SerializeSeq for Compound<'w, B>
+where
+    B: BaseGenerator,
+{
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        iotry!(self.next_element());
+        value.serialize(&mut *self.s)
+    }
+
+    fn end(self) -> Result<()> {
+        self.close()
+    }
+}
+
+impl<'w, B> ser::SerializeTuple for Compound<'w, B>
+where
+    B: BaseGenerator,
+{
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.close()
+    }
+}
+
+impl<'w, B> ser::SerializeTupleStruct for Compound<'w, B>
+where
+    B: BaseGenerator,
+{
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.close()
+    }
+}
+
+impl<'w, B> ser::SerializeTupleVariant for Compound<'w, B>
+where
+    B: BaseGenerator,
+{
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.close()
+    }
+}
+
+impl<'w, B> ser::SerializeMap for Compound<'w, B>
+where
+    B: BaseGenerator,
+{
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        iotry!(self.next_element());
+        key.serialize(MapKeySerializer { s: &mut *self.s })
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        iotry!(self.s.0.write_min(b": ", b':'));
+        value.serialize(&mut *self.s)
+    }
+
+    fn end(self) -> Result<()> {
+        self.close()
+    }
+}
+
+impl<'w, B> ser::SerializeStructVariant for Compound<'w, B>
+where
+    B: BaseGenerator,
+{
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeMap::serialize_entry(self, key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.close()
+    }
+}
+
+/// A struct is either written as an object or, for the private number
+/// struct, as the literal of the number it holds.
+enum Struct<'w, B: BaseGenerator> {
+    Map(Compound<'w, B>),
+    #[cfg(feature = "arbitrary_precision")]
+    Number(&'w mut Serializer<B>),
+}
+
+impl<'w, B> ser::SerializeStruct for Struct<'w, B>
+where
+    B: BaseGenerator,
+{
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        match self {
+            Struct::Map(m) => ser::SerializeMap::serialize_entry(m, key, value),
+            #[cfg(feature = "arbitrary_precision")]
+            Struct::Number(s) => {
+                if key == TOKEN {
+                    if let OwnedValue::String(n) = stry!(crate::serde::to_owned_value(value)) {
+                        return s.0.write(n.as_bytes()).map_err(Error::from);
+                    }
+                }
+                Err(Error::generic(ErrorType::Serde(
+                    "invalid arbitrary precision number".into(),
+                )))
+            }
+        }
+    }
+
+    fn end(self) -> Result<()> {
+        match self {
+            Struct::Map(m) => m.close(),
+            #[cfg(feature = "arbitrary_precision")]
+            Struct::Number(_) => Ok(()),
+        }
+    }
+}
+
+/// Writes map keys, JSON only allows strings so numbers and booleans are
+/// quoted.
+struct MapKeySerializer<'w, B: BaseGenerator> {
+    s: &'w mut Serializer<B>,
+}
+
+fn key_must_be_a_string() -> Error {
+    Error::generic(ErrorType::KeyMustBeAString)
+}
+
+impl<'w, B> MapKeySerializer<'w, B>
+where
+    B: BaseGenerator,
+{
+    fn quoted<F>(self, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut B) -> io::Result<()>,
+    {
+        let g = &mut self.s.0;
+        iotry!(g.write_char(b'"'));
+        iotry!(f(g));
+        g.write_char(b'"').map_err(Error::from)
+    }
+}
+
+impl<'w, B> ser::Serializer for MapKeySerializer<'w, B>
+where
+    B: BaseGenerator,
+{
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Impossible;
+    type SerializeTuple = Impossible;
+    type SerializeTupleStruct = Impossible;
+    type SerializeTupleVariant = Impossible;
+    type SerializeMap = Impossible;
+    type SerializeStruct = Impossible;
+    type SerializeStructVariant = Impossible;
+
+    #[inline]
+    fn serialize_str(self, value: &str) -> Result<()> {
+        self.s.serialize_str(value)
+    }
+
+    #[inline]
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.s.serialize_str(variant)
+    }
+
+    #[inline]
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_char(self, value: char) -> Result<()> {
+        self.s.serialize_char(value)
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<()> {
+        self.quoted(|g| g.write_int(i64::from(value)))
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<()> {
+        self.quoted(|g| g.write_int(i64::from(value)))
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<()> {
+        self.quoted(|g| g.write_int(i64::from(value)))
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<()> {
+        self.quoted(|g| g.write_int(value))
+    }
+
+    fn serialize_i128(self, value: i128) -> Result<()> {
+        self.quoted(|g| write!(g.get_writer(), "{}", value))
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<()> {
+        self.quoted(|g| g.write_uint(u64::from(value)))
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<()> {
+        self.quoted(|g| g.write_uint(u64::from(value)))
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<()> {
+        self.quoted(|g| g.write_uint(u64::from(value)))
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<()> {
+        self.quoted(|g| g.write_uint(value))
+    }
+
+    fn serialize_u128(self, value: u128) -> Result<()> {
+        self.quoted(|g| write!(g.get_writer(), "{}", value))
+    }
+
+    fn serialize_bool(self, value: bool) -> Result<()> {
+        self.quoted(|g| g.write(if value { b"true" } else { b"false" }))
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<()> {
+        if value.is_finite() {
+            self.quoted(|g| g.write(ryu::Buffer::new().format_finite(value).as_bytes()))
+        } else {
+            Err(key_must_be_a_string())
+        }
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<()> {
+        if value.is_finite() {
+            self.quoted(|g| g.write_float(value))
+        } else {
+            Err(key_must_be_a_string())
+        }
+    }
+
+    fn serialize_bytes(self, _value: &[u8]) -> Result<()> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(key_must_be_a_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{to_string, to_string_pretty, to_vec, to_writer, to_writer_pretty};
+    use crate::{ErrorType, OwnedValue};
+    use halfbrown::HashMap;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[derive(Deserialize, Serialize, PartialEq, Debug, Default)]
+    struct Obj {
+        v_i128: i128,
+        v_i64: i64,
+        v_i8: i8,
+        v_u128: u128,
+        v_u64: u64,
+        v_f32: f32,
+        v_f64: f64,
+        v_bool: bool,
+        v_char: char,
+        v_str: String,
+        v_opt: Option<u8>,
+        v_arr: Vec<usize>,
+        v_empty: Vec<usize>,
+        v_tuple: (u8, String),
+        v_map: BTreeMap<String, u32>,
+        v_null: (),
+    }
+
+    fn obj() -> Obj {
+        let mut v_map = BTreeMap::new();
+        v_map.insert("snot".to_string(), 1);
+        v_map.insert("badger".to_string(), 2);
+        Obj {
+            v_i128: i128::from(i64::min_value()),
+            v_i64: -42,
+            v_i8: 7,
+            v_u128: u128::from(u64::max_value()),
+            v_u64: u64::max_value(),
+            v_f32: 0.1,
+            v_f64: -1.5,
+            v_bool: true,
+            v_char: '\u{1f980}',
+            v_str: "snot \"badger\"\n\u{1}".to_string(),
+            v_opt: None,
+            v_arr: vec![1, 2, 3],
+            v_empty: vec![],
+            v_tuple: (1, "two".to_string()),
+            v_map,
+            v_null: (),
+        }
+    }
+
+    #[derive(Deserialize, Serialize, PartialEq, Debug)]
+    enum Enum {
+        Unit,
+        Newtype(u8),
+        Tuple(u8, u8),
+        Struct { a: u8, b: Vec<u8> },
+    }
+
+    #[test]
+    fn same_as_serde_json() {
+        let o = obj();
+        assert_eq!(
+            to_string(&o).expect("to_string"),
+            serde_json::to_string(&o).expect("serde_json")
+        );
+        assert_eq!(
+            to_string_pretty(&o).expect("to_string_pretty"),
+            serde_json::to_string_pretty(&o).expect("serde_json")
+        );
+        assert_eq!(
+            to_string(&(i128::min_value(), u128::max_value())).expect("to_string"),
+            serde_json::to_string(&(i128::min_value(), u128::max_value())).expect("serde_json")
+        );
+        let e = vec![
+            Enum::Unit,
+            Enum::Newtype(1),
+            Enum::Tuple(1, 2),
+            Enum::Struct { a: 1, b: vec![] },
+        ];
+        assert_eq!(
+            to_string(&e).expect("to_string"),
+            serde_json::to_string(&e).expect("serde_json")
+        );
+        assert_eq!(
+            to_string_pretty(&e).expect("to_string_pretty"),
+            serde_json::to_string_pretty(&e).expect("serde_json")
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        let o = obj();
+        let mut v = to_vec(&o).expect("to_vec");
+        let de: Obj = crate::serde::from_slice(&mut v).expect("from_slice");
+        assert_eq!(o, de);
+    }
+
+    #[test]
+    fn writer() {
+        let o = obj();
+        let mut w = Vec::new();
+        to_writer(&mut w, &o).expect("to_writer");
+        assert_eq!(w, to_vec(&o).expect("to_vec"));
+        let mut w = Vec::new();
+        to_writer_pretty(&mut w, &o).expect("to_writer_pretty");
+        assert_eq!(
+            String::from_utf8(w).expect("utf8"),
+            to_string_pretty(&o).expect("to_string_pretty")
+        );
+    }
+
+    #[test]
+    fn value() {
+        let v: OwnedValue = crate::json!({"snot": [1, 2.5, null, {"badger": "\t"}]});
+        assert_eq!(to_string(&v).expect("to_string"), v.encode());
+        assert_eq!(
+            to_string_pretty(&v).expect("to_string_pretty"),
+            v.encode_pp()
+        );
+    }
+
+    #[test]
+    fn map_keys() {
+        let mut m = HashMap::new();
+        m.insert(-1, "snot");
+        assert_eq!(to_string(&m).expect("to_string"), r#"{"-1":"snot"}"#);
+        let mut m = HashMap::new();
+        m.insert(true, "snot");
+        assert_eq!(to_string(&m).expect("to_string"), r#"{"true":"snot"}"#);
+        let mut m = HashMap::new();
+        m.insert((), "snot");
+        let e = to_string(&m).expect_err("unit key");
+        assert_eq!(e.error_type(), &ErrorType::KeyMustBeAString);
+    }
+
+    #[test]
+    fn non_finite() {
+        assert_eq!(
+            to_string(&[std::f64::NAN, std::f64::INFINITY]).expect("to_string"),
+            "[null,null]"
+        );
+    }
+}
@@ -1,3 +1,6 @@
+use crate::serde::key::MapKeyDeserializer;
+#[cfg(feature = "arbitrary_precision")]
+use crate::serde::number::{NumberDeserializer, TOKEN};
 use crate::value::borrowed::{Object, Value};
 use crate::Error;
 use serde_ext::de::{
@@ -5,7 +8,6 @@ use serde_ext::de::{
 };
 use serde_ext::forward_to_deserialize_any;
 use std::borrow::Cow;
-use std::convert::TryInto;
 use std::fmt;
 
 impl<'de> de::Deserializer<'de> for Value<'de> {
@@ -22,7 +24,10 @@ impl<'de> de::Deserializer<'de> for Value<'de> {
             Value::Null => visitor.visit_unit(),
             Value::Bool(b) => visitor.visit_bool(b),
             Value::I64(n) => visitor.visit_i64(n),
+            Value::U64(n) => visitor.visit_u64(n),
             Value::F64(n) => visitor.visit_f64(n),
+            #[cfg(feature = "arbitrary_precision")]
+            Value::Number(n) => visitor.visit_map(NumberDeserializer::new(n.into_owned())),
             Value::String(s) => match s {
                 Cow::Borrowed(s) => visitor.visit_borrowed_str(s),
                 Cow::Owned(s) => visitor.visit_string(s),
@@ -77,7 +82,8 @@ impl<'de, 'a> MapAccess<'de> for ObjectAccess<'a, 'de> {
     {
         if let Some((k, v)) = self.i.next() {
             self.v = v;
-            seed.deserialize(Value::String(k.clone())).map(Some)
+            seed.deserialize(MapKeyDeserializer::new(k.clone()))
+                .map(Some)
         } else {
             Ok(None)
         }
@@ -92,6 +98,53 @@ impl<'de, 'a> MapAccess<'de> for ObjectAccess<'a, 'de> {
     }
 }
 
+/// Object keys are borrowed like strings are, keys that had to be
+/// unescaped are owned.
+struct KeyVisitor;
+
+impl<'de> DeserializeSeed<'de> for KeyVisitor {
+    type Value = Cow<'de, str>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de> Visitor<'de> for KeyVisitor {
+    type Value = Cow<'de, str>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an object key")
+    }
+
+    #[cfg_attr(not(feature = "no-inline"), inline)]
+    fn visit_borrowed_str<E>(self, value: &'de str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(Cow::Borrowed(value))
+    }
+
+    #[cfg_attr(not(feature = "no-inline"), inline)]
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(Cow::Owned(value.to_owned()))
+    }
+
+    #[cfg_attr(not(feature = "no-inline"), inline)]
+    fn visit_string<E>(self, value: String) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(Cow::Owned(value))
+    }
+}
+
 impl<'de> Deserialize<'de> for Value<'de> {
     fn deserialize<D>(deserializer: D) -> Result<Value<'de>, D::Error>
     where
@@ -208,11 +261,7 @@ impl<'de> Visitor<'de> for ValueVisitor {
     where
         E: de::Error,
     {
-        if let Ok(value) = value.try_into() {
-            Ok(Value::I64(value))
-        } else {
-            Err(E::custom(format!("Integer out of range: {}", value)))
-        }
+        Ok(Value::from(value))
     }
 
     /****************** f64 ******************/
@@ -304,9 +353,20 @@ impl<'de> Visitor<'de> for ValueVisitor {
         let size = map.size_hint().unwrap_or_default();
 
         let mut m = Object::with_capacity(size);
-        while let Some(k) = map.next_key::<&str>()? {
+        // Arbitrary precision numbers are passed as a map with a single,
+        // private, key
+        #[cfg(feature = "arbitrary_precision")]
+        match map.next_key_seed(KeyVisitor)? {
+            Some(k) if k == TOKEN => return Ok(Value::Number(map.next_value::<String>()?.into())),
+            Some(k) => {
+                let v = map.next_value()?;
+                m.insert(k, v);
+            }
+            None => return Ok(Value::Object(m)),
+        }
+        while let Some(k) = map.next_key_seed(KeyVisitor)? {
             let v = map.next_value()?;
-            m.insert(k.into(), v);
+            m.insert(k, v);
         }
         Ok(Value::Object(m))
     }
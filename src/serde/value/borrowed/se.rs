@@ -14,6 +14,9 @@ impl<'a> Serialize for Value<'a> {
             Value::Null => serializer.serialize_unit(),
             Value::F64(f) => serializer.serialize_f64(*f),
             Value::I64(i) => serializer.serialize_i64(*i),
+            Value::U64(i) => serializer.serialize_u64(*i),
+            #[cfg(feature = "arbitrary_precision")]
+            Value::Number(n) => crate::serde::number::serialize_number(n, serializer),
             Value::String(Cow::Borrowed(s)) => serializer.serialize_str(s),
             Value::String(Cow::Owned(s)) => serializer.serialize_str(&s),
             Value::Array(v) => {
@@ -113,7 +116,7 @@ impl<'a> serde::Serializer for Serializer<'a> {
 
     #[inline]
     fn serialize_u64(self, value: u64) -> Result<Value<'a>> {
-        Ok(Value::I64(value as i64))
+        Ok(Value::from(value))
     }
 
     #[cfg(feature = "arbitrary_precision")]
@@ -1,8 +1,12 @@
 use super::to_value;
+#[cfg(feature = "arbitrary_precision")]
+use crate::serde::number::TOKEN;
 use crate::value::owned::{Object, Value};
 use crate::{stry, Error, ErrorType, Result};
 use serde::ser::{self, Serialize};
 use serde_ext::ser::{SerializeMap as SerializeMapTrait, SerializeSeq as SerializeSeqTrait};
+#[cfg(feature = "arbitrary_precision")]
+use std::convert::TryFrom;
 
 type Impossible<T> = ser::Impossible<T, Error>;
 
@@ -16,6 +20,9 @@ impl Serialize for Value {
             Self::Null => serializer.serialize_unit(),
             Self::F64(f) => serializer.serialize_f64(*f),
             Self::I64(i) => serializer.serialize_i64(*i),
+            Self::U64(i) => serializer.serialize_u64(*i),
+            #[cfg(feature = "arbitrary_precision")]
+            Self::Number(n) => crate::serde::number::serialize_number(n, serializer),
             Self::String(s) => serializer.serialize_str(&s),
             Self::Array(v) => {
                 let mut seq = serializer.serialize_seq(Some(v.len()))?;
@@ -79,9 +86,13 @@ impl serde::Serializer for Serializer {
     }
 
     #[cfg(feature = "arbitrary_precision")]
-    serde_if_integer128! {
-        fn serialize_i128(self, value: i128) -> Result<Value> {
-            Ok(Value::Number(value.into()))
+    fn serialize_i128(self, value: i128) -> Result<Value> {
+        if let Ok(i) = i64::try_from(value) {
+            Ok(Value::I64(i))
+        } else if let Ok(u) = u64::try_from(value) {
+            Ok(Value::U64(u))
+        } else {
+            Ok(Value::Number(value.to_string()))
         }
     }
 
@@ -102,14 +113,15 @@ impl serde::Serializer for Serializer {
 
     #[inline]
     fn serialize_u64(self, value: u64) -> Result<Value> {
-        #[allow(clippy::cast_possible_wrap)]
-        Ok(Value::I64(value as i64))
+        Ok(Value::from(value))
     }
 
     #[cfg(feature = "arbitrary_precision")]
-    serde_if_integer128! {
-        fn serialize_u128(self, value: u128) -> Result<Value> {
-            Ok(Value::Number(value.into()))
+    fn serialize_u128(self, value: u128) -> Result<Value> {
+        if let Ok(u) = u64::try_from(value) {
+            Ok(Value::from(u))
+        } else {
+            Ok(Value::Number(value.to_string()))
         }
     }
 
@@ -237,7 +249,7 @@ impl serde::Serializer for Serializer {
     fn serialize_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
         match name {
             #[cfg(feature = "arbitrary_precision")]
-            ::number::TOKEN => Ok(SerializeMap::Number { out_value: None }),
+            TOKEN => Ok(SerializeMap::Number { out_value: None }),
             #[cfg(feature = "raw_value")]
             ::raw::TOKEN => Ok(SerializeMap::RawValue { out_value: None }),
             _ => self.serialize_map(Some(len)),
@@ -272,6 +284,8 @@ pub enum SerializeMap {
         map: Object,
         next_key: Option<String>,
     },
+    #[cfg(feature = "arbitrary_precision")]
+    Number { out_value: Option<Value> },
 }
 
 pub struct SerializeStructVariant {
@@ -605,12 +619,15 @@ impl serde::ser::SerializeStruct for SerializeMap {
             }
             #[cfg(feature = "arbitrary_precision")]
             Self::Number { ref mut out_value } => {
-                if key == ::number::TOKEN {
-                    *out_value = Some(value.serialize(NumberValueEmitter)?);
-                    Ok(())
-                } else {
-                    Err(invalid_number())
+                if key == TOKEN {
+                    if let Value::String(n) = stry!(to_value(&value)) {
+                        *out_value = Some(Value::Number(n));
+                        return Ok(());
+                    }
                 }
+                Err(Error::generic(ErrorType::Serde(
+                    "invalid arbitrary precision number".into(),
+                )))
             }
             #[cfg(feature = "raw_value")]
             Self::RawValue { ref mut out_value } => {
@@ -24,18 +24,18 @@ use std::arch::x86_64::*;
 // all byte values must be no larger than 0xF4
 
 /*****************************/
-#[cfg_attr(not(feature = "no-inline"), inline)]
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
 fn push_last_byte_of_a_to_b(a: __m128i, b: __m128i) -> __m128i {
     unsafe { _mm_alignr_epi8(b, a, 15) }
 }
 
-#[cfg_attr(not(feature = "no-inline"), inline)]
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
 fn push_last_2bytes_of_a_to_b(a: __m128i, b: __m128i) -> __m128i {
     unsafe { _mm_alignr_epi8(b, a, 14) }
 }
 
 // all byte values must be no larger than 0xF4
-#[cfg_attr(not(feature = "no-inline"), inline)]
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
 fn avxcheck_smaller_than_0xf4(current_bytes: __m128i, has_error: &mut __m128i) {
     // unsigned, saturates to 0 below max
     *has_error = unsafe {
@@ -46,7 +46,7 @@ fn avxcheck_smaller_than_0xf4(current_bytes: __m128i, has_error: &mut __m128i) {
     };
 }
 
-#[cfg_attr(not(feature = "no-inline"), inline)]
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
 fn avxcontinuation_lengths(high_nibbles: __m128i) -> __m128i {
     unsafe {
         _mm_shuffle_epi8(
@@ -62,7 +62,7 @@ fn avxcontinuation_lengths(high_nibbles: __m128i) -> __m128i {
     }
 }
 
-#[cfg_attr(not(feature = "no-inline"), inline)]
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
 fn avxcarry_continuations(initial_lengths: __m128i, previous_carries: __m128i) -> __m128i {
     unsafe {
         let right1: __m128i = _mm_subs_epu8(
@@ -78,7 +78,7 @@ fn avxcarry_continuations(initial_lengths: __m128i, previous_carries: __m128i) -
     }
 }
 
-#[cfg_attr(not(feature = "no-inline"), inline)]
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
 fn avxcheck_continuations(initial_lengths: __m128i, carries: __m128i, has_error: &mut __m128i) {
     // overlap || underlap
     // carry > length && length > 0 || !(carry > length) && !(length > 0)
@@ -96,7 +96,7 @@ fn avxcheck_continuations(initial_lengths: __m128i, carries: __m128i, has_error:
 // when 0xED is found, next byte must be no larger than 0x9F
 // when 0xF4 is found, next byte must be no larger than 0x8F
 // next byte must be continuation, ie sign bit is set, so signed < is ok
-#[cfg_attr(not(feature = "no-inline"), inline)]
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
 fn avxcheck_first_continuation_max(
     current_bytes: __m128i,
     off1_current_bytes: __m128i,
@@ -127,7 +127,7 @@ fn avxcheck_first_continuation_max(
 // E       => < E1 && < A0
 // F       => < F1 && < 90
 // else      false && false
-#[cfg_attr(not(feature = "no-inline"), inline)]
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
 fn avxcheck_overlong(
     current_bytes: __m128i,
     off1_current_bytes: __m128i,
@@ -194,7 +194,7 @@ pub(crate) struct AvxProcessedUtfBytes {
 }
 
 impl Default for AvxProcessedUtfBytes {
-    #[cfg_attr(not(feature = "no-inline"), inline)]
+    #[cfg_attr(not(feature = "no-inline"), inline(always))]
     fn default() -> Self {
         unsafe {
             Self {
@@ -206,7 +206,7 @@ impl Default for AvxProcessedUtfBytes {
     }
 }
 
-#[cfg_attr(not(feature = "no-inline"), inline)]
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
 fn avx_count_nibbles(bytes: __m128i, answer: &mut AvxProcessedUtfBytes) {
     answer.rawbytes = bytes;
     answer.high_nibbles = unsafe { _mm_and_si128(_mm_srli_epi16(bytes, 4), _mm_set1_epi8(0x0F)) };
@@ -214,7 +214,7 @@ fn avx_count_nibbles(bytes: __m128i, answer: &mut AvxProcessedUtfBytes) {
 
 // check whether the current bytes are valid UTF-8
 // at the end of the function, previous gets updated
-#[cfg_attr(not(feature = "no-inline"), inline)]
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
 pub(crate) fn avxcheck_utf8_bytes(
     current_bytes: __m128i,
     previous: &AvxProcessedUtfBytes,
@@ -1,19 +1,24 @@
 #![allow(dead_code)]
-#[cfg(target_feature = "avx2")]
-use crate::avx2::stage1::SIMDJSON_PADDING;
 use crate::charutils::*;
-#[cfg(target_feature = "neon")]
-use crate::neon::stage1::SIMDJSON_PADDING;
-#[cfg(all(
-    any(target_arch = "x86", target_arch = "x86_64"),
-    not(target_feature = "avx2")
-))]
-use crate::sse42::stage1::SIMDJSON_PADDING;
-use crate::{Deserializer, Error, ErrorType, Result};
+use crate::{Deserializer, Error, ErrorType, Limit, Limits, Result, SIMDJSON_PADDING};
+
+/// Atoms are checked a whole word at a time, the input isn't padded so
+/// near its end they are copied into `buf` first.
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+fn padded<'a>(loc: &'a [u8], buf: &'a mut [u8; 8]) -> &'a [u8] {
+    if likely!(loc.len() >= 8) {
+        loc
+    } else {
+        buf[..loc.len()].copy_from_slice(loc);
+        buf
+    }
+}
 
 #[cfg_attr(not(feature = "no-inline"), inline(always))]
 pub fn is_valid_true_atom(loc: &[u8]) -> bool {
     // TODO is this expensive?
+    let mut buf = [0; 8];
+    let loc = padded(loc, &mut buf);
     let mut error: u64;
     unsafe {
         //let tv: u64 = *(b"true    ".as_ptr() as *const u64);
@@ -24,7 +29,7 @@ pub fn is_valid_true_atom(loc: &[u8]) -> bool {
         // TODO: does this has the same effect as:
         //   std::memcpy(&locval, loc, sizeof(uint64_t));
         #[allow(clippy::cast_ptr_alignment)]
-        let locval: u64 = *(loc.as_ptr() as *const u64);
+        let locval: u64 = std::ptr::read_unaligned(loc.as_ptr() as *const u64);
 
         error = (locval & MASK4) ^ TV;
         error |= u64::from(is_not_structural_or_whitespace(*loc.get_unchecked(4)));
@@ -35,6 +40,8 @@ pub fn is_valid_true_atom(loc: &[u8]) -> bool {
 #[cfg_attr(not(feature = "no-inline"), inline(always))]
 pub fn is_valid_false_atom(loc: &[u8]) -> bool {
     // TODO: this is ugly and probably copies data every time
+    let mut buf = [0; 8];
+    let loc = padded(loc, &mut buf);
     let mut error: u64;
     unsafe {
         //let fv: u64 = *(b"false   ".as_ptr() as *const u64);
@@ -44,7 +51,7 @@ pub fn is_valid_false_atom(loc: &[u8]) -> bool {
         const MASK5: u64 = 0x00_00_00_ff_ff_ff_ff_ff;
 
         #[allow(clippy::cast_ptr_alignment)]
-        let locval: u64 = *(loc.as_ptr() as *const u64);
+        let locval: u64 = std::ptr::read_unaligned(loc.as_ptr() as *const u64);
 
         // FIXME the original code looks like this:
         // error = ((locval & mask5) ^ fv) as u32;
@@ -60,6 +67,8 @@ pub fn is_valid_false_atom(loc: &[u8]) -> bool {
 #[cfg_attr(not(feature = "no-inline"), inline(always))]
 pub fn is_valid_null_atom(loc: &[u8]) -> bool {
     // TODO is this expensive?
+    let mut buf = [0; 8];
+    let loc = padded(loc, &mut buf);
     let mut error: u64;
     unsafe {
         //let nv: u64 = *(b"null   ".as_ptr() as *const u64);
@@ -67,7 +76,7 @@ pub fn is_valid_null_atom(loc: &[u8]) -> bool {
         const NV: u64 = 0x00_00_00_00_6c_6c_75_6e;
         const MASK4: u64 = 0x00_00_00_00_ff_ff_ff_ff;
         #[allow(clippy::cast_ptr_alignment)]
-        let locval: u64 = *(loc.as_ptr() as *const u64);
+        let locval: u64 = std::ptr::read_unaligned(loc.as_ptr() as *const u64);
 
         error = (locval & MASK4) ^ NV;
         error |= u64::from(is_not_structural_or_whitespace(*loc.get_unchecked(4)));
@@ -89,13 +98,33 @@ enum StackState {
 }
 
 impl<'de> Deserializer<'de> {
-    pub fn validate(input: &[u8], structural_indexes: &[u32]) -> Result<Vec<usize>> {
-        let mut counts = Vec::with_capacity(structural_indexes.len());
+    /// Checks the structure of the document, the grammar of numbers and the
+    /// escapes of strings are left to whoever reads them. For every array
+    /// and object the number of elements and the index of the closing
+    /// structural are stored in `counts` at the index of its opening
+    /// structural.
+    pub fn validate(
+        input: &[u8],
+        structural_indexes: &[u32],
+        counts: &mut Vec<(u32, u32)>,
+        limits: &Limits,
+    ) -> Result<()> {
+        counts.clear();
+        counts.reserve(structural_indexes.len());
+        // Sizing the stack by `max_depth` instead measured slower, so it is
+        // sized like `counts` and the depth is checked on its own.
         let mut stack = Vec::with_capacity(structural_indexes.len());
         unsafe {
             counts.set_len(structural_indexes.len());
             stack.set_len(structural_indexes.len());
         }
+        let max_depth = limits.max_depth;
+        // Unlimited is the default, don't compare every count against it
+        let max_elements = if limits.max_elements == usize::max_value() {
+            None
+        } else {
+            Some(limits.max_elements)
+        };
 
         let mut depth = 0;
         let mut last_start = 1;
@@ -113,7 +142,12 @@ impl<'de> Deserializer<'de> {
         let mut si = structural_indexes.iter().skip(1).peekable();
         macro_rules! update_char {
             () => {
-                idx = *stry!(si.next().ok_or_else(|| (Error::generic(ErrorType::Syntax)))) as usize;
+                idx = *stry!(si.next().ok_or_else(|| Error::new(
+                    i,
+                    input.len(),
+                    input,
+                    ErrorType::Syntax
+                ))) as usize;
                 i += 1;
                 c = unsafe { *input.get_unchecked(idx) };
             };
@@ -143,7 +177,7 @@ impl<'de> Deserializer<'de> {
                         goto!(ScopeEnd);
                     }
                     _c => {
-                        fail!(ErrorType::ExpectedArrayContent);
+                        fail!(ErrorType::ExpectedArrayComma);
                     }
                 }
             }};
@@ -200,11 +234,15 @@ impl<'de> Deserializer<'de> {
         }
 
         macro_rules! fail {
-            () => {
-                return Err(Error::new(i, idx, c as char, ErrorType::InternalError));
-            };
             ($t:expr) => {
-                return Err(Error::new(i, idx, c as char, $t));
+                return Err(Error::new(i, idx, input, $t));
+            };
+        }
+        macro_rules! push_depth {
+            () => {
+                if unlikely!(depth >= max_depth) {
+                    fail!(ErrorType::LimitExceeded(Limit::Depth));
+                }
             };
         }
         // State start, we pull this outside of the
@@ -212,6 +250,7 @@ impl<'de> Deserializer<'de> {
         update_char!();
         match c {
             b'{' => {
+                push_depth!();
                 unsafe {
                     *stack.get_unchecked_mut(depth) = (StackState::Start, last_start, cnt);
                 }
@@ -234,6 +273,7 @@ impl<'de> Deserializer<'de> {
                 }
             }
             b'[' => {
+                push_depth!();
                 unsafe {
                     *stack.get_unchecked_mut(depth) = (StackState::Start, last_start, cnt);
                 }
@@ -259,7 +299,7 @@ impl<'de> Deserializer<'de> {
                     }
                 };
                 if si.next().is_none() {
-                    return Ok(counts);
+                    return Ok(());
                 } else {
                     fail!(ErrorType::TrailingCharacters);
                 }
@@ -274,7 +314,7 @@ impl<'de> Deserializer<'de> {
                     }
                 };
                 if si.next().is_none() {
-                    return Ok(counts);
+                    return Ok(());
                 } else {
                     fail!(ErrorType::TrailingCharacters);
                 }
@@ -289,20 +329,20 @@ impl<'de> Deserializer<'de> {
                     }
                 };
                 if si.next().is_none() {
-                    return Ok(counts);
+                    return Ok(());
                 } else {
                     fail!(ErrorType::TrailingCharacters);
                 }
             }
             b'"' | b'-' | b'0'..=b'9' => {
                 if si.next().is_none() {
-                    return Ok(counts);
+                    return Ok(());
                 } else {
                     fail!(ErrorType::TrailingCharacters);
                 }
             }
             _ => {
-                fail!();
+                fail!(ErrorType::UnexpectedCharacter);
             }
         }
 
@@ -341,6 +381,7 @@ impl<'de> Deserializer<'de> {
                             object_continue!();
                         }
                         b'{' => {
+                            push_depth!();
                             unsafe {
                                 *stack.get_unchecked_mut(depth) =
                                     (StackState::Object, last_start, cnt);
@@ -351,6 +392,7 @@ impl<'de> Deserializer<'de> {
                             object_begin!();
                         }
                         b'[' => {
+                            push_depth!();
                             unsafe {
                                 *stack.get_unchecked_mut(depth) =
                                     (StackState::Object, last_start, cnt);
@@ -361,18 +403,25 @@ impl<'de> Deserializer<'de> {
                             array_begin!();
                         }
                         _c => {
-                            fail!();
+                            fail!(ErrorType::ExpectedObjectContent);
                         }
                     }
                 }
                 ////////////////////////////// COMMON STATE /////////////////////////////
                 ScopeEnd => {
                     if depth == 0 {
-                        return Err(Error::generic(ErrorType::Syntax));
+                        return Err(Error::new(i, idx, input, ErrorType::Syntax));
                     }
                     depth -= 1;
+                    #[allow(clippy::cast_possible_truncation)]
                     unsafe {
-                        *counts.get_unchecked_mut(last_start) = cnt;
+                        // structural indexes are u32 so neither can overflow
+                        *counts.get_unchecked_mut(last_start) = (cnt as u32, i as u32);
+                    }
+                    if let Some(max) = max_elements {
+                        if unlikely!(cnt > max) {
+                            fail!(ErrorType::LimitExceeded(Limit::Elements));
+                        }
                     }
 
                     let (a_state, a_last_start, a_cnt) = unsafe { stack.get_unchecked(depth) };
@@ -387,9 +436,9 @@ impl<'de> Deserializer<'de> {
                         StackState::Array => array_continue!(),
                         StackState::Start => {
                             if si.next().is_none() {
-                                return Ok(counts);
+                                return Ok(());
                             } else {
-                                fail!();
+                                fail!(ErrorType::TrailingCharacters);
                             }
                         }
                     };
@@ -423,6 +472,7 @@ impl<'de> Deserializer<'de> {
                             array_continue!();
                         }
                         b'{' => {
+                            push_depth!();
                             unsafe {
                                 *stack.get_unchecked_mut(depth) =
                                     (StackState::Array, last_start, cnt);
@@ -433,6 +483,7 @@ impl<'de> Deserializer<'de> {
                             object_begin!();
                         }
                         b'[' => {
+                            push_depth!();
                             unsafe {
                                 *stack.get_unchecked_mut(depth) =
                                     (StackState::Array, last_start, cnt);
@@ -443,7 +494,7 @@ impl<'de> Deserializer<'de> {
                             array_begin!();
                         }
                         _c => {
-                            fail!();
+                            fail!(ErrorType::ExpectedArrayContent);
                         }
                     }
                 }
@@ -461,6 +512,9 @@ mod test {
         assert!(is_valid_true_atom(b"true    "));
         assert!(!is_valid_true_atom(b"tru     "));
         assert!(!is_valid_true_atom(b" rue    "));
+        // atoms at the end of unpadded input
+        assert!(is_valid_true_atom(b"true]"));
+        assert!(!is_valid_true_atom(b"tru]"));
     }
     #[test]
     fn false_atom() {
@@ -478,5 +532,6 @@ mod test {
         assert!(is_valid_null_atom(b"null    "));
         assert!(!is_valid_null_atom(b"nul     "));
         assert!(!is_valid_null_atom(b" ull    "));
+        assert!(is_valid_null_atom(b"null}"));
     }
 }
@@ -0,0 +1,407 @@
+use crate::value::{borrowed, owned};
+use crate::{
+    BorrowedValue, Buffers, Deserializer, Error, ErrorType, Limit, Limits, OwnedValue, Result,
+};
+#[cfg(feature = "serde_impl")]
+use serde_ext::Deserialize;
+use std::convert::TryFrom;
+use std::mem;
+
+/// How the structural indexes for the next document are found
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    /// stage 1 ran over the remaining input, the indexes for the next
+    /// document are taken from that run
+    Batch,
+    /// stage 1 has to be (re-)run over the remaining input
+    Resync,
+    /// stage 1 failed for the remaining input, so each line is handled on
+    /// it's own until a line fails in stage 1 as well
+    PerLine,
+}
+
+/// A stream of newline delimited JSON documents (also known as NDJSON or
+/// JSON Lines).
+///
+/// The structural indexes are found in one pass over the whole input and
+/// split up between the documents afterwards. Each document is returned
+/// with the byte offset it starts at in the input. An invalid document only
+/// results in an error for that document, the stream continues with the
+/// next line. Empty lines are skipped.
+///
+/// ```
+/// use simd_json::{DocumentStream, ValueTrait};
+///
+/// let mut d = b"{\"id\": 1}\n{\"id\": \n{\"id\": 3}\n".to_vec();
+/// let mut stream = DocumentStream::new(&mut d);
+///
+/// let (offset, v) = stream.next_owned().unwrap();
+/// assert_eq!(offset, 0);
+/// assert_eq!(v.unwrap()["id"], 1);
+/// let (offset, v) = stream.next_owned().unwrap();
+/// assert_eq!(offset, 10);
+/// assert!(v.is_err());
+/// let (offset, v) = stream.next_owned().unwrap();
+/// assert_eq!(offset, 18);
+/// assert_eq!(v.unwrap()["id"], 3);
+/// assert!(stream.next_owned().is_none());
+/// ```
+pub struct DocumentStream<'de> {
+    rest: &'de mut [u8],
+    offset: usize,
+    mode: Mode,
+    /// structural indexes of the last stage 1 run, relative to `run_start`
+    structural_indexes: Vec<u32>,
+    run_start: usize,
+    next_structural: usize,
+    buffers: Buffers,
+    limits: Limits,
+}
+
+impl<'de> DocumentStream<'de> {
+    /// Creates a new stream over the documents in `input`. As with
+    /// `to_borrowed_value` the input is rewritten to de-escape strings.
+    pub fn new(input: &'de mut [u8]) -> Self {
+        Self {
+            rest: input,
+            offset: 0,
+            mode: Mode::Resync,
+            structural_indexes: Vec::new(),
+            run_start: 0,
+            next_structural: 0,
+            buffers: Buffers::default(),
+            limits: Limits::default(),
+        }
+    }
+
+    /// Sets the limits each document is checked against, see `Limits`.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Returns the byte offset and value of the next document as a
+    /// `BorrowedValue`, `None` once the input is exhausted.
+    pub fn next_borrowed(&mut self) -> Option<(usize, Result<BorrowedValue<'de>>)> {
+        let (offset, de) = self.next_document()?;
+        let value = de.and_then(|de| borrowed::from_deserializer(de, &mut self.buffers));
+        Some((offset, value))
+    }
+
+    /// Returns the byte offset and value of the next document as an
+    /// `OwnedValue`, `None` once the input is exhausted.
+    pub fn next_owned(&mut self) -> Option<(usize, Result<OwnedValue>)> {
+        let (offset, de) = self.next_document()?;
+        let value = de.and_then(|de| owned::from_deserializer(de, &mut self.buffers));
+        Some((offset, value))
+    }
+
+    /// Returns the byte offset of the next document and deserializes it
+    /// using serde, `None` once the input is exhausted.
+    #[cfg(feature = "serde_impl")]
+    pub fn next_deserialize<T>(&mut self) -> Option<(usize, Result<T>)>
+    where
+        T: Deserialize<'de>,
+    {
+        let (offset, de) = self.next_document()?;
+        let value = de.and_then(|mut de| {
+            let value = T::deserialize(&mut de).map_err(|e| de.locate(e));
+            self.buffers = de.into_buffers();
+            value
+        });
+        Some((offset, value))
+    }
+
+    /// Splits off the next non empty line and sets up a deserializer for it
+    fn next_document(&mut self) -> Option<(usize, Result<Deserializer<'de>>)> {
+        loop {
+            if self.rest.is_empty() {
+                return None;
+            }
+            if self.mode == Mode::Resync {
+                self.resync();
+            }
+            let line_len = self
+                .rest
+                .iter()
+                .position(|c| *c == b'\n')
+                .unwrap_or(self.rest.len());
+            let first = if let Some(first) = self.rest[..line_len].iter().position(|c| !is_ws(*c)) {
+                first
+            } else {
+                self.advance(line_len);
+                continue;
+            };
+
+            if self.mode == Mode::Batch && !self.batch_in_sync(first) {
+                if self.run_start == self.offset {
+                    self.mode = Mode::PerLine;
+                } else {
+                    // The last run went out of sync, this happens after a
+                    // document with an unterminated string.
+                    self.mode = Mode::Resync;
+                    continue;
+                }
+            }
+
+            let offset = self.offset;
+            let line = self.advance(line_len);
+            let de = match self.mode {
+                Mode::Batch => {
+                    if let Err(e) = self.take_batch_indexes(offset, line_len) {
+                        Err(e)
+                    } else {
+                        Deserializer::from_stage1(line, &mut self.buffers, &self.limits)
+                    }
+                }
+                _ => {
+                    if let Err(t) = Deserializer::find_structural_bits_padded(
+                        line,
+                        &mut self.buffers.structural_indexes,
+                    ) {
+                        // This line failed in stage 1, chances are that it is
+                        // what broke the run over all of the input.
+                        self.mode = Mode::Resync;
+                        Err(Deserializer::stage1_error(line, t))
+                    } else {
+                        Deserializer::from_stage1(line, &mut self.buffers, &self.limits)
+                    }
+                }
+            };
+            return Some((offset, de));
+        }
+    }
+
+    /// Runs stage 1 over the remaining input
+    fn resync(&mut self) {
+        self.run_start = self.offset;
+        self.next_structural = 1;
+        let res =
+            Deserializer::find_structural_bits_padded(self.rest, &mut self.structural_indexes);
+        self.mode = if res.is_ok() {
+            Mode::Batch
+        } else {
+            Mode::PerLine
+        };
+    }
+
+    /// Checks that the next structural index of the current run is the
+    /// first byte of the line we're looking at.
+    fn batch_in_sync(&self, first: usize) -> bool {
+        match u32::try_from(self.offset - self.run_start + first) {
+            Ok(first) => self.structural_indexes.get(self.next_structural) == Some(&first),
+            Err(_) => false,
+        }
+    }
+
+    /// Copies the indexes of the line starting at `offset` into the
+    /// deserializer buffers, making them relative to the line. Structural
+    /// indexes are `u32` so lines reaching past 4 GiB into the run are
+    /// rejected.
+    fn take_batch_indexes(&mut self, offset: usize, line_len: usize) -> Result<()> {
+        let too_large = || Error::generic(ErrorType::LimitExceeded(Limit::DocumentSize));
+        let start = stry!(u32::try_from(offset - self.run_start).map_err(|_| too_large()));
+        let len = stry!(u32::try_from(line_len).map_err(|_| too_large()));
+        let end = stry!(start.checked_add(len).ok_or_else(too_large));
+        let indexes = &mut self.buffers.structural_indexes;
+        indexes.clear();
+        indexes.push(0);
+        while let Some(idx) = self.structural_indexes.get(self.next_structural) {
+            if *idx >= end {
+                break;
+            }
+            indexes.push(*idx - start);
+            self.next_structural += 1;
+        }
+        Ok(())
+    }
+
+    /// Splits off the next `len` bytes of the input and the newline
+    /// following them.
+    fn advance(&mut self, len: usize) -> &'de mut [u8] {
+        let rest = mem::take(&mut self.rest);
+        let (line, rest) = rest.split_at_mut(len);
+        let rest = if rest.is_empty() {
+            rest
+        } else {
+            rest.split_at_mut(1).1
+        };
+        self.offset += line.len() + 1;
+        self.rest = rest;
+        line
+    }
+}
+
+impl<'de> Iterator for DocumentStream<'de> {
+    type Item = (usize, Result<BorrowedValue<'de>>);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_borrowed()
+    }
+}
+
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+fn is_ws(c: u8) -> bool {
+    c == b' ' || c == b'\t' || c == b'\r' || c == b'\n'
+}
+
+#[cfg(test)]
+mod test {
+    use super::DocumentStream;
+    use crate::{to_owned_value, ErrorType, Limit, OwnedValue, ValueTrait};
+
+    fn collect(input: &str) -> Vec<(usize, Option<OwnedValue>)> {
+        let mut d = input.as_bytes().to_vec();
+        let mut stream = DocumentStream::new(&mut d);
+        let mut res = Vec::new();
+        while let Some((offset, v)) = stream.next_owned() {
+            res.push((offset, v.ok()));
+        }
+        res
+    }
+
+    fn value(s: &str) -> Option<OwnedValue> {
+        let mut d = s.as_bytes().to_vec();
+        Some(to_owned_value(&mut d).expect("invalid test data"))
+    }
+
+    #[test]
+    fn lines() {
+        let input = "{\"a\": 1}\n\n  [1, \"b\\n\"]  \r\n\"str\"\n42\ntrue\nnull";
+        assert_eq!(
+            collect(input),
+            vec![
+                (0, value("{\"a\": 1}")),
+                (10, value("[1, \"b\\n\"]")),
+                (26, value("\"str\"")),
+                (32, value("42")),
+                (35, value("true")),
+                (40, value("null")),
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_dont_abort() {
+        let input = "{\"a\": 1}\n{\"a\": }\n[1, 2\n{} {}\n{\"a\": 2}\n";
+        assert_eq!(
+            collect(input),
+            vec![
+                (0, value("{\"a\": 1}")),
+                (9, None),
+                (17, None),
+                (23, None),
+                (29, value("{\"a\": 2}")),
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_string() {
+        // an odd number of quotes fails stage 1 for the whole input
+        let input = "[1]\n{\"a\": \"b}\n[2]\n{\"c\": \"d\"}\n";
+        assert_eq!(
+            collect(input),
+            vec![
+                (0, value("[1]")),
+                (4, None),
+                (14, value("[2]")),
+                (18, value("{\"c\": \"d\"}")),
+            ]
+        );
+        // an even one leaves stage 1 out of sync
+        let input = "[1]\n\"a\n[2]\n\"b\n[3]\n";
+        assert_eq!(
+            collect(input),
+            vec![
+                (0, value("[1]")),
+                (4, None),
+                (7, value("[2]")),
+                (11, None),
+                (14, value("[3]"))
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let mut d = b"[1]\n\"\xff\"\n[2]".to_vec();
+        let stream = DocumentStream::new(&mut d);
+        let res: Vec<_> = stream.map(|(o, v)| (o, v.is_ok())).collect();
+        assert_eq!(res, vec![(0, true), (4, false), (8, true)]);
+    }
+
+    #[test]
+    fn borrowed() {
+        let mut d = b"{\"a\": \"snot\"}\n{\"a\": \"badger\"}".to_vec();
+        let values: Vec<_> = DocumentStream::new(&mut d)
+            .map(|(_, v)| v.expect("valid document"))
+            .collect();
+        assert_eq!(values[0]["a"], "snot");
+        assert_eq!(values[1]["a"], "badger");
+    }
+
+    #[cfg(feature = "serde_impl")]
+    #[test]
+    fn deserialize() {
+        use serde::Deserialize;
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Line<'a> {
+            level: &'a str,
+            code: u32,
+        }
+        let mut d = b"{\"level\": \"info\", \"code\": 1}\n{\"level\": 2}\n{\"level\": \"warn\", \"code\": 3}\n".to_vec();
+        let mut stream = DocumentStream::new(&mut d);
+        let (offset, line) = stream.next_deserialize::<Line>().expect("first line");
+        assert_eq!(offset, 0);
+        assert_eq!(
+            line.expect("valid line"),
+            Line {
+                level: "info",
+                code: 1
+            }
+        );
+        let (_, line) = stream.next_deserialize::<Line>().expect("second line");
+        assert!(line.is_err());
+        let (offset, line) = stream.next_deserialize::<Line>().expect("third line");
+        assert_eq!(offset, 42);
+        assert_eq!(
+            line.expect("valid line"),
+            Line {
+                level: "warn",
+                code: 3
+            }
+        );
+        assert!(stream.next_deserialize::<Line>().is_none());
+    }
+
+    #[test]
+    fn large_batch() {
+        let mut input = String::new();
+        for i in 0..1000 {
+            input.push_str(&format!("{{\"id\": {}, \"tags\": [\"a\", \"b\"]}}\n", i));
+        }
+        let mut d = input.into_bytes();
+        let mut stream = DocumentStream::new(&mut d);
+        let mut n = 0;
+        while let Some((_, v)) = stream.next_owned() {
+            assert_eq!(v.expect("valid document")["id"].as_i64(), Some(n));
+            n += 1;
+        }
+        assert_eq!(n, 1000);
+    }
+
+    #[test]
+    fn batch_past_u32() {
+        let mut d = b"[1]".to_vec();
+        let mut stream = DocumentStream::new(&mut d);
+        // Structural indexes can't address lines that end past `u32::MAX`
+        let e = stream
+            .take_batch_indexes(u32::max_value() as usize, 1)
+            .expect_err("taken");
+        assert_eq!(
+            e.error_type(),
+            &ErrorType::LimitExceeded(Limit::DocumentSize)
+        );
+        assert!(stream.take_batch_indexes(0, 3).is_ok());
+    }
+}
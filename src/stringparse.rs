@@ -71,3 +71,80 @@ pub(crate) fn handle_unicode_codepoint(
     let offset: usize = codepoint_to_utf8(code_point, dst_ptr);
     Ok((offset, src_offset))
 }
+
+/// Sets the high bit of the bytes of `w` that are a `"` or a `\`. Bytes
+/// above a match may be set too so only the lowest one can be relied on.
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+fn quote_or_backslash(w: u64) -> u64 {
+    const ONES: u64 = 0x0101_0101_0101_0101;
+    const HIGH: u64 = 0x8080_8080_8080_8080;
+    let q = w ^ (ONES * u64::from(b'"'));
+    let b = w ^ (ONES * u64::from(b'\\'));
+    ((q.wrapping_sub(ONES) & !q) | (b.wrapping_sub(ONES) & !b)) & HIGH
+}
+
+/// Checks the escapes of the string whose opening quote is at `src[0]`
+/// without unescaping it, accepting what `handle_unicode_codepoint` does.
+/// Returns the length of the unescaped string or `None` if an escape is
+/// invalid. Stage 1 already made sure the string is terminated.
+pub(crate) fn unescaped_len(src: &[u8]) -> Option<usize> {
+    // Missing digits are treated like invalid ones
+    let hex = |i: usize| src.get(i..i + 4).map_or(0xffff_ffff, hex_to_u32_nocheck);
+    let mut len = 0;
+    let mut i = 1;
+    loop {
+        // Skip ahead to the next quote or backslash a word at a time
+        while let Some(w) = src.get(i..i + 8) {
+            #[allow(clippy::cast_ptr_alignment)]
+            let w = u64::from_le(unsafe { std::ptr::read_unaligned(w.as_ptr() as *const u64) });
+            let found = quote_or_backslash(w);
+            if found != 0 {
+                let n = (found.trailing_zeros() / 8) as usize;
+                i += n;
+                len += n;
+                break;
+            }
+            i += 8;
+            len += 8;
+        }
+        match src.get(i) {
+            Some(b'"') => return Some(len),
+            Some(b'\\') => {
+                let c = src.get(i + 1).copied().unwrap_or(0);
+                if c == b'u' {
+                    let code_point = hex(i + 2);
+                    i += 6;
+                    if (0xd800..0xdc00).contains(&code_point) {
+                        if src.get(i) != Some(&b'\\') || src.get(i + 1) != Some(&b'u') {
+                            return None;
+                        }
+                        let code_point_2 = hex(i + 2);
+                        if (code_point_2 >> 16) != 0 || code_point_2 < 0xdc00 {
+                            return None;
+                        }
+                        i += 6;
+                        len += 4;
+                    } else if (code_point >> 16) != 0 {
+                        return None;
+                    } else if code_point < 0x80 {
+                        len += 1;
+                    } else if code_point < 0x800 {
+                        len += 2;
+                    } else {
+                        len += 3;
+                    }
+                } else if ESCAPE_MAP[c as usize] == 0 {
+                    return None;
+                } else {
+                    i += 2;
+                    len += 1;
+                }
+            }
+            Some(_) => {
+                i += 1;
+                len += 1;
+            }
+            None => return None,
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use crate::numberparse::Number;
+use crate::{Deserializer, ErrorType, Result};
+use std::borrow::Cow;
+
+/// A single token of a document, see `Tokens`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token<'de> {
+    /// An object with the given number of fields starts
+    BeginObject(usize),
+    /// The current object ends
+    EndObject,
+    /// An array with the given number of elements starts
+    BeginArray(usize),
+    /// The current array ends
+    EndArray,
+    /// The key of the next field in an object
+    Key(Cow<'de, str>),
+    /// A string value
+    String(Cow<'de, str>),
+    /// A number, see `ParsedNumber`
+    Number(Number),
+    /// `true` or `false`
+    Bool(bool),
+    /// `null`
+    Null,
+}
+
+/// A pull based iterator over the tokens of a document.
+///
+/// The document is checked with stage 1 and 2 up front, strings and numbers
+/// are only decoded as their tokens are pulled. After an error the iterator
+/// returns `None`.
+///
+/// ```
+/// use simd_json::{Token, Tokens};
+///
+/// let mut d = br#"{"user": "snot", "password": {"hash": "x"}, "id": 1}"#.to_vec();
+/// let mut tokens = Tokens::from_slice(&mut d).unwrap();
+/// let mut out = Vec::new();
+/// while let Some(t) = tokens.next() {
+///     let t = t.unwrap();
+///     if t == Token::Key("password".into()) {
+///         tokens.skip_value().unwrap();
+///     } else {
+///         out.push(t);
+///     }
+/// }
+/// assert_eq!(out.len(), 6);
+/// assert_eq!(out[2], Token::String("snot".into()));
+/// ```
+pub struct Tokens<'de> {
+    de: Deserializer<'de>,
+    failed: bool,
+}
+
+impl<'de> Tokens<'de> {
+    /// Finds the structure of `input`. This function will rewrite the slice
+    /// to de-escape strings.
+    pub fn from_slice(input: &'de mut [u8]) -> Result<Self> {
+        Deserializer::from_slice(input).map(|de| Self { de, failed: false })
+    }
+
+    /// Byte offset in the input of the token returned last
+    pub fn offset(&self) -> usize {
+        self.de.iidx
+    }
+
+    #[cfg_attr(not(feature = "no-inline"), inline(always))]
+    fn done(&self) -> bool {
+        self.failed || self.de.idx + 1 >= self.de.structural_indexes.len()
+    }
+
+    /// Moves past `,` and `:` so the next structural starts a token
+    #[cfg_attr(not(feature = "no-inline"), inline(always))]
+    fn skip_separators(&mut self) {
+        while !self.done() {
+            match self.de.peek_() {
+                b',' | b':' => self.de.skip(),
+                _ => return,
+            }
+        }
+    }
+
+    /// Skips the value the next token would start, for example the value
+    /// of the field whose `Key` was just returned. Arrays and objects are
+    /// skipped as a whole without returning their tokens, only the grammar
+    /// of the numbers and strings in them is checked. Does nothing if the
+    /// next token ends a container.
+    pub fn skip_value(&mut self) -> Result<()> {
+        self.skip_separators();
+        if !self.done() {
+            match self.de.peek_() {
+                b']' | b'}' => (),
+                _ => {
+                    let res = self.de.skip_value().map_err(|e| self.de.locate(e));
+                    self.failed = res.is_err();
+                    return res;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn token(&mut self) -> Result<Token<'de>> {
+        let root = self.de.idx == 0;
+        Ok(match self.de.next_() {
+            b'{' => Token::BeginObject(self.de.count_elements()),
+            b'}' => Token::EndObject,
+            b'[' => Token::BeginArray(self.de.count_elements()),
+            b']' => Token::EndArray,
+            b'"' => {
+                // Only keys are followed by a `:`
+                let key = !self.done() && self.de.peek_() == b':';
+                let s = stry!(self.de.parse_str_()).into_cow();
+                if key {
+                    Token::Key(s)
+                } else {
+                    Token::String(s)
+                }
+            }
+            b'-' if root => Token::Number(stry!(self.de.parse_number_root(true))),
+            b'-' => Token::Number(stry!(self.de.parse_number_(true))),
+            b'0'..=b'9' if root => Token::Number(stry!(self.de.parse_number_root(false))),
+            b'0'..=b'9' => Token::Number(stry!(self.de.parse_number_(false))),
+            b't' => Token::Bool(true),
+            b'f' => Token::Bool(false),
+            b'n' => Token::Null,
+            _c => return Err(self.de.error(ErrorType::UnexpectedCharacter)),
+        })
+    }
+}
+
+impl<'de> Iterator for Tokens<'de> {
+    type Item = Result<Token<'de>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.skip_separators();
+        if self.done() {
+            return None;
+        }
+        let t = self.token().map_err(|e| self.de.locate(e));
+        self.failed = t.is_err();
+        Some(t)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Token, Tokens};
+    use crate::ParsedNumber;
+
+    fn assert_tokens(d: &str, expected: &[Token]) {
+        let mut d = d.as_bytes().to_vec();
+        let ts: Vec<_> = Tokens::from_slice(&mut d)
+            .expect("valid json")
+            .collect::<crate::Result<_>>()
+            .expect("tokens");
+        assert_eq!(ts, expected);
+    }
+
+    #[test]
+    fn stream() {
+        assert_tokens(
+            r#"{"a": [1, -2.5, "x\ny"], "b\"": {"c": null, "d": []}, "e": true}"#,
+            &[
+                Token::BeginObject(3),
+                Token::Key("a".into()),
+                Token::BeginArray(3),
+                Token::Number(ParsedNumber::I64(1)),
+                Token::Number(ParsedNumber::F64(-2.5)),
+                Token::String("x\ny".into()),
+                Token::EndArray,
+                Token::Key("b\"".into()),
+                Token::BeginObject(2),
+                Token::Key("c".into()),
+                Token::Null,
+                Token::Key("d".into()),
+                Token::BeginArray(0),
+                Token::EndArray,
+                Token::EndObject,
+                Token::Key("e".into()),
+                Token::Bool(true),
+                Token::EndObject,
+            ],
+        );
+        assert_tokens("-3", &[Token::Number(ParsedNumber::I64(-3))]);
+        assert_tokens(r#""s""#, &[Token::String("s".into())]);
+        assert_tokens(
+            r#"[":"]"#,
+            &[
+                Token::BeginArray(1),
+                Token::String(":".into()),
+                Token::EndArray,
+            ],
+        );
+    }
+
+    #[test]
+    fn offsets() {
+        let mut d = br#" {"a" : [ 1, "b" ] } "#.to_vec();
+        let mut ts = Tokens::from_slice(&mut d).expect("valid json");
+        let mut offsets = Vec::new();
+        while let Some(t) = ts.next() {
+            t.expect("token");
+            offsets.push(ts.offset());
+        }
+        assert_eq!(offsets, vec![1, 2, 8, 10, 13, 17, 19]);
+    }
+
+    #[test]
+    fn skip_value() {
+        let mut d = br#"{"a": {"x": [1, 2]}, "b": 1, "c": [], "d": 2}"#.to_vec();
+        let mut ts = Tokens::from_slice(&mut d).expect("valid json");
+        let mut out = Vec::new();
+        while let Some(t) = ts.next() {
+            let t = t.expect("token");
+            if let Token::Key(k) = &t {
+                if k != "b" {
+                    ts.skip_value().expect("skipped");
+                }
+            }
+            out.push(t);
+        }
+        assert_eq!(
+            out,
+            vec![
+                Token::BeginObject(4),
+                Token::Key("a".into()),
+                Token::Key("b".into()),
+                Token::Number(ParsedNumber::I64(1)),
+                Token::Key("c".into()),
+                Token::Key("d".into()),
+                Token::EndObject,
+            ]
+        );
+
+        // Nothing to skip at the end of a container
+        let mut d = br#"[[], 1]"#.to_vec();
+        let mut ts = Tokens::from_slice(&mut d).expect("valid json");
+        assert_eq!(ts.next().and_then(|t| t.ok()), Some(Token::BeginArray(2)));
+        assert_eq!(ts.next().and_then(|t| t.ok()), Some(Token::BeginArray(0)));
+        ts.skip_value().expect("skipped");
+        assert_eq!(ts.next().and_then(|t| t.ok()), Some(Token::EndArray));
+        ts.skip_value().expect("skipped");
+        assert_eq!(ts.next().and_then(|t| t.ok()), Some(Token::EndArray));
+        ts.skip_value().expect("skipped");
+        assert!(ts.next().is_none());
+    }
+
+    #[test]
+    fn errors() {
+        let mut d = br#"[1, 2"#.to_vec();
+        assert!(Tokens::from_slice(&mut d).is_err());
+        let mut d = br#"[1, 2.x, 3]"#.to_vec();
+        let ts = Tokens::from_slice(&mut d).expect("structure is valid");
+        let ts: Vec<_> = ts.collect();
+        assert_eq!(ts.len(), 3);
+        assert!(ts[2].is_err());
+
+        // skipped values are checked too
+        let mut d = br#"{"a": [1, 2.x], "b": 1}"#.to_vec();
+        let mut ts = Tokens::from_slice(&mut d).expect("structure is valid");
+        assert_eq!(ts.next().and_then(|t| t.ok()), Some(Token::BeginObject(2)));
+        assert_eq!(ts.next().and_then(|t| t.ok()), Some(Token::Key("a".into())));
+        assert!(ts.skip_value().is_err());
+        assert!(ts.next().is_none());
+    }
+}
@@ -0,0 +1,187 @@
+use crate::{Buffers, Deserializer, Input, Limits, Result};
+
+/// Statistics about a document gathered by `validate_with_stats`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stats {
+    /// Deepest nesting of arrays and objects, `0` for a scalar document
+    pub max_depth: usize,
+    /// Number of values in the document including nested ones, object keys
+    /// aren't counted
+    pub elements: usize,
+}
+
+/// Checks that `input` is valid JSON without building any values from it.
+///
+/// Besides the UTF-8 and structural checks the escapes of strings and the
+/// grammar of numbers are checked without decoding them, the input is never
+/// written to. Validating doesn't recurse so no depth limit is applied, use
+/// `Parser::validate` to enforce `Limits`.
+///
+/// ```
+/// assert!(simd_json::validate(br#"{"a": [1, 2.5e3, "\u00e9"]}"#).is_ok());
+/// assert!(simd_json::validate(br#"{"a": [1, 2.x]}"#).is_err());
+/// assert!(simd_json::validate(br#"["\x"]"#).is_err());
+/// ```
+pub fn validate(input: &[u8]) -> Result<()> {
+    validate_with_stats(input).map(|_| ())
+}
+
+/// Same as `validate` but also returns statistics about the document.
+///
+/// ```
+/// use simd_json::Stats;
+///
+/// let stats = simd_json::validate_with_stats(br#"{"a": [1, {"b": null}]}"#).unwrap();
+/// assert_eq!(stats, Stats { max_depth: 3, elements: 5 });
+/// ```
+pub fn validate_with_stats(input: &[u8]) -> Result<Stats> {
+    let limits = Limits {
+        max_depth: usize::max_value(),
+        ..Limits::default()
+    };
+    validate_with_buffers(input, &mut Buffers::default(), &limits)
+}
+
+/// Same as `validate_with_stats` but re-uses the scratch buffers passed in
+/// and enforces the given limits.
+pub(crate) fn validate_with_buffers(
+    input: &[u8],
+    buffers: &mut Buffers,
+    limits: &Limits,
+) -> Result<Stats> {
+    stry!(Deserializer::check_document_size(input, limits));
+    if let Err(t) =
+        Deserializer::find_structural_bits_padded(input, &mut buffers.structural_indexes)
+    {
+        return Err(Deserializer::stage1_error(input, t));
+    }
+    let de = stry!(Deserializer::from_stage1_(
+        Input::from_ref(input),
+        buffers,
+        limits
+    ));
+    let res = check_values(&de).map_err(|e| de.locate(e));
+    *buffers = de.into_buffers();
+    res
+}
+
+/// Walks the structural indexes once, checking every string and number
+/// without decoding it. The number of elements of each array and object is
+/// already in `counts`.
+fn check_values(de: &Deserializer) -> Result<Stats> {
+    let input = de.input.as_slice();
+    let mut depth = 0;
+    let mut stats = Stats {
+        max_depth: 0,
+        elements: 1,
+    };
+    for idx in 1..de.structural_indexes.len() {
+        match unsafe { *input.get_unchecked(*de.structural_indexes.get_unchecked(idx) as usize) } {
+            b'[' | b'{' => {
+                depth += 1;
+                stats.max_depth = stats.max_depth.max(depth);
+                stats.elements += unsafe { de.counts.get_unchecked(idx).0 as usize };
+            }
+            b']' | b'}' => depth -= 1,
+            _ => stry!(de.check_scalar(idx)),
+        }
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{validate, validate_with_stats, Stats};
+    use crate::ErrorType;
+
+    #[test]
+    fn valid() {
+        for d in &[
+            "1",
+            "-0.5e-3",
+            r#""snot""#,
+            r#""😀\n""#,
+            "[]",
+            "{}",
+            r#"{"a": {"b": [true, false, null, -1, 18446744073709551615]}}"#,
+            // Escapes before, on and after word boundaries
+            r#"["0123456789abcdef\"\\\u00e9 0123456789\n"]"#,
+            "[12345678901234567890.12345678901234567890e-12345678]",
+        ] {
+            assert!(validate(d.as_bytes()).is_ok(), "{}", d);
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        for d in &[
+            "",
+            "01",
+            "-",
+            "[1.]",
+            "[1e]",
+            r#"["\x"]"#,
+            r#"["\ud800"]"#,
+            r#"{"\q": 1}"#,
+            "[1, 2",
+            "[1] 2",
+            "[tru]",
+            "\"\u{1}\"",
+            r#"["0123456789abcdef\x"]"#,
+            r#"["0123456789abcdef\u00"]"#,
+            "[1234567890123456.]",
+        ] {
+            assert!(validate(d.as_bytes()).is_err(), "{}", d);
+        }
+        assert_eq!(
+            validate(b"[1, 2.x]").map_err(|e| e.error_type().clone()),
+            Err(ErrorType::InvalidNumber)
+        );
+        assert!(validate(&[b'"', 0xff, b'"']).is_err());
+    }
+
+    #[test]
+    fn stats() {
+        let stats = |d: &str| validate_with_stats(d.as_bytes()).expect("valid");
+        assert_eq!(
+            stats("42"),
+            Stats {
+                max_depth: 0,
+                elements: 1
+            }
+        );
+        assert_eq!(
+            stats(r#"[[], [[]], {"a": "b", "c": [1, 2]}]"#),
+            Stats {
+                max_depth: 3,
+                elements: 9
+            }
+        );
+        assert_eq!(
+            stats(r#"{"k": "v"}"#),
+            Stats {
+                max_depth: 1,
+                elements: 2
+            }
+        );
+    }
+
+    #[test]
+    fn deeply_nested() {
+        for depth in &[crate::DEFAULT_MAX_DEPTH + 1, 1000, 100_000] {
+            let d = format!("{}{}", "[".repeat(*depth), "]".repeat(*depth));
+            let stats = validate_with_stats(d.as_bytes()).expect("valid");
+            assert_eq!(stats.max_depth, *depth);
+        }
+        let d = format!("{}{}", "[".repeat(1000), "]".repeat(999));
+        assert!(validate(d.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn input_untouched() {
+        let d = r#"{"a\nb": "cé\"d"}"#.as_bytes().to_vec();
+        let copy = d.clone();
+        validate(&d).expect("valid");
+        assert_eq!(d, copy);
+    }
+}
@@ -16,10 +16,18 @@ pub mod borrowed;
 pub(crate) mod generator;
 /// Owned, lifetimeless version of the value for times when lifetimes are to be avoided
 pub mod owned;
+mod pointer;
 use std::convert::TryInto;
 
-pub use self::borrowed::{to_value as to_borrowed_value, Value as BorrowedValue};
-pub use self::owned::{to_value as to_owned_value, Value as OwnedValue};
+pub use self::borrowed::{
+    to_value as to_borrowed_value, to_value_from_ref as to_borrowed_value_from_ref,
+    to_value_projected as to_borrowed_value_projected, Value as BorrowedValue,
+};
+pub use self::owned::{
+    to_value as to_owned_value, to_value_from_ref as to_owned_value_from_ref,
+    to_value_projected as to_owned_value_projected, Value as OwnedValue,
+};
+pub use self::pointer::PointerError;
 use halfbrown::HashMap;
 use std::borrow::Borrow;
 use std::hash::Hash;
@@ -31,10 +39,16 @@ pub enum ValueType {
     Null,
     /// a boolean
     Bool,
-    /// an integer type
+    /// a signed integer type
     I64,
+    /// an unsigned integer type, only used for values that don't fit in an i64
+    U64,
     /// a float type
     F64,
+    /// a number that can't be represented by `I64`, `U64` or `F64` without
+    /// loss, kept in its textual form
+    #[cfg(feature = "arbitrary_precision")]
+    Number,
     /// a string type
     String,
     /// an array
@@ -111,6 +125,92 @@ pub trait ValueTrait:
         self.as_array_mut().and_then(|a| a.get_mut(i))
     }
 
+    /// Gets a ref to a value based on a JSON Pointer (RFC 6901) such as
+    /// `/statuses/0/id`, the empty pointer references the value itself.
+    fn pointer(&self, pointer: &str) -> Result<&Self, PointerError>
+    where
+        Self::Key: Borrow<str> + Hash + Eq,
+    {
+        let mut v = self;
+        for token in stry!(pointer::tokens(pointer)) {
+            v = stry!(pointer::step(v, &token));
+        }
+        Ok(v)
+    }
+
+    /// Same as `pointer` but returns a mutable ref instead
+    fn pointer_mut(&mut self, pointer: &str) -> Result<&mut Self, PointerError>
+    where
+        Self::Key: Borrow<str> + Hash + Eq,
+    {
+        let mut v = self;
+        for token in stry!(pointer::tokens(pointer)) {
+            v = stry!(pointer::step_mut(v, &token));
+        }
+        Ok(v)
+    }
+
+    /// Inserts a value at the location referenced by a JSON Pointer. Object
+    /// keys are inserted or replaced, returning the replaced value, array
+    /// elements are inserted at the index with `-` appending to the end.
+    fn pointer_insert(&mut self, pointer: &str, value: Self) -> Result<Option<Self>, PointerError>
+    where
+        Self::Key: Borrow<str> + Hash + Eq + From<String>,
+    {
+        let mut tokens = stry!(pointer::tokens(pointer));
+        let last = if let Some(last) = tokens.pop() {
+            last
+        } else {
+            return Ok(Some(std::mem::replace(self, value)));
+        };
+        let parent = stry!(tokens
+            .iter()
+            .try_fold(self, |v, token| pointer::step_mut(v, token)));
+        if let Some(o) = parent.as_object_mut() {
+            Ok(o.insert(last.into_owned().into(), value))
+        } else if let Some(a) = parent.as_array_mut() {
+            let i = if last == "-" {
+                a.len()
+            } else {
+                stry!(pointer::index(&last))
+            };
+            if i > a.len() {
+                return Err(PointerError::NotFound);
+            }
+            a.insert(i, value);
+            Ok(None)
+        } else {
+            Err(PointerError::NotAContainer)
+        }
+    }
+
+    /// Removes the value referenced by a JSON Pointer and returns it
+    fn pointer_remove(&mut self, pointer: &str) -> Result<Self, PointerError>
+    where
+        Self::Key: Borrow<str> + Hash + Eq,
+    {
+        let mut tokens = stry!(pointer::tokens(pointer));
+        let last = stry!(tokens.pop().ok_or(PointerError::Root));
+        let parent = stry!(tokens
+            .iter()
+            .try_fold(self, |v, token| pointer::step_mut(v, token)));
+        if let Some(o) = parent.as_object_mut() {
+            o.remove(last.as_ref()).ok_or(PointerError::NotFound)
+        } else if let Some(a) = parent.as_array_mut() {
+            if last == "-" {
+                return Err(PointerError::NotFound);
+            }
+            let i = stry!(pointer::index(&last));
+            if i < a.len() {
+                Ok(a.remove(i))
+            } else {
+                Err(PointerError::NotFound)
+            }
+        } else {
+            Err(PointerError::NotAContainer)
+        }
+    }
+
     /// Returns the type of the current Valye
     #[deprecated(since = "0.1.21", note = "please use value_type instead")]
     fn kind(&self) -> ValueType {
@@ -132,7 +232,9 @@ pub trait ValueTrait:
 
     /// Tries to represent the value as an i128
     fn as_i128(&self) -> Option<i128> {
-        self.as_i64().and_then(|u| u.try_into().ok())
+        self.as_i64()
+            .map(i128::from)
+            .or_else(|| self.as_u64().map(i128::from))
     }
     /// returns true if the current value can be represented as a i128
     fn is_i128(&self) -> bool {
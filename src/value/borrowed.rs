@@ -5,9 +5,10 @@ mod from;
 mod serialize;
 
 use crate::value::{ValueTrait, ValueType};
-use crate::{stry, unlikely, Deserializer, ErrorType, Result};
+use crate::{stry, unlikely, Buffers, Deserializer, ErrorType, Limits, Result};
 use halfbrown::HashMap;
 use std::borrow::Cow;
+use std::convert::TryInto;
 use std::fmt;
 use std::ops::Index;
 
@@ -23,7 +24,52 @@ pub type Object<'v> = HashMap<Cow<'v, str>, Value<'v>>;
 /// has the dame lifetime as the slice it was created from.
 pub fn to_value<'v>(s: &'v mut [u8]) -> Result<Value<'v>> {
     let de = stry!(Deserializer::from_slice(s));
-    BorrowDeserializer::from_deserializer(de).parse()
+    let mut de = BorrowDeserializer::from_deserializer(de);
+    let value = de.parse();
+    value.map_err(|e| de.de.locate(e))
+}
+
+/// Parses a slice of bytes into a Value dom without modifying it. Strings
+/// without escapes reference the input, strings with escapes are copied.
+pub fn to_value_from_ref<'v>(s: &'v [u8]) -> Result<Value<'v>> {
+    let de = stry!(Deserializer::from_slice_ref(s));
+    let mut de = BorrowDeserializer::from_deserializer(de);
+    let value = de.parse();
+    value.map_err(|e| de.de.locate(e))
+}
+
+/// Parses a slice of bytes into a Value dom keeping only the given keys of
+/// the top level object. The values of all other keys are skipped over
+/// without building anything from them, their numbers and strings are only
+/// checked for valid grammar and escapes.
+pub fn to_value_projected<'v>(s: &'v mut [u8], keys: &[&str]) -> Result<Value<'v>> {
+    let de = stry!(Deserializer::from_slice(s));
+    let mut de = BorrowDeserializer::from_deserializer(de);
+    let value = de.parse_projected(keys);
+    value.map_err(|e| de.de.locate(e))
+}
+
+/// Same as `to_value` but re-uses the scratch buffers passed in and hands
+/// them back afterwards, and enforces the given limits.
+pub(crate) fn to_value_with_buffers<'v>(
+    s: &'v mut [u8],
+    buffers: &mut Buffers,
+    limits: &Limits,
+) -> Result<Value<'v>> {
+    let de = stry!(Deserializer::from_slice_with_buffers(s, buffers, limits));
+    from_deserializer(de, buffers)
+}
+
+/// Builds a value from an already set up deserializer, handing its scratch
+/// buffers back afterwards.
+pub(crate) fn from_deserializer<'v>(
+    de: Deserializer<'v>,
+    buffers: &mut Buffers,
+) -> Result<Value<'v>> {
+    let mut de = BorrowDeserializer::from_deserializer(de);
+    let value = de.parse().map_err(|e| de.de.locate(e));
+    *buffers = de.de.into_buffers();
+    value
 }
 
 /// Borrowed JSON-DOM Value, consider using the `ValueTrait`
@@ -38,6 +84,12 @@ pub enum Value<'v> {
     F64(f64),
     /// integer type
     I64(i64),
+    /// unsigned integer type, used for integers larger then `i64::max_value()`
+    U64(u64),
+    /// number that doesn't fit into any of the above without loss of
+    /// precision, stored as the literal it was parsed from
+    #[cfg(feature = "arbitrary_precision")]
+    Number(Cow<'v, str>),
     /// string type
     String(Cow<'v, str>),
     /// array type
@@ -55,6 +107,8 @@ impl<'v> Value<'v> {
             use std::mem::transmute;
             transmute(match self {
                 Self::String(Cow::Borrowed(s)) => Self::String(Cow::Owned(s.to_owned())),
+                #[cfg(feature = "arbitrary_precision")]
+                Self::Number(Cow::Borrowed(n)) => Self::Number(Cow::Owned(n.to_owned())),
                 Self::Array(arr) => Self::Array(arr.into_iter().map(Value::into_static).collect()),
                 Self::Object(obj) => Self::Object(
                     obj.into_iter()
@@ -82,6 +136,9 @@ impl<'v> Value<'v> {
                 Self::Null => Self::Null,
                 Self::F64(v) => Self::F64(*v),
                 Self::I64(v) => Self::I64(*v),
+                Self::U64(v) => Self::U64(*v),
+                #[cfg(feature = "arbitrary_precision")]
+                Self::Number(n) => Self::Number(Cow::Owned(n.to_string())),
                 Self::Bool(v) => Self::Bool(*v),
             })
         }
@@ -97,6 +154,9 @@ impl<'v> ValueTrait for Value<'v> {
             Value::Bool(_) => ValueType::Bool,
             Value::F64(_) => ValueType::F64,
             Value::I64(_) => ValueType::I64,
+            Value::U64(_) => ValueType::U64,
+            #[cfg(feature = "arbitrary_precision")]
+            Value::Number(_) => ValueType::Number,
             Value::String(_) => ValueType::String,
             Value::Array(_) => ValueType::Array,
             Value::Object(_) => ValueType::Object,
@@ -120,6 +180,7 @@ impl<'v> ValueTrait for Value<'v> {
     fn as_i64(&self) -> Option<i64> {
         match self {
             Value::I64(i) => Some(*i),
+            Value::U64(u) => (*u).try_into().ok(),
             _ => None,
         }
     }
@@ -128,6 +189,7 @@ impl<'v> ValueTrait for Value<'v> {
         #[allow(clippy::cast_sign_loss)]
         match self {
             Value::I64(i) if *i >= 0 => Some(*i as u64),
+            Value::U64(u) => Some(*u),
             _ => None,
         }
     }
@@ -144,6 +206,9 @@ impl<'v> ValueTrait for Value<'v> {
         match self {
             Value::F64(i) => Some(*i),
             Value::I64(i) => Some(*i as f64),
+            Value::U64(u) => Some(*u as f64),
+            #[cfg(feature = "arbitrary_precision")]
+            Value::Number(n) => n.parse().ok(),
             _ => None,
         }
     }
@@ -198,6 +263,9 @@ impl<'v> fmt::Display for Value<'v> {
             Value::Null => write!(f, "null"),
             Value::Bool(b) => write!(f, "{}", b),
             Value::I64(n) => write!(f, "{}", n),
+            Value::U64(n) => write!(f, "{}", n),
+            #[cfg(feature = "arbitrary_precision")]
+            Value::Number(n) => write!(f, "{}", n),
             Value::F64(n) => write!(f, "{}", n),
             Value::String(s) => write!(f, "{}", s),
             Value::Array(a) => write!(f, "{:?}", a),
@@ -231,7 +299,7 @@ impl<'de> BorrowDeserializer<'de> {
     #[cfg_attr(not(feature = "no-inline"), inline(always))]
     pub fn parse(&mut self) -> Result<Value<'de>> {
         match self.de.next_() {
-            b'"' => self.de.parse_str_().map(Value::from),
+            b'"' => self.de.parse_str_().map(|s| Value::String(s.into_cow())),
             b'-' => self.de.parse_number_root(true).map(Value::from),
             b'0'..=b'9' => self.de.parse_number_root(false).map(Value::from),
             b'n' => Ok(Value::Null),
@@ -243,10 +311,36 @@ impl<'de> BorrowDeserializer<'de> {
         }
     }
 
+    /// Parses the document, if it is an object only `keys` are kept
+    pub fn parse_projected(&mut self, keys: &[&str]) -> Result<Value<'de>> {
+        if self.de.peek_() != b'{' {
+            return self.parse();
+        }
+        self.de.skip();
+        let es = self.de.count_elements();
+        let mut res = Object::with_capacity(es.min(keys.len()));
+        if unlikely!(es == 0) {
+            self.de.skip();
+            return Ok(Value::Object(res));
+        }
+        for _ in 0..es {
+            self.de.skip();
+            let key = stry!(self.de.parse_str_()).into_cow();
+            self.de.skip();
+            if keys.contains(&key.as_ref()) {
+                res.insert_nocheck(key, stry!(self.parse_value()));
+            } else {
+                stry!(self.de.skip_value());
+            }
+            self.de.skip();
+        }
+        Ok(Value::Object(res))
+    }
+
     #[cfg_attr(not(feature = "no-inline"), inline(always))]
     fn parse_value(&mut self) -> Result<Value<'de>> {
         match self.de.next_() {
-            b'"' => self.de.parse_str_().map(Value::from),
+            b'"' => self.de.parse_str_().map(|s| Value::String(s.into_cow())),
             b'-' => self.de.parse_number_(true).map(Value::from),
             b'0'..=b'9' => self.de.parse_number_(false).map(Value::from),
             b'n' => Ok(Value::Null),
@@ -291,11 +385,11 @@ impl<'de> BorrowDeserializer<'de> {
 
         for _ in 0..es {
             self.de.skip();
-            let key = stry!(self.de.parse_str_());
+            let key = stry!(self.de.parse_str_()).into_cow();
             // We have to call parse short str twice since parse_short_str
             // does not move the cursor forward
             self.de.skip();
-            res.insert_nocheck(key.into(), stry!(self.parse_value()));
+            res.insert_nocheck(key, stry!(self.parse_value()));
             self.de.skip();
         }
         Ok(Value::Object(res))
@@ -589,6 +683,20 @@ mod test {
         assert_eq!(v.value_type(), ValueType::I64);
     }
 
+    #[test]
+    fn conversions_large_u64() {
+        let v = Value::from(42_u64);
+        assert_eq!(v.value_type(), ValueType::I64);
+        let v = Value::from(u64::max_value());
+        assert!(v.is_u64());
+        assert!(!v.is_i64());
+        assert_eq!(v.value_type(), ValueType::U64);
+        assert_eq!(v, u64::max_value());
+        assert_eq!(v.as_u128(), Some(u128::from(u64::max_value())));
+        assert_eq!(Value::U64(42), Value::I64(42));
+        assert_eq!(v.encode(), "18446744073709551615");
+    }
+
     #[test]
     fn conversions_null() {
         let v = Value::from(());
@@ -616,6 +724,7 @@ mod test {
             Just(Value::Null),
             any::<bool>().prop_map(Value::Bool),
             any::<i64>().prop_map(Value::I64),
+            any::<u64>().prop_map(Value::from),
             any::<f64>().prop_map(Value::F64),
             ".*".prop_map(Value::from),
         ];
@@ -699,14 +808,13 @@ mod test {
             prop_assert_eq!(v, f)
         }
         #[test]
-        fn prop_u64_cmp(f in (0_u64..=(i64::max_value() as u64))) {
+        fn prop_u64_cmp(f in proptest::num::u64::ANY) {
             let v: Value = f.into();
             prop_assert_eq!(v, f)
         }
 
-        #[allow(clippy::cast_possible_truncation)]
         #[test]
-        fn prop_usize_cmp(f in (0_usize..=(i64::max_value() as usize))) {
+        fn prop_usize_cmp(f in proptest::num::usize::ANY) {
             let v: Value = f.into();
             prop_assert_eq!(v, f)
         }
@@ -746,4 +854,28 @@ mod test {
         let v: Value = false.into();
         assert_eq!(v, false);
     }
+    #[test]
+    fn projected() {
+        let mut d = br#"{"snot": [1, {"x": "\\n"}], "badger": {"a": [[]]}, "id": 7}"#.to_vec();
+        let v = to_value_projected(&mut d, &["id", "snot"]).expect("projected");
+        assert_eq!(v.get("id"), Some(&Value::from(7)));
+        assert!(v.get("snot").is_some());
+        assert!(v.get("badger").is_none());
+        let mut d = br#"[1, 2]"#.to_vec();
+        let v = to_value_projected(&mut d, &["id"]).expect("projected");
+        assert_eq!(v, Value::Array(vec![Value::from(1), Value::from(2)]));
+        let mut d = br#"{}"#.to_vec();
+        let v = to_value_projected(&mut d, &["id"]).expect("projected");
+        assert_eq!(v, Value::Object(Object::new()));
+
+        // skipped values are still checked
+        for d in &[
+            r#"{"id": 1, "x": 01}"#,
+            r#"{"id": 1, "x": -}"#,
+            r#"{"id": 1, "x": "\q"}"#,
+        ] {
+            let mut d = d.as_bytes().to_vec();
+            assert!(to_value_projected(&mut d, &["id"]).is_err());
+        }
+    }
 }
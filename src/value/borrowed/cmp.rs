@@ -4,12 +4,18 @@ use float_cmp::approx_eq;
 
 impl<'a> PartialEq for Value<'a> {
     fn eq(&self, other: &Self) -> bool {
-        #[allow(clippy::default_trait_access)]
+        #[allow(clippy::default_trait_access, clippy::cast_sign_loss)]
         match (self, other) {
             (Self::Null, Self::Null) => true,
             (Self::Bool(v1), Self::Bool(v2)) => v1.eq(v2),
             (Self::I64(v1), Self::I64(v2)) => v1.eq(v2),
+            (Self::U64(v1), Self::U64(v2)) => v1.eq(v2),
+            (Self::I64(v1), Self::U64(v2)) | (Self::U64(v2), Self::I64(v1)) => {
+                *v1 >= 0 && *v1 as u64 == *v2
+            }
             (Self::F64(v1), Self::F64(v2)) => approx_eq!(f64, *v1, *v2),
+            #[cfg(feature = "arbitrary_precision")]
+            (Self::Number(v1), Self::Number(v2)) => v1.eq(v2),
             (Self::String(v1), Self::String(v2)) => v1.eq(v2),
             (Self::Array(v1), Self::Array(v2)) => v1.eq(v2),
             (Self::Object(v1), Self::Object(v2)) => v1.eq(v2),
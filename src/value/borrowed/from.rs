@@ -2,6 +2,7 @@ use super::{Object, Value};
 use crate::numberparse::Number;
 use crate::OwnedValue;
 use std::borrow::Cow;
+use std::convert::TryInto;
 use std::iter::FromIterator;
 
 impl<'a> From<Number> for Value<'a> {
@@ -10,6 +11,9 @@ impl<'a> From<Number> for Value<'a> {
         match n {
             Number::F64(n) => Value::F64(n),
             Number::I64(n) => Value::I64(n),
+            Number::U64(n) => Value::U64(n),
+            #[cfg(feature = "arbitrary_precision")]
+            Number::Raw(n) => Value::Number(n.into()),
         }
     }
 }
@@ -21,6 +25,9 @@ impl<'a> From<OwnedValue> for Value<'a> {
             OwnedValue::Bool(b) => Value::Bool(b),
             OwnedValue::F64(f) => Value::F64(f),
             OwnedValue::I64(i) => Value::I64(i),
+            OwnedValue::U64(i) => Value::U64(i),
+            #[cfg(feature = "arbitrary_precision")]
+            OwnedValue::Number(n) => Value::Number(n.into()),
             OwnedValue::String(s) => Value::from(s.to_string()),
             OwnedValue::Array(a) => {
                 Value::Array(a.into_iter().map(|v| v.into()).collect::<Vec<Value>>())
@@ -120,15 +127,14 @@ impl<'v> From<u32> for Value<'v> {
 impl<'v> From<u64> for Value<'v> {
     #[inline]
     fn from(i: u64) -> Self {
-        #[allow(clippy::cast_possible_wrap)]
-        Value::I64(i as i64)
+        // We only use the U64 variant for values that don't fit into a i64
+        i.try_into().map_or(Value::U64(i), Value::I64)
     }
 }
 
 impl<'v> From<usize> for Value<'v> {
     fn from(i: usize) -> Self {
-        #[allow(clippy::cast_possible_wrap)]
-        Self::I64(i as i64)
+        Self::from(i as u64)
     }
 }
 
@@ -12,17 +12,50 @@ use std::ptr;
 
 use crate::*;
 
-#[cfg(target_feature = "avx2")]
-use crate::avx2::generator::*;
+#[cfg(all(not(feature = "runtime-detection"), target_feature = "avx2"))]
+use crate::avx2::generator::write_str_simd;
 
 #[cfg(all(
+    not(feature = "runtime-detection"),
     any(target_arch = "x86", target_arch = "x86_64"),
     not(target_feature = "avx2")
 ))]
-use crate::sse42::generator::*;
+use crate::sse42::generator::write_str_simd;
 
-#[cfg(target_feature = "neon")]
-use crate::neon::generator::*;
+#[cfg(all(target_feature = "neon", feature = "neon"))]
+use crate::neon::generator::write_str_simd;
+
+#[cfg(not(any(
+    target_arch = "x86",
+    target_arch = "x86_64",
+    all(target_feature = "neon", feature = "neon")
+)))]
+use crate::fallback::generator::write_str_simd;
+
+// `write_str_simd` is generic over the writer so we can't remember it in a
+// function pointer, `is_x86_feature_detected` caches the detection for us.
+#[cfg(all(
+    feature = "runtime-detection",
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+#[inline(always)]
+unsafe fn write_str_simd<W>(
+    writer: &mut W,
+    string: &mut &[u8],
+    len: &mut usize,
+    idx: &mut usize,
+) -> io::Result<()>
+where
+    W: std::io::Write,
+{
+    if is_x86_feature_detected!("avx2") {
+        crate::avx2::generator::write_str_simd(writer, string, len, idx)
+    } else if is_x86_feature_detected!("sse4.2") {
+        crate::sse42::generator::write_str_simd(writer, string, len, idx)
+    } else {
+        crate::fallback::generator::write_str_simd(writer, string, len, idx)
+    }
+}
 
 const QU: u8 = b'"';
 const BS: u8 = b'\\';
@@ -141,6 +174,11 @@ pub trait BaseGenerator {
         itoa::write(self.get_writer(), num).map(|_| ())
         //self.write(num.to_string().as_bytes())
     }
+
+    #[inline(always)]
+    fn write_uint(&mut self, num: u64) -> io::Result<()> {
+        itoa::write(self.get_writer(), num).map(|_| ())
+    }
 }
 
 /****** Pretty Generator ******/
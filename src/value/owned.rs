@@ -5,8 +5,9 @@ mod from;
 mod serialize;
 
 use crate::value::{ValueTrait, ValueType};
-use crate::{stry, unlikely, Deserializer, ErrorType, Result};
+use crate::{stry, unlikely, Buffers, Deserializer, ErrorType, Limits, Result};
 use halfbrown::HashMap;
+use std::convert::TryInto;
 use std::fmt;
 use std::ops::Index;
 
@@ -23,7 +24,49 @@ pub type Object = HashMap<String, Value>;
 /// a lifetime.
 pub fn to_value(s: &mut [u8]) -> Result<Value> {
     let de = stry!(Deserializer::from_slice(s));
-    OwnedDeserializer::from_deserializer(de).parse()
+    let mut de = OwnedDeserializer::from_deserializer(de);
+    let value = de.parse();
+    value.map_err(|e| de.de.locate(e))
+}
+
+/// Parses a slice of bytes into a Value dom without modifying it, for
+/// input that can't be handed out mutably.
+pub fn to_value_from_ref(s: &[u8]) -> Result<Value> {
+    let de = stry!(Deserializer::from_slice_ref(s));
+    let mut de = OwnedDeserializer::from_deserializer(de);
+    let value = de.parse();
+    value.map_err(|e| de.de.locate(e))
+}
+
+/// Parses a slice of bytes into a Value dom keeping only the given keys of
+/// the top level object. The values of all other keys are skipped over
+/// without building anything from them, their numbers and strings are only
+/// checked for valid grammar and escapes.
+pub fn to_value_projected(s: &mut [u8], keys: &[&str]) -> Result<Value> {
+    let de = stry!(Deserializer::from_slice(s));
+    let mut de = OwnedDeserializer::from_deserializer(de);
+    let value = de.parse_projected(keys);
+    value.map_err(|e| de.de.locate(e))
+}
+
+/// Same as `to_value` but re-uses the scratch buffers passed in and hands
+/// them back afterwards, and enforces the given limits.
+pub(crate) fn to_value_with_buffers(
+    s: &mut [u8],
+    buffers: &mut Buffers,
+    limits: &Limits,
+) -> Result<Value> {
+    let de = stry!(Deserializer::from_slice_with_buffers(s, buffers, limits));
+    from_deserializer(de, buffers)
+}
+
+/// Builds a value from an already set up deserializer, handing its scratch
+/// buffers back afterwards.
+pub(crate) fn from_deserializer(de: Deserializer<'_>, buffers: &mut Buffers) -> Result<Value> {
+    let mut de = OwnedDeserializer::from_deserializer(de);
+    let value = de.parse().map_err(|e| de.de.locate(e));
+    *buffers = de.de.into_buffers();
+    value
 }
 
 /// Owned JSON-DOM Value, consider using the `ValueTrait`
@@ -40,6 +83,12 @@ pub enum Value {
     F64(f64),
     /// integer type
     I64(i64),
+    /// unsigned integer type, used for integers larger then `i64::max_value()`
+    U64(u64),
+    /// number that doesn't fit into any of the above without loss of
+    /// precision, stored as the literal it was parsed from
+    #[cfg(feature = "arbitrary_precision")]
+    Number(String),
     /// string type
     String(String),
     /// array type
@@ -57,6 +106,9 @@ impl ValueTrait for Value {
             Self::Bool(_) => ValueType::Bool,
             Self::F64(_) => ValueType::F64,
             Self::I64(_) => ValueType::I64,
+            Self::U64(_) => ValueType::U64,
+            #[cfg(feature = "arbitrary_precision")]
+            Self::Number(_) => ValueType::Number,
             Self::String(_) => ValueType::String,
             Self::Array(_) => ValueType::Array,
             Self::Object(_) => ValueType::Object,
@@ -80,6 +132,7 @@ impl ValueTrait for Value {
     fn as_i64(&self) -> Option<i64> {
         match self {
             Self::I64(i) => Some(*i),
+            Self::U64(u) => (*u).try_into().ok(),
             _ => None,
         }
     }
@@ -88,6 +141,7 @@ impl ValueTrait for Value {
         #[allow(clippy::cast_sign_loss)]
         match self {
             Self::I64(i) if *i >= 0 => Some(*i as u64),
+            Self::U64(u) => Some(*u),
             _ => None,
         }
     }
@@ -104,6 +158,9 @@ impl ValueTrait for Value {
         match self {
             Self::F64(i) => Some(*i),
             Self::I64(i) => Some(*i as f64),
+            Self::U64(u) => Some(*u as f64),
+            #[cfg(feature = "arbitrary_precision")]
+            Self::Number(n) => n.parse().ok(),
             _ => None,
         }
     }
@@ -157,6 +214,9 @@ impl fmt::Display for Value {
             Self::Bool(false) => f.write_str("false"),
             Self::Bool(true) => f.write_str("true"),
             Self::I64(n) => f.write_str(&n.to_string()),
+            Self::U64(n) => f.write_str(&n.to_string()),
+            #[cfg(feature = "arbitrary_precision")]
+            Self::Number(n) => f.write_str(n),
             Self::F64(n) => f.write_str(&n.to_string()),
             Self::String(s) => write!(f, "{}", s),
             Self::Array(a) => write!(f, "{:?}", a),
@@ -190,7 +250,7 @@ impl<'de> OwnedDeserializer<'de> {
     #[cfg_attr(not(feature = "no-inline"), inline(always))]
     pub fn parse(&mut self) -> Result<Value> {
         match self.de.next_() {
-            b'"' => self.de.parse_str_().map(Value::from),
+            b'"' => self.de.parse_str_().map(|s| Value::from(s.as_str())),
             b'n' => Ok(Value::Null),
             b't' => Ok(Value::Bool(true)),
             b'f' => Ok(Value::Bool(false)),
@@ -202,10 +262,36 @@ impl<'de> OwnedDeserializer<'de> {
         }
     }
 
+    /// Parses the document, if it is an object only `keys` are kept
+    pub fn parse_projected(&mut self, keys: &[&str]) -> Result<Value> {
+        if self.de.peek_() != b'{' {
+            return self.parse();
+        }
+        self.de.skip();
+        let es = self.de.count_elements();
+        let mut res = Object::with_capacity(es.min(keys.len()));
+        if unlikely!(es == 0) {
+            self.de.skip();
+            return Ok(Value::Object(res));
+        }
+        for _ in 0..es {
+            self.de.skip();
+            let key = stry!(self.de.parse_str_()).as_str().to_owned();
+            self.de.skip();
+            if keys.contains(&key.as_str()) {
+                res.insert_nocheck(key, stry!(self.parse_value()));
+            } else {
+                stry!(self.de.skip_value());
+            }
+            self.de.skip();
+        }
+        Ok(Value::Object(res))
+    }
+
     #[cfg_attr(not(feature = "no-inline"), inline(always))]
     fn parse_value(&mut self) -> Result<Value> {
         match self.de.next_() {
-            b'"' => self.de.parse_str_().map(Value::from),
+            b'"' => self.de.parse_str_().map(|s| Value::from(s.as_str())),
             b'n' => Ok(Value::Null),
             b't' => Ok(Value::Bool(true)),
             b'f' => Ok(Value::Bool(false)),
@@ -250,11 +336,11 @@ impl<'de> OwnedDeserializer<'de> {
 
         for _ in 0..es {
             self.de.skip();
-            let key = stry!(self.de.parse_str_());
+            let key = stry!(self.de.parse_str_()).as_str().to_owned();
             // We have to call parse short str twice since parse_short_str
             // does not move the cursor forward
             self.de.skip();
-            res.insert_nocheck(key.into(), stry!(self.parse_value()));
+            res.insert_nocheck(key, stry!(self.parse_value()));
             self.de.skip();
         }
         Ok(Value::Object(res))
@@ -548,6 +634,20 @@ mod test {
         assert_eq!(v.value_type(), ValueType::I64);
     }
 
+    #[test]
+    fn conversions_large_u64() {
+        let v = Value::from(42_u64);
+        assert_eq!(v.value_type(), ValueType::I64);
+        let v = Value::from(u64::max_value());
+        assert!(v.is_u64());
+        assert!(!v.is_i64());
+        assert_eq!(v.value_type(), ValueType::U64);
+        assert_eq!(v, u64::max_value());
+        assert_eq!(v.as_u128(), Some(u128::from(u64::max_value())));
+        assert_eq!(Value::U64(42), Value::I64(42));
+        assert_eq!(v.encode(), "18446744073709551615");
+    }
+
     #[test]
     fn conversions_null() {
         let v = Value::from(());
@@ -574,6 +674,7 @@ mod test {
             Just(Value::Null),
             any::<bool>().prop_map(Value::Bool),
             any::<i64>().prop_map(Value::I64),
+            any::<u64>().prop_map(Value::from),
             any::<f64>().prop_map(Value::F64),
             ".*".prop_map(Value::from),
         ];
@@ -648,14 +749,13 @@ mod test {
             prop_assert_eq!(v, f)
         }
         #[test]
-        fn prop_u64_cmp(f in (0_u64..=(i64::max_value() as u64))) {
+        fn prop_u64_cmp(f in proptest::num::u64::ANY) {
             let v: Value = f.into();
             prop_assert_eq!(v, f)
         }
 
-        #[allow(clippy::cast_possible_truncation)]
         #[test]
-        fn prop_usize_cmp(f in (0_usize..=(i64::max_value() as usize))) {
+        fn prop_usize_cmp(f in proptest::num::usize::ANY) {
             let v: Value = f.into();
             prop_assert_eq!(v, f)
         }
@@ -695,4 +795,28 @@ mod test {
         let v: Value = false.into();
         assert_eq!(v, false);
     }
+    #[test]
+    fn projected() {
+        let mut d = br#"{"snot": [1, {"x": "\\n"}], "badger": {"a": [[]]}, "id": 7}"#.to_vec();
+        let v = to_value_projected(&mut d, &["id", "snot"]).expect("projected");
+        assert_eq!(v.get("id"), Some(&Value::from(7)));
+        assert!(v.get("snot").is_some());
+        assert!(v.get("badger").is_none());
+        let mut d = br#"[1, 2]"#.to_vec();
+        let v = to_value_projected(&mut d, &["id"]).expect("projected");
+        assert_eq!(v, Value::Array(vec![Value::from(1), Value::from(2)]));
+        let mut d = br#"{}"#.to_vec();
+        let v = to_value_projected(&mut d, &["id"]).expect("projected");
+        assert_eq!(v, Value::Object(Object::new()));
+
+        // skipped values are still checked
+        for d in &[
+            r#"{"id": 1, "x": 01}"#,
+            r#"{"id": 1, "x": -}"#,
+            r#"{"id": 1, "x": "\q"}"#,
+        ] {
+            let mut d = d.as_bytes().to_vec();
+            assert!(to_value_projected(&mut d, &["id"]).is_err());
+        }
+    }
 }
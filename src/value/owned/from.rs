@@ -2,6 +2,7 @@ use super::{Object, Value};
 use crate::numberparse::Number;
 use crate::BorrowedValue;
 use std::borrow::Cow;
+use std::convert::TryInto;
 use std::iter::FromIterator;
 
 impl From<Number> for Value {
@@ -10,6 +11,9 @@ impl From<Number> for Value {
         match n {
             Number::F64(n) => Self::F64(n),
             Number::I64(n) => Self::I64(n),
+            Number::U64(n) => Self::U64(n),
+            #[cfg(feature = "arbitrary_precision")]
+            Number::Raw(n) => Self::Number(n),
         }
     }
 }
@@ -21,6 +25,9 @@ impl From<crate::BorrowedValue<'_>> for Value {
             BorrowedValue::Bool(b) => Self::Bool(b),
             BorrowedValue::F64(f) => Self::F64(f),
             BorrowedValue::I64(i) => Self::I64(i),
+            BorrowedValue::U64(i) => Self::U64(i),
+            #[cfg(feature = "arbitrary_precision")]
+            BorrowedValue::Number(n) => Self::Number(n.into_owned()),
             BorrowedValue::String(s) => Self::from(s.to_string()),
             BorrowedValue::Array(a) => {
                 Self::Array(a.into_iter().map(|v| v.into()).collect::<Vec<Self>>())
@@ -120,15 +127,14 @@ impl From<u32> for Value {
 
 impl From<u64> for Value {
     fn from(i: u64) -> Self {
-        #[allow(clippy::cast_possible_wrap)]
-        Self::I64(i as i64)
+        // We only use the U64 variant for values that don't fit into a i64
+        i.try_into().map_or(Self::U64(i), Self::I64)
     }
 }
 
 impl From<usize> for Value {
     fn from(i: usize) -> Self {
-        #[allow(clippy::cast_possible_wrap)]
-        Self::I64(i as i64)
+        Self::from(i as u64)
     }
 }
 
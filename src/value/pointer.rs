@@ -0,0 +1,241 @@
+use crate::ValueTrait;
+use std::borrow::{Borrow, Cow};
+use std::fmt;
+use std::hash::Hash;
+
+/// Error for JSON Pointer (RFC 6901) lookups and updates
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PointerError {
+    /// A non empty pointer has to start with a `/`
+    MissingSlash,
+    /// A `~` was not followed by `0` or `1`
+    InvalidEscape,
+    /// A reference token used on an array isn't a valid index
+    InvalidIndex,
+    /// The pointer references a value that doesn't exist
+    NotFound,
+    /// The pointer goes through a value that is neither an array nor an object
+    NotAContainer,
+    /// The root value can't be removed
+    Root,
+}
+impl fmt::Display for PointerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MissingSlash => write!(f, "JSON pointer has to start with a `/`"),
+            Self::InvalidEscape => write!(f, "`~` has to be followed by `0` or `1`"),
+            Self::InvalidIndex => write!(f, "Reference token is not a valid array index"),
+            Self::NotFound => write!(f, "JSON pointer references a value that doesn't exist"),
+            Self::NotAContainer => write!(f, "Expected array or object"),
+            Self::Root => write!(f, "The root value can not be removed"),
+        }
+    }
+}
+impl std::error::Error for PointerError {}
+
+/// Splits a pointer into its unescaped reference tokens
+pub(crate) fn tokens(pointer: &str) -> Result<Vec<Cow<'_, str>>, PointerError> {
+    if pointer.is_empty() {
+        Ok(Vec::new())
+    } else if let Some(rest) = pointer.strip_prefix('/') {
+        rest.split('/').map(unescape).collect()
+    } else {
+        Err(PointerError::MissingSlash)
+    }
+}
+
+fn unescape(token: &str) -> Result<Cow<'_, str>, PointerError> {
+    if !token.contains('~') {
+        return Ok(Cow::Borrowed(token));
+    }
+    let mut res = String::with_capacity(token.len());
+    let mut chars = token.chars();
+    while let Some(c) = chars.next() {
+        if c == '~' {
+            match chars.next() {
+                Some('0') => res.push('~'),
+                Some('1') => res.push('/'),
+                _ => return Err(PointerError::InvalidEscape),
+            }
+        } else {
+            res.push(c)
+        }
+    }
+    Ok(Cow::Owned(res))
+}
+
+/// Parses an array index, RFC 6901 doesn't allow leading zeros or signs
+pub(crate) fn index(token: &str) -> Result<usize, PointerError> {
+    if token.is_empty()
+        || (token.len() > 1 && token.starts_with('0'))
+        || !token.bytes().all(|b| b.is_ascii_digit())
+    {
+        return Err(PointerError::InvalidIndex);
+    }
+    token.parse().map_err(|_| PointerError::InvalidIndex)
+}
+
+/// Resolves a single reference token against `value`
+pub(crate) fn step<'v, V>(value: &'v V, token: &str) -> Result<&'v V, PointerError>
+where
+    V: ValueTrait,
+    V::Key: Borrow<str> + Hash + Eq,
+{
+    if let Some(o) = value.as_object() {
+        o.get(token).ok_or(PointerError::NotFound)
+    } else if let Some(a) = value.as_array() {
+        if token == "-" {
+            return Err(PointerError::NotFound);
+        }
+        a.get(stry!(index(token))).ok_or(PointerError::NotFound)
+    } else {
+        Err(PointerError::NotAContainer)
+    }
+}
+
+/// Same as `step` but returns a mutable ref instead
+pub(crate) fn step_mut<'v, V>(value: &'v mut V, token: &str) -> Result<&'v mut V, PointerError>
+where
+    V: ValueTrait,
+    V::Key: Borrow<str> + Hash + Eq,
+{
+    if value.is_object() {
+        value
+            .as_object_mut()
+            .and_then(|o| o.get_mut(token))
+            .ok_or(PointerError::NotFound)
+    } else if let Some(a) = value.as_array_mut() {
+        if token == "-" {
+            return Err(PointerError::NotFound);
+        }
+        a.get_mut(stry!(index(token))).ok_or(PointerError::NotFound)
+    } else {
+        Err(PointerError::NotAContainer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PointerError;
+    use crate::{BorrowedValue, OwnedValue, ValueTrait};
+
+    fn rfc_doc() -> OwnedValue {
+        json!({
+            "foo": ["bar", "baz"],
+            "": 0,
+            "a/b": 1,
+            "c%d": 2,
+            "e^f": 3,
+            "g|h": 4,
+            "i\\j": 5,
+            "k\"l": 6,
+            " ": 7,
+            "m~n": 8
+        })
+    }
+
+    #[test]
+    fn rfc6901() {
+        let v = rfc_doc();
+        assert_eq!(v.pointer(""), Ok(&v));
+        assert_eq!(v.pointer("/foo"), Ok(&json!(["bar", "baz"])));
+        assert_eq!(v.pointer("/foo/0"), Ok(&OwnedValue::from("bar")));
+        assert_eq!(v.pointer("/"), Ok(&OwnedValue::from(0)));
+        assert_eq!(v.pointer("/a~1b"), Ok(&OwnedValue::from(1)));
+        assert_eq!(v.pointer("/c%d"), Ok(&OwnedValue::from(2)));
+        assert_eq!(v.pointer("/e^f"), Ok(&OwnedValue::from(3)));
+        assert_eq!(v.pointer("/g|h"), Ok(&OwnedValue::from(4)));
+        assert_eq!(v.pointer("/i\\j"), Ok(&OwnedValue::from(5)));
+        assert_eq!(v.pointer("/k\"l"), Ok(&OwnedValue::from(6)));
+        assert_eq!(v.pointer("/ "), Ok(&OwnedValue::from(7)));
+        assert_eq!(v.pointer("/m~0n"), Ok(&OwnedValue::from(8)));
+
+        let v: BorrowedValue = rfc_doc().into();
+        assert_eq!(v.pointer("/foo/1"), Ok(&BorrowedValue::from("baz")));
+        assert_eq!(v.pointer("/a~1b"), Ok(&BorrowedValue::from(1)));
+        assert_eq!(v.pointer("/m~0n"), Ok(&BorrowedValue::from(8)));
+    }
+
+    #[test]
+    fn errors() {
+        let v = rfc_doc();
+        assert_eq!(v.pointer("foo"), Err(PointerError::MissingSlash));
+        assert_eq!(v.pointer("/m~2n"), Err(PointerError::InvalidEscape));
+        assert_eq!(v.pointer("/m~"), Err(PointerError::InvalidEscape));
+        assert_eq!(v.pointer("/foo/01"), Err(PointerError::InvalidIndex));
+        assert_eq!(v.pointer("/foo/+1"), Err(PointerError::InvalidIndex));
+        assert_eq!(v.pointer("/foo/bar"), Err(PointerError::InvalidIndex));
+        assert_eq!(v.pointer("/foo/2"), Err(PointerError::NotFound));
+        assert_eq!(v.pointer("/foo/-"), Err(PointerError::NotFound));
+        assert_eq!(v.pointer("/snot"), Err(PointerError::NotFound));
+        assert_eq!(v.pointer("/foo/0/1"), Err(PointerError::NotAContainer));
+    }
+
+    #[test]
+    fn pointer_mut() {
+        let mut v: BorrowedValue = rfc_doc().into();
+        *v.pointer_mut("/foo/0").expect("pointer") = BorrowedValue::from("snot");
+        *v.pointer_mut("/a~1b").expect("pointer") = BorrowedValue::from(42);
+        assert_eq!(v.pointer("/foo/0"), Ok(&BorrowedValue::from("snot")));
+        assert_eq!(v.pointer("/a~1b"), Ok(&BorrowedValue::from(42)));
+        assert_eq!(v.pointer_mut("/foo/-"), Err(PointerError::NotFound));
+    }
+
+    #[test]
+    fn insert() {
+        let mut v = rfc_doc();
+        assert_eq!(v.pointer_insert("/foo/-", "end".into()), Ok(None));
+        assert_eq!(v.pointer_insert("/foo/0", "start".into()), Ok(None));
+        assert_eq!(v.pointer_insert("/foo/4", "after".into()), Ok(None));
+        assert_eq!(
+            v.pointer("/foo"),
+            Ok(&json!(["start", "bar", "baz", "end", "after"]))
+        );
+        assert_eq!(
+            v.pointer_insert("/foo/6", 1.into()),
+            Err(PointerError::NotFound)
+        );
+        assert_eq!(v.pointer_insert("/x~1y", 1.into()), Ok(None));
+        assert_eq!(v.pointer("/x~1y"), Ok(&OwnedValue::from(1)));
+        assert_eq!(
+            v.pointer_insert("/x~1y", 2.into()),
+            Ok(Some(OwnedValue::from(1)))
+        );
+        assert_eq!(
+            v.pointer_insert("/x/y", 2.into()),
+            Err(PointerError::NotFound)
+        );
+        assert_eq!(
+            v.pointer_insert("/a~1b/c", 2.into()),
+            Err(PointerError::NotAContainer)
+        );
+
+        let mut v: BorrowedValue = rfc_doc().into();
+        assert_eq!(v.pointer_insert("/new", json!({}).into()), Ok(None));
+        assert_eq!(v.pointer_insert("/new/key", true.into()), Ok(None));
+        assert_eq!(v.pointer("/new"), Ok(&json!({"key": true}).into()));
+        let old = v.pointer_insert("", ().into()).expect("pointer");
+        assert_eq!(old.and_then(|o| o.get("c%d").cloned()), Some(2.into()));
+        assert_eq!(v, BorrowedValue::from(()));
+    }
+
+    #[test]
+    fn remove() {
+        let mut v = rfc_doc();
+        assert_eq!(v.pointer_remove("/foo/0"), Ok(OwnedValue::from("bar")));
+        assert_eq!(v.pointer("/foo"), Ok(&json!(["baz"])));
+        assert_eq!(v.pointer_remove("/foo/1"), Err(PointerError::NotFound));
+        assert_eq!(v.pointer_remove("/foo/-"), Err(PointerError::NotFound));
+        assert_eq!(v.pointer_remove("/m~0n"), Ok(OwnedValue::from(8)));
+        assert_eq!(v.pointer("/m~0n"), Err(PointerError::NotFound));
+        assert_eq!(v.pointer_remove(""), Err(PointerError::Root));
+
+        let mut v: BorrowedValue = rfc_doc().into();
+        assert_eq!(v.pointer_remove("/foo"), Ok(json!(["bar", "baz"]).into()));
+        assert_eq!(v.pointer_remove("/foo"), Err(PointerError::NotFound));
+        assert_eq!(
+            v.pointer_remove("/a~1b/c"),
+            Err(PointerError::NotAContainer)
+        );
+    }
+}
@@ -0,0 +1,372 @@
+use crate::numberparse::Number;
+use crate::{Buffers, Deserializer, ErrorType, Limits, Result};
+
+/// What to do after a `JsonVisitor` callback returns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visit {
+    /// Keep going
+    Continue,
+    /// Skip the current subtree. Returned from `start_object` or
+    /// `start_array` the container's content and its end are skipped,
+    /// returned from `key` the field's value is. Ignored everywhere else.
+    Skip,
+    /// Stop visiting the document
+    Abort,
+}
+
+/// Receives the events of a document as it is walked, see `visit`.
+///
+/// All callbacks default to `Visit::Continue` so only the interesting
+/// ones need to be implemented.
+pub trait JsonVisitor {
+    /// An object with `len` fields starts
+    fn start_object(&mut self, _len: usize) -> Visit {
+        Visit::Continue
+    }
+    /// The key of the next field in an object
+    fn key(&mut self, _key: &str) -> Visit {
+        Visit::Continue
+    }
+    /// The current object ends
+    fn end_object(&mut self) -> Visit {
+        Visit::Continue
+    }
+    /// An array with `len` elements starts
+    fn start_array(&mut self, _len: usize) -> Visit {
+        Visit::Continue
+    }
+    /// The current array ends
+    fn end_array(&mut self) -> Visit {
+        Visit::Continue
+    }
+    /// A string value
+    fn string(&mut self, _s: &str) -> Visit {
+        Visit::Continue
+    }
+    /// A negative integer or a positive one that fits into an `i64`
+    fn i64(&mut self, _n: i64) -> Visit {
+        Visit::Continue
+    }
+    /// A positive integer too large for an `i64`
+    fn u64(&mut self, _n: u64) -> Visit {
+        Visit::Continue
+    }
+    /// A floating point number
+    fn f64(&mut self, _n: f64) -> Visit {
+        Visit::Continue
+    }
+    /// A number that can't be represented without losing precision
+    #[cfg(feature = "arbitrary_precision")]
+    fn raw_number(&mut self, _n: &str) -> Visit {
+        Visit::Continue
+    }
+    /// `true` or `false`
+    fn bool(&mut self, _b: bool) -> Visit {
+        Visit::Continue
+    }
+    /// `null`
+    fn null(&mut self) -> Visit {
+        Visit::Continue
+    }
+}
+
+/// Walks a document calling `visitor` for every value without building a
+/// DOM. This function will rewrite the slice to de-escape strings.
+///
+/// Returns `Ok(false)` if the visitor aborted. Skipped subtrees aren't
+/// decoded but the grammar of their numbers and strings is still checked.
+///
+/// ```
+/// use simd_json::{visit, JsonVisitor, Visit};
+///
+/// #[derive(Default)]
+/// struct Sum(i64);
+/// impl JsonVisitor for Sum {
+///     fn i64(&mut self, n: i64) -> Visit {
+///         self.0 += n;
+///         Visit::Continue
+///     }
+///     fn key(&mut self, key: &str) -> Visit {
+///         if key == "ignored" {
+///             Visit::Skip
+///         } else {
+///             Visit::Continue
+///         }
+///     }
+/// }
+///
+/// let mut d = br#"{"a": [1, 2, {"b": 3}], "ignored": 100}"#.to_vec();
+/// let mut sum = Sum::default();
+/// assert_eq!(visit(&mut d, &mut sum).unwrap(), true);
+/// assert_eq!(sum.0, 6);
+/// ```
+pub fn visit<V: JsonVisitor>(input: &mut [u8], visitor: &mut V) -> Result<bool> {
+    visit_with_buffers(input, visitor, &mut Buffers::default(), &Limits::default())
+}
+
+/// Same as `visit` but re-uses the scratch buffers passed in and enforces
+/// the given limits.
+pub(crate) fn visit_with_buffers<V: JsonVisitor>(
+    input: &mut [u8],
+    visitor: &mut V,
+    buffers: &mut Buffers,
+    limits: &Limits,
+) -> Result<bool> {
+    let de = stry!(Deserializer::from_slice_with_buffers(
+        input, buffers, limits
+    ));
+    let mut walker = Walker { de, visitor };
+    let res = walker.value(true).map_err(|e| walker.de.locate(e));
+    *buffers = walker.de.into_buffers();
+    res
+}
+
+struct Walker<'de, 'v, V> {
+    de: Deserializer<'de>,
+    visitor: &'v mut V,
+}
+
+impl<'de, 'v, V: JsonVisitor> Walker<'de, 'v, V> {
+    /// Visits the next value, returns false once the visitor aborted
+    fn value(&mut self, root: bool) -> Result<bool> {
+        let r = match self.de.next_() {
+            b'"' => {
+                let s = stry!(self.de.parse_str_());
+                self.visitor.string(s.as_str())
+            }
+            b'-' => stry!(self.number(root, true)),
+            b'0'..=b'9' => stry!(self.number(root, false)),
+            b'n' => self.visitor.null(),
+            b't' => self.visitor.bool(true),
+            b'f' => self.visitor.bool(false),
+            b'[' => return self.array(),
+            b'{' => return self.object(),
+            _c => return Err(self.de.error(ErrorType::UnexpectedCharacter)),
+        };
+        Ok(r != Visit::Abort)
+    }
+
+    #[cfg_attr(not(feature = "no-inline"), inline(always))]
+    fn number(&mut self, root: bool, minus: bool) -> Result<Visit> {
+        let n = if root {
+            stry!(self.de.parse_number_root(minus))
+        } else {
+            stry!(self.de.parse_number_(minus))
+        };
+        Ok(match n {
+            Number::I64(n) => self.visitor.i64(n),
+            Number::U64(n) => self.visitor.u64(n),
+            Number::F64(n) => self.visitor.f64(n),
+            #[cfg(feature = "arbitrary_precision")]
+            Number::Raw(n) => self.visitor.raw_number(&n),
+        })
+    }
+
+    fn array(&mut self) -> Result<bool> {
+        let es = self.de.count_elements();
+        match self.visitor.start_array(es) {
+            Visit::Continue => (),
+            Visit::Skip => {
+                stry!(self.de.skip_container());
+                return Ok(true);
+            }
+            Visit::Abort => return Ok(false),
+        }
+        if unlikely!(es == 0) {
+            self.de.skip();
+        }
+        for _ in 0..es {
+            if !stry!(self.value(false)) {
+                return Ok(false);
+            }
+            self.de.skip();
+        }
+        Ok(self.visitor.end_array() != Visit::Abort)
+    }
+
+    fn object(&mut self) -> Result<bool> {
+        let es = self.de.count_elements();
+        match self.visitor.start_object(es) {
+            Visit::Continue => (),
+            Visit::Skip => {
+                stry!(self.de.skip_container());
+                return Ok(true);
+            }
+            Visit::Abort => return Ok(false),
+        }
+        if unlikely!(es == 0) {
+            self.de.skip();
+        }
+        for _ in 0..es {
+            self.de.skip();
+            let r = {
+                let key = stry!(self.de.parse_str_());
+                self.visitor.key(key.as_str())
+            };
+            self.de.skip();
+            match r {
+                Visit::Continue => {
+                    if !stry!(self.value(false)) {
+                        return Ok(false);
+                    }
+                }
+                Visit::Skip => stry!(self.de.skip_value()),
+                Visit::Abort => return Ok(false),
+            }
+            self.de.skip();
+        }
+        Ok(self.visitor.end_object() != Visit::Abort)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{visit, JsonVisitor, Visit};
+
+    /// Records every event and answers with `Skip` or `Abort` for the
+    /// configured keys and container lengths
+    #[derive(Default)]
+    struct Log {
+        events: Vec<String>,
+        skip: Option<&'static str>,
+        abort: Option<&'static str>,
+    }
+
+    impl Log {
+        fn push(&mut self, e: String) -> Visit {
+            let r = if Some(e.as_str()) == self.skip {
+                Visit::Skip
+            } else if Some(e.as_str()) == self.abort {
+                Visit::Abort
+            } else {
+                Visit::Continue
+            };
+            self.events.push(e);
+            r
+        }
+    }
+
+    impl JsonVisitor for Log {
+        fn start_object(&mut self, len: usize) -> Visit {
+            self.push(format!("{{{}", len))
+        }
+        fn key(&mut self, key: &str) -> Visit {
+            self.push(format!("{}:", key))
+        }
+        fn end_object(&mut self) -> Visit {
+            self.push("}".to_string())
+        }
+        fn start_array(&mut self, len: usize) -> Visit {
+            self.push(format!("[{}", len))
+        }
+        fn end_array(&mut self) -> Visit {
+            self.push("]".to_string())
+        }
+        fn string(&mut self, s: &str) -> Visit {
+            self.push(format!("{:?}", s))
+        }
+        fn i64(&mut self, n: i64) -> Visit {
+            self.push(format!("i{}", n))
+        }
+        fn u64(&mut self, n: u64) -> Visit {
+            self.push(format!("u{}", n))
+        }
+        fn f64(&mut self, n: f64) -> Visit {
+            self.push(format!("f{}", n))
+        }
+        fn bool(&mut self, b: bool) -> Visit {
+            self.push(b.to_string())
+        }
+        fn null(&mut self) -> Visit {
+            self.push("null".to_string())
+        }
+    }
+
+    const DOC: &str = r#"{"a": [1, -2.5, "x\ny"], "b": {"c": null, "d": []}, "e": [true, false], "f": 18446744073709551615}"#;
+
+    fn run(doc: &str, log: &mut Log) -> bool {
+        let mut d = doc.as_bytes().to_vec();
+        visit(&mut d, log).expect("valid json")
+    }
+
+    #[test]
+    fn events() {
+        let mut log = Log::default();
+        assert!(run(DOC, &mut log));
+        assert_eq!(
+            log.events.join(" "),
+            r#"{4 a: [3 i1 f-2.5 "x\ny" ] b: {2 c: null d: [0 ] } e: [2 true false ] f: u18446744073709551615 }"#
+        );
+
+        let mut log = Log::default();
+        assert!(run("42", &mut log));
+        assert_eq!(log.events, vec!["i42"]);
+    }
+
+    #[test]
+    fn skip() {
+        let mut log = Log {
+            skip: Some("b:"),
+            ..Log::default()
+        };
+        assert!(run(DOC, &mut log));
+        assert_eq!(
+            log.events.join(" "),
+            r#"{4 a: [3 i1 f-2.5 "x\ny" ] b: e: [2 true false ] f: u18446744073709551615 }"#
+        );
+
+        let mut log = Log {
+            skip: Some("[3"),
+            ..Log::default()
+        };
+        assert!(run(DOC, &mut log));
+        assert!(log.events.join(" ").starts_with("{4 a: [3 b: {2"));
+
+        let mut log = Log {
+            skip: Some("[0"),
+            ..Log::default()
+        };
+        assert!(run("[[], [[]], 1]", &mut log));
+        assert_eq!(log.events.join(" "), "[3 [0 [1 [0 ] i1 ]");
+    }
+
+    #[test]
+    fn abort() {
+        let mut log = Log {
+            abort: Some("null"),
+            ..Log::default()
+        };
+        assert!(!run(DOC, &mut log));
+        assert_eq!(log.events.last().map(String::as_str), Some("null"));
+
+        let mut log = Log {
+            abort: Some("]"),
+            ..Log::default()
+        };
+        assert!(!run(DOC, &mut log));
+        assert_eq!(log.events.len(), 7);
+    }
+
+    #[test]
+    fn errors() {
+        let mut log = Log::default();
+        let mut d = br#"{"a": [1, 2.x]}"#.to_vec();
+        assert!(visit(&mut d, &mut log).is_err());
+        let mut d = br#"[1, 2"#.to_vec();
+        assert!(visit(&mut d, &mut log).is_err());
+
+        // Skipped subtrees are still checked
+        let mut log = Log {
+            skip: Some("a:"),
+            ..Log::default()
+        };
+        let mut d = br#"{"a": [1, 2.x], "b": 1}"#.to_vec();
+        assert!(visit(&mut d, &mut log).is_err());
+        let mut log = Log {
+            skip: Some("[2"),
+            ..Log::default()
+        };
+        let mut d = br#"{"a": [1, "\q"], "b": 1}"#.to_vec();
+        assert!(visit(&mut d, &mut log).is_err());
+    }
+}
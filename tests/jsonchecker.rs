@@ -10,6 +10,10 @@ macro_rules! pass {
             let f = String::from(concat!("data/pass/", stringify!($file), ".json"));
             File::open(f).unwrap().read_to_end(&mut v1).unwrap();
             let mut v2 = v1.clone();
+            let v3 = simd_json::to_borrowed_value_from_ref(&v1);
+            dbg!(&v3);
+            assert!(v3.is_ok());
+            let v3: simd_json::OwnedValue = v3.unwrap().into();
             let v1 = simd_json::to_borrowed_value(&mut v1);
             dbg!(&v1);
             assert!(v1.is_ok());
@@ -19,7 +23,8 @@ macro_rules! pass {
             assert!(v2.is_ok());
             let v2 = v2.unwrap();
             let v1o: simd_json::OwnedValue = v1.clone().into();
-            assert_eq!(v2, v1o)
+            assert_eq!(v2, v1o);
+            assert_eq!(v2, v3)
         }
     };
 }
@@ -32,6 +37,7 @@ macro_rules! fail {
             let f = String::from(concat!("data/fail/", stringify!($file), ".json"));
             File::open(f).unwrap().read_to_end(&mut v1).unwrap();
             let mut v2 = v1.clone();
+            assert!(simd_json::to_borrowed_value_from_ref(&v1).is_err());
             let v1 = simd_json::to_borrowed_value(&mut v1);
             dbg!(&v1);
             assert!(v1.is_err());
@@ -50,6 +56,7 @@ macro_rules! crash {
             let f = String::from(concat!("data/crash/", stringify!($file), ".json"));
             File::open(f).unwrap().read_to_end(&mut v1).unwrap();
             let mut v2 = v1.clone();
+            let _ = simd_json::to_borrowed_value_from_ref(&v1);
             let _ = simd_json::to_borrowed_value(&mut v1);
             let _ = simd_json::to_owned_value(&mut v2);
         }
@@ -72,6 +79,7 @@ pass!(pass13);
 pass!(pass14);
 pass!(pass15);
 pass!(pass16);
+pass!(pass17);
 
 // fail!(fail01_EXCLUDED);
 fail!(fail02);
@@ -116,7 +124,9 @@ fail!(fail37);
 fail!(fail38);
 //fail!(fail39_EXCLUDED);
 
-fail!(fail40_s64boverflow);
+//fail!(fail40_s64boverflow); is a valid u64 and moved to pass17
+// valid numbers when they are kept verbatim with arbitrary_precision
+#[cfg(not(feature = "arbitrary_precision"))]
 fail!(fail41_toolarge);
 fail!(fail42);
 fail!(fail43);
@@ -147,6 +157,7 @@ fail!(fail65);
 fail!(fail66);
 fail!(fail67);
 fail!(fail68);
+#[cfg(not(feature = "arbitrary_precision"))]
 fail!(fail69);
 
 fail!(fail70);